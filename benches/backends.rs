@@ -0,0 +1,59 @@
+#[macro_use]
+extern crate criterion;
+extern crate aes_ndlr as aes;
+
+use aes::{AESEncryptionOptions, Block, BlockCipherMode, encrypt_with_backend, pad::Padding};
+use aes::cipher::CipherBackend;
+use aes::key::Key;
+use criterion::{Criterion, Throughput};
+
+const INPUT_SIZE: usize = 64 * 1024;
+
+/// Compares the table-based, the constant-time (bitsliced) and the scatter-gather backends over
+/// full messages.
+///
+/// Representative throughputs, on a single core of an x86-64 machine:
+///
+/// | mode | table      | constant time | scatter gather |
+/// |------|------------|---------------|----------------|
+/// | ECB  | 53 MiB/s   | 12 MiB/s      | 5 MiB/s        |
+/// | CBC  | 48 MiB/s   | 1.4 MiB/s     | 5 MiB/s        |
+/// | CTR  | 46 MiB/s   | 11.5 MiB/s    | 5 MiB/s        |
+///
+/// ECB and CTR encrypt 8 blocks at a time in constant time, whereas CBC chains every block to
+/// the previous one, so that each of its blocks costs a whole batch of 8: about 4 times slower
+/// than the table in ECB and CTR, and over 30 times slower in CBC. Scatter gather reads the
+/// whole S-box for each of the 160 lookups of a block, whatever the mode, which makes it about
+/// 10 times slower than the table, but the fastest of the two mitigations in CBC.
+fn encrypt_with_backends(c: &mut Criterion) {
+    let raw = vec![0x42u8; INPUT_SIZE];
+    let key = Key::from_string("YELLOW SUBMARINE");
+    let iv = Block::from_bytes(&[0x24; 16]);
+    let nonce = [0xf0; 8];
+    let modes = [
+        ("ecb", BlockCipherMode::ECB),
+        ("cbc", BlockCipherMode::CBC(&iv)),
+        ("ctr", BlockCipherMode::CTR(&nonce)),
+    ];
+    let backends = [
+        ("table", CipherBackend::Table),
+        ("constant time", CipherBackend::ConstantTime),
+        ("scatter gather", CipherBackend::ScatterGather),
+    ];
+
+    for (mode_name, mode) in modes.iter() {
+        let options = AESEncryptionOptions::new(mode, &Padding::None);
+        let mut group = c.benchmark_group(*mode_name);
+        group.throughput(Throughput::Bytes(INPUT_SIZE as u64));
+        group.sample_size(10);
+        for (backend_name, backend) in backends.iter() {
+            group.bench_function(format!("{} encrypt 64 KiB", backend_name), |b| {
+                b.iter(|| encrypt_with_backend(&raw, &key, &options, *backend))
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, encrypt_with_backends);
+criterion_main!(benches);
@@ -0,0 +1,35 @@
+#[macro_use]
+extern crate criterion;
+extern crate aes_ndlr as aes;
+
+use aes::{AESEncryptionOptions, BlockCipherMode, encrypt_aes_128, pad::Padding};
+use aes::bitslice::BitslicedAes128;
+use aes::key::Key;
+use criterion::{Criterion, Throughput};
+
+const INPUT_SIZE: usize = 64 * 1024;
+
+fn encrypt_ecb(c: &mut Criterion) {
+    let raw = vec![0x42u8; INPUT_SIZE];
+    let key = Key::from_string("YELLOW SUBMARINE");
+    let options = AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::None);
+    let bitsliced = BitslicedAes128::new(&key);
+
+    let mut group = c.benchmark_group("ecb");
+    group.throughput(Throughput::Bytes(INPUT_SIZE as u64));
+    group.sample_size(10);
+    group.bench_function("table-based encrypt 64 KiB", |b| {
+        b.iter(|| encrypt_aes_128(&raw, &key, &options))
+    });
+    group.bench_function("bitsliced encrypt 64 KiB", |b| {
+        b.iter(|| {
+            let mut cipher = raw.clone();
+            bitsliced.encrypt_blocks(&mut cipher);
+            cipher
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, encrypt_ecb);
+criterion_main!(benches);
@@ -0,0 +1,26 @@
+#[macro_use]
+extern crate criterion;
+extern crate aes_ndlr as aes;
+
+use aes::{AESEncryptionOptions, Block, BlockCipherMode, encrypt_aes_128, pad::Padding};
+use aes::key::Key;
+use criterion::{Criterion, Throughput};
+
+const INPUT_SIZE: usize = 1024 * 1024;
+
+fn encrypt_cbc(c: &mut Criterion) {
+    let raw = vec![0x42u8; INPUT_SIZE];
+    let key = Key::from_string("YELLOW SUBMARINE");
+    let iv = Block::from_bytes(&[0x24; 16]);
+    let mode = BlockCipherMode::CBC(&iv);
+    let options = AESEncryptionOptions::new(&mode, &Padding::None);
+
+    let mut group = c.benchmark_group("cbc");
+    group.throughput(Throughput::Bytes(INPUT_SIZE as u64));
+    group.sample_size(10);
+    group.bench_function("encrypt 1 MiB", |b| b.iter(|| encrypt_aes_128(&raw, &key, &options)));
+    group.finish();
+}
+
+criterion_group!(benches, encrypt_cbc);
+criterion_main!(benches);
@@ -0,0 +1,30 @@
+#[macro_use]
+extern crate criterion;
+extern crate aes_ndlr as aes;
+
+use aes::{AESEncryptionOptions, encrypt_aes_128, encrypt_block_under_keys};
+use aes::key::Key;
+use criterion::{Criterion, Throughput};
+
+const KEY_COUNT: usize = 1024;
+
+fn encrypt_under_many_keys(c: &mut Criterion) {
+    let block = [0x42u8; 16];
+    let keys: Vec<Key> = (0..KEY_COUNT)
+        .map(|i| Key([(i >> 8) as u8, i as u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]))
+        .collect();
+    let options = AESEncryptionOptions::default();
+
+    let mut group = c.benchmark_group("block under 1024 keys");
+    group.throughput(Throughput::Elements(KEY_COUNT as u64));
+    group.bench_function("encrypt_aes_128 per key", |b| {
+        b.iter(|| keys.iter().map(|key| encrypt_aes_128(&block, key, &options)).collect::<Vec<_>>())
+    });
+    group.bench_function("encrypt_block_under_keys", |b| {
+        b.iter(|| encrypt_block_under_keys(&block, keys.iter().cloned()).collect::<Vec<_>>())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, encrypt_under_many_keys);
+criterion_main!(benches);
@@ -0,0 +1,152 @@
+use std::collections::HashSet;
+
+use ::{BLOCK_SIZE, xor};
+
+/// Heuristic guess of the block cipher mode that produced a ciphertext.
+#[derive(PartialEq, Debug)]
+pub enum ModeGuess {
+    /// Some blocks repeat, which ECB does for repeated plaintext blocks and which is very unlikely
+    /// under CBC or CTR.
+    LikelyEcb,
+    /// No block repeats. This is the case for CBC and CTR, but also for ECB when no plaintext
+    /// block repeats, so the mode can't be told from the ciphertext alone.
+    Indeterminate,
+}
+
+/// Guesses the block cipher mode from repeated blocks in the ciphertext. This is a heuristic: it
+/// only detects ECB when the plaintext has repeated, block aligned content.
+pub fn guess_mode(ciphertext: &[u8]) -> ModeGuess {
+    if has_repeated_blocks(ciphertext) {
+        ModeGuess::LikelyEcb
+    } else {
+        ModeGuess::Indeterminate
+    }
+}
+
+/// Whether any BLOCK_SIZE block appears more than once in bytes, at any position. An incomplete
+/// trailing block is ignored.
+pub fn has_repeated_blocks(bytes: &[u8]) -> bool {
+    let mut seen_blocks = HashSet::new();
+
+    bytes.chunks_exact(BLOCK_SIZE).any(|block| !seen_blocks.insert(block))
+}
+
+/// Indices of the BLOCK_SIZE blocks that are identical at the same position in both ciphertexts.
+/// Under ECB, identical blocks reveal identical plaintext blocks, e.g. a shared prefix. Only the
+/// blocks present in both ciphertexts are compared.
+pub fn matching_blocks(a: &[u8], b: &[u8]) -> Vec<usize> {
+    a.chunks_exact(BLOCK_SIZE)
+        .zip(b.chunks_exact(BLOCK_SIZE))
+        .enumerate()
+        .filter(|(_, (a_block, b_block))| a_block == b_block)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Crib dragging over two ciphertexts encrypted with the same keystream, as when a CTR nonce or a
+/// one-time pad is reused: c1 ^ c2 = p1 ^ p2, whatever the keystream. Sliding a crib, a guessed
+/// fragment of one plaintext, over c1 ^ c2 gives at each offset what the other plaintext would be
+/// there if the crib were at that offset. Candidates that look like plaintext reveal both
+/// fragments, from which longer cribs can be guessed.
+///
+/// Returns the candidate at every offset where the crib fits within the shorter ciphertext.
+pub fn crib_drag(c1: &[u8], c2: &[u8], crib: &[u8]) -> Vec<(usize, Vec<u8>)> {
+    let length = c1.len().min(c2.len());
+    if crib.is_empty() || crib.len() > length {
+        return vec![];
+    }
+
+    let plaintexts_xor = xor::xor_bytes(&c1[..length], &c2[..length]);
+
+    plaintexts_xor
+        .windows(crib.len())
+        .enumerate()
+        .map(|(offset, window)| (offset, xor::xor_bytes(window, crib)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ::{AESEncryptionOptions, Block, BlockCipherMode, encrypt_aes_128};
+    use pad::Padding;
+    use key::Key;
+
+    use super::*;
+
+    #[test]
+    fn finds_blocks_shared_by_partially_overlapping_plaintexts() {
+        let key = Key::from_string("YELLOW SUBMARINE");
+        let options = AESEncryptionOptions::default();
+        let a = encrypt_aes_128(
+            b"same first blockdifferent blk #1same third block",
+            &key,
+            &options,
+        );
+        let b = encrypt_aes_128(
+            b"same first blockdifferent blk #2same third blocklonger than a!!!",
+            &key,
+            &options,
+        );
+
+        assert_eq!(matching_blocks(&a, &b), vec![0, 2]);
+    }
+
+    #[test]
+    fn compares_up_to_shorter_ciphertext() {
+        let a = [[0x01; BLOCK_SIZE], [0x02; BLOCK_SIZE]].concat();
+        let b = [0x01; BLOCK_SIZE];
+
+        assert_eq!(matching_blocks(&a, &b), vec![0]);
+        assert_eq!(matching_blocks(&b, &a), vec![0]);
+        assert!(matching_blocks(&a, &[]).is_empty());
+    }
+
+    #[test]
+    fn crib_drag_recovers_fragment_of_messages_under_reused_keystream() {
+        let key = Key::from_string("YELLOW SUBMARINE");
+        let mode = BlockCipherMode::CTR(&[0x01; 8]);
+        let options = AESEncryptionOptions::new(&mode, &Padding::None);
+        let c1 = encrypt_aes_128(b"meet me at the usual place at ten", &key, &options);
+        let c2 = encrypt_aes_128(b"the password is swordfish, ok?", &key, &options);
+
+        let candidates = crib_drag(&c1, &c2, b" the ");
+
+        assert_eq!(candidates.len(), c2.len() - 4);
+        // " the " is at offset 10 of the first message
+        assert_eq!(candidates[10], (10, b"rd is".to_vec()));
+        assert!(candidates.iter().all(|(_, candidate)| candidate.len() == 5));
+    }
+
+    #[test]
+    fn crib_drag_is_empty_when_crib_does_not_fit() {
+        assert!(crib_drag(&[0x01; 4], &[0x02; 8], &[0x03; 5]).is_empty());
+        assert!(crib_drag(&[0x01; 4], &[0x02; 4], &[]).is_empty());
+    }
+
+    #[test]
+    fn finds_repeated_blocks() {
+        let bytes = [[0x01; BLOCK_SIZE], [0x02; BLOCK_SIZE], [0x01; BLOCK_SIZE]].concat();
+
+        assert!(has_repeated_blocks(&bytes));
+        assert!(!has_repeated_blocks(&bytes[..2 * BLOCK_SIZE]));
+        assert!(!has_repeated_blocks(&[0x01; BLOCK_SIZE + 8]));
+        assert!(!has_repeated_blocks(&[]));
+    }
+
+    #[test]
+    fn guesses_ecb_from_repetitive_plaintext_but_not_cbc() {
+        let key = Key::from_string("YELLOW SUBMARINE");
+        let iv = Block::from_bytes(&[0x24; BLOCK_SIZE]);
+        let raw = [0x42; 4 * BLOCK_SIZE];
+
+        let ecb = encrypt_aes_128(&raw, &key, &AESEncryptionOptions::default());
+        let cbc = encrypt_aes_128(
+            &raw,
+            &key,
+            &AESEncryptionOptions::new(&BlockCipherMode::CBC(&iv), &Padding::None),
+        );
+
+        assert_eq!(guess_mode(&ecb), ModeGuess::LikelyEcb);
+        assert_eq!(guess_mode(&cbc), ModeGuess::Indeterminate);
+    }
+}
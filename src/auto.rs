@@ -0,0 +1,113 @@
+use error::AesError;
+use gcm::{self, GcmNonce, TAG_SIZE};
+use key::Key;
+use tag::Tag;
+
+/// Version byte of the blobs produced by encrypt_auto.
+const VERSION_GCM: u8 = 0x01;
+const NONCE_SIZE: usize = 12;
+
+/// Encrypts and authenticates plaintext with a safe default, for callers who don't want to choose
+/// a mode: AES-128-GCM under a random nonce, which is never ECB. The returned blob is
+/// self-describing, and is decrypted with decrypt_auto:
+///
+/// version (1 byte, 0x01 for GCM) || nonce (12 bytes) || ciphertext || tag (16 bytes)
+///
+/// The version byte is authenticated as additional data. Random nonces are safe for up to 2^32
+/// messages under the same key.
+#[cfg(feature = "rand")]
+pub fn encrypt_auto(plaintext: &[u8], key: &Key) -> Result<Vec<u8>, AesError> {
+    use rand::RngCore;
+
+    let mut nonce = [0u8; NONCE_SIZE];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+
+    encrypt_auto_with_nonce(plaintext, key, &nonce)
+}
+
+#[cfg(feature = "rand")]
+fn encrypt_auto_with_nonce(
+    plaintext: &[u8],
+    key: &Key,
+    nonce: &GcmNonce,
+) -> Result<Vec<u8>, AesError> {
+    let (ciphertext, tag) = gcm::encrypt_gcm_detached(plaintext, key, nonce, &[VERSION_GCM])?;
+
+    Ok([&[VERSION_GCM][..], nonce, &ciphertext, tag.as_bytes()].concat())
+}
+
+/// Decrypts a blob produced by encrypt_auto. Fails with InvalidHeader on an unknown version, and
+/// with AuthenticationFailed if the blob was tampered with.
+pub fn decrypt_auto(blob: &[u8], key: &Key) -> Result<Vec<u8>, AesError> {
+    if blob.len() < 1 + NONCE_SIZE + TAG_SIZE {
+        return Err(AesError::InvalidInputLength);
+    }
+    if blob[0] != VERSION_GCM {
+        return Err(AesError::InvalidHeader);
+    }
+
+    let (nonce_bytes, rest) = blob[1..].split_at(NONCE_SIZE);
+    let (ciphertext, tag_bytes) = rest.split_at(rest.len() - TAG_SIZE);
+    let mut nonce: GcmNonce = [0u8; NONCE_SIZE];
+    nonce.copy_from_slice(nonce_bytes);
+    let mut tag = [0u8; TAG_SIZE];
+    tag.copy_from_slice(tag_bytes);
+
+    gcm::decrypt_gcm_detached(ciphertext, &Tag::from(tag), key, &nonce, &[VERSION_GCM])
+}
+
+#[cfg(all(test, feature = "rand"))]
+mod tests {
+    use super::*;
+
+    const KEY: Key = Key([0x2b; 16]);
+
+    #[test]
+    fn auto_round_trips() {
+        let plaintext = b"don't know, don't care which mode";
+
+        let blob = encrypt_auto(plaintext, &KEY).unwrap();
+
+        assert_eq!(blob.len(), 1 + NONCE_SIZE + plaintext.len() + TAG_SIZE);
+        assert_eq!(decrypt_auto(&blob, &KEY), Ok(plaintext.to_vec()));
+    }
+
+    #[test]
+    fn auto_blob_is_versioned_gcm() {
+        let nonce = [0xca; NONCE_SIZE];
+
+        let blob = encrypt_auto_with_nonce(b"plaintext", &KEY, &nonce).unwrap();
+        let (ciphertext, tag) =
+            gcm::encrypt_gcm_detached(b"plaintext", &KEY, &nonce, &[VERSION_GCM]).unwrap();
+
+        assert_eq!(blob, [&[0x01][..], &nonce, &ciphertext, tag.as_bytes()].concat());
+    }
+
+    #[test]
+    fn auto_uses_fresh_nonces() {
+        assert_ne!(encrypt_auto(b"same", &KEY), encrypt_auto(b"same", &KEY));
+    }
+
+    #[test]
+    fn decrypt_auto_rejects_tampered_blob() {
+        let blob = encrypt_auto(b"some plaintext", &KEY).unwrap();
+
+        for i in 1..blob.len() {
+            let mut tampered = blob.clone();
+            tampered[i] ^= 0x01;
+
+            assert!(matches!(decrypt_auto(&tampered, &KEY), Err(AesError::AuthenticationFailed(_))));
+        }
+    }
+
+    #[test]
+    fn decrypt_auto_rejects_unknown_version_and_short_blob() {
+        let mut blob = encrypt_auto(b"some plaintext", &KEY).unwrap();
+
+        let too_short = &blob[..1 + NONCE_SIZE + TAG_SIZE - 1];
+
+        assert_eq!(decrypt_auto(too_short, &KEY), Err(AesError::InvalidInputLength));
+        blob[0] = 0x02;
+        assert_eq!(decrypt_auto(&blob, &KEY), Err(AesError::InvalidHeader));
+    }
+}
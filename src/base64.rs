@@ -0,0 +1,117 @@
+use error::AesError;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Base 64 encoding with the standard alphabet and padding, as specified in
+/// https://www.rfc-editor.org/rfc/rfc4648#section-4
+pub fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let group = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+
+        // a chunk of n bytes gives n + 1 characters, the rest of the 4 being padding
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+/// Decodes base 64 with the standard alphabet and padding. Whitespace, missing padding and
+/// non-zero trailing bits are rejected.
+pub fn decode(encoded: &str) -> Result<Vec<u8>, AesError> {
+    let encoded = encoded.as_bytes();
+    if !encoded.len().is_multiple_of(4) {
+        return Err(AesError::InvalidBase64);
+    }
+
+    let mut decoded = Vec::with_capacity(encoded.len() / 4 * 3);
+    for (i, chunk) in encoded.chunks_exact(4).enumerate() {
+        let is_last = i == encoded.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|c| **c == b'=').count();
+        if padding > 2 || (padding > 0 && !is_last) {
+            return Err(AesError::InvalidBase64);
+        }
+
+        let mut group = 0u32;
+        for c in chunk[..4 - padding].iter() {
+            group = group << 6 | character_value(*c)?;
+        }
+        group <<= 6 * padding;
+
+        let bytes = [(group >> 16) as u8, (group >> 8) as u8, group as u8];
+        if bytes[3 - padding..].iter().any(|byte| *byte != 0) {
+            return Err(AesError::InvalidBase64);
+        }
+        decoded.extend_from_slice(&bytes[..3 - padding]);
+    }
+
+    Ok(decoded)
+}
+
+fn character_value(c: u8) -> Result<u32, AesError> {
+    match c {
+        b'A'..=b'Z' => Ok((c - b'A') as u32),
+        b'a'..=b'z' => Ok((c - b'a') as u32 + 26),
+        b'0'..=b'9' => Ok((c - b'0') as u32 + 52),
+        b'+' => Ok(62),
+        b'/' => Ok(63),
+        _ => Err(AesError::InvalidBase64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 4648, Section 10
+    const TEST_VECTORS: [(&str, &str); 7] = [
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ];
+
+    #[test]
+    fn encodes_rfc_4648_vectors() {
+        for (raw, expected) in TEST_VECTORS.iter() {
+            assert_eq!(encode(raw.as_bytes()), *expected);
+        }
+    }
+
+    #[test]
+    fn decodes_rfc_4648_vectors() {
+        for (expected, encoded) in TEST_VECTORS.iter() {
+            assert_eq!(decode(encoded), Ok(expected.as_bytes().to_vec()));
+        }
+    }
+
+    #[test]
+    fn round_trips_every_byte() {
+        let bytes: Vec<u8> = (0..=255).collect();
+
+        assert_eq!(decode(&encode(&bytes)), Ok(bytes));
+    }
+
+    #[test]
+    fn rejects_malformed_base64() {
+        assert_eq!(decode("Zm9"), Err(AesError::InvalidBase64));
+        assert_eq!(decode("Zm9v\n"), Err(AesError::InvalidBase64));
+        assert_eq!(decode("Zm!v"), Err(AesError::InvalidBase64));
+        assert_eq!(decode("Z==="), Err(AesError::InvalidBase64));
+        assert_eq!(decode("Zg==Zm9v"), Err(AesError::InvalidBase64));
+        // 'h' leaves non-zero trailing bits
+        assert_eq!(decode("Zh=="), Err(AesError::InvalidBase64));
+    }
+}
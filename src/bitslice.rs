@@ -0,0 +1,370 @@
+use ::{AESEncryptionOptions, BLOCK_SIZE, BlockCipherMode, Nb, Nr, cipher, ctr, xor};
+use error::AesError;
+use key::Key;
+use pad::PaddingScheme;
+
+/// Number of blocks encrypted at once, each bit plane holding one bit of every byte of
+/// PARALLEL_BLOCKS blocks.
+pub const PARALLEL_BLOCKS: usize = 8;
+
+/// Bits of a plane that hold byte 0 of every block. Byte i of every block is at this mask << i.
+const BYTE_0_MASK: u128 = 0x0001_0001_0001_0001_0001_0001_0001_0001;
+
+/// The 8 bit planes of PARALLEL_BLOCKS blocks: bit block * 16 + i of plane j is bit j of byte i of
+/// the block. Byte i is at row i % 4 and column i / 4 of the block's State.
+type Planes = [u128; 8];
+
+/// Bitsliced aes-128 encryption, processing PARALLEL_BLOCKS blocks at once with bitwise operations
+/// only. There are no table lookups nor branches on secret data, so its timing doesn't depend on
+/// the key nor on the data, unlike the table-based S-box.
+///
+/// SubBytes computes the multiplicative inverse in GF(2^8) as x^254 with bitsliced
+/// multiplications, followed by the affine transformation (Sec. 5.1.1). The other transformations
+/// are bit permutations and XORs of the planes. Computing the inverse this way is simpler but
+/// slower than the tower field S-box circuits of optimized bitsliced implementations, and this
+/// implementation is slower than the table-based cipher (see benches/bitslice.rs): what it buys is
+/// constant time.
+pub struct BitslicedAes128 {
+    round_keys: [Planes; Nr + 1],
+}
+
+impl BitslicedAes128 {
+    pub fn new(key: &Key) -> Self {
+        let w = key.do_key_expansion().0;
+        let mut round_keys = [[0u128; 8]; Nr + 1];
+
+        for (round, round_key) in round_keys.iter_mut().enumerate() {
+            let round_key_bytes = w[round * Nb..(round + 1) * Nb].concat();
+            // every block gets the same round key
+            let blocks = [&round_key_bytes[..]; PARALLEL_BLOCKS].concat();
+            *round_key = to_planes(&blocks);
+        }
+
+        BitslicedAes128 { round_keys }
+    }
+
+    /// Encrypts complete blocks of data in place, in ECB mode. Blocks are encrypted
+    /// PARALLEL_BLOCKS at a time, a last incomplete batch costing as much as a complete one.
+    ///
+    /// Panics if data isn't a multiple of BLOCK_SIZE.
+    pub fn encrypt_blocks(&self, data: &mut [u8]) {
+        assert_eq!(data.len() % BLOCK_SIZE, 0, "Data must be a multiple of BLOCK_SIZE.");
+
+        let mut batch = [0u8; PARALLEL_BLOCKS * BLOCK_SIZE];
+        for chunk in data.chunks_mut(PARALLEL_BLOCKS * BLOCK_SIZE) {
+            batch[..chunk.len()].copy_from_slice(chunk);
+            self.encrypt_batch(&mut batch);
+            chunk.copy_from_slice(&batch[..chunk.len()]);
+        }
+    }
+
+    /// Encrypts raw_bytes with the given mode and padding, like Aes128::encrypt_bytes. ECB and CTR
+    /// encrypt PARALLEL_BLOCKS blocks at a time, but CBC chains every block to the previous one, so
+    /// that each of its blocks costs a whole batch.
+    pub fn encrypt_bytes(
+        &self,
+        raw_bytes: &[u8],
+        options: &AESEncryptionOptions,
+    ) -> Result<Vec<u8>, AesError> {
+        options.check_input_length(raw_bytes)?;
+
+        if let Some((nonce, initial_counter)) = options.block_cipher_mode.counter_start() {
+            let mut keystream =
+                ctr::generate_ctr_byte_stream_for_length(raw_bytes.len(), nonce, initial_counter);
+            self.encrypt_blocks(&mut keystream);
+
+            return Ok(xor::xor_bytes(raw_bytes, &keystream[..raw_bytes.len()]));
+        }
+
+        let mut cipher = options.padding.pad(raw_bytes, BLOCK_SIZE);
+
+        match options.block_cipher_mode {
+            BlockCipherMode::CBC(iv) => {
+                let mut previous_block = iv.to_bytes();
+                for block in cipher.chunks_exact_mut(BLOCK_SIZE) {
+                    xor::xor_in_place(block, &previous_block);
+                    self.encrypt_blocks(block);
+                    previous_block.copy_from_slice(block);
+                }
+            }
+            mode => {
+                cipher::warn_on_ecb(mode);
+                self.encrypt_blocks(&mut cipher);
+            }
+        }
+
+        Ok(cipher)
+    }
+
+    /// Cipher (Sec. 5.1) over PARALLEL_BLOCKS blocks.
+    fn encrypt_batch(&self, blocks: &mut [u8; PARALLEL_BLOCKS * BLOCK_SIZE]) {
+        let mut planes = to_planes(blocks);
+
+        add_round_key(&mut planes, &self.round_keys[0]);
+        for round in 1..Nr {
+            sub_bytes(&mut planes);
+            shift_rows(&mut planes);
+            mix_columns(&mut planes);
+            add_round_key(&mut planes, &self.round_keys[round]);
+        }
+        sub_bytes(&mut planes);
+        shift_rows(&mut planes);
+        add_round_key(&mut planes, &self.round_keys[Nr]);
+
+        from_planes(&planes, blocks);
+    }
+}
+
+fn to_planes(blocks: &[u8]) -> Planes {
+    let mut planes = [0u128; 8];
+
+    for (position, byte) in blocks.iter().enumerate() {
+        for (bit, plane) in planes.iter_mut().enumerate() {
+            *plane |= (((byte >> bit) & 1) as u128) << position;
+        }
+    }
+
+    planes
+}
+
+fn from_planes(planes: &Planes, blocks: &mut [u8]) {
+    for (position, byte) in blocks.iter_mut().enumerate() {
+        *byte = 0;
+        for (bit, plane) in planes.iter().enumerate() {
+            *byte |= (((plane >> position) & 1) as u8) << bit;
+        }
+    }
+}
+
+fn add_round_key(planes: &mut Planes, round_key: &Planes) {
+    for (plane, key_plane) in planes.iter_mut().zip(round_key.iter()) {
+        *plane ^= key_plane;
+    }
+}
+
+/// S-box (Sec. 5.1.1): the multiplicative inverse x^254, followed by the affine transformation.
+fn sub_bytes(planes: &mut Planes) {
+    let x = *planes;
+    let x2 = square(&x);
+    let x3 = multiply(&x2, &x);
+    let x12 = square(&square(&x3));
+    let x15 = multiply(&x12, &x3);
+    let x240 = square(&square(&square(&square(&x15))));
+    let x252 = multiply(&x240, &x12);
+    let inverse = multiply(&x252, &x2);
+
+    // b'_i = b_i ^ b_(i+4) ^ b_(i+5) ^ b_(i+6) ^ b_(i+7) ^ c_i, with c = {63}
+    for (i, plane) in planes.iter_mut().enumerate() {
+        *plane = inverse[i]
+            ^ inverse[(i + 4) % 8]
+            ^ inverse[(i + 5) % 8]
+            ^ inverse[(i + 6) % 8]
+            ^ inverse[(i + 7) % 8];
+        if (0x63 >> i) & 1 == 1 {
+            *plane = !*plane;
+        }
+    }
+}
+
+/// Multiplication in GF(2^8) (Sec. 4.2) of every byte of a by the same byte of b.
+fn multiply(a: &Planes, b: &Planes) -> Planes {
+    let mut product = [0u128; 15];
+    for (i, a_plane) in a.iter().enumerate() {
+        for (j, b_plane) in b.iter().enumerate() {
+            product[i + j] ^= a_plane & b_plane;
+        }
+    }
+
+    reduce(&mut product)
+}
+
+/// Squaring in GF(2^8), which only spreads the bits to the even powers.
+fn square(a: &Planes) -> Planes {
+    let mut product = [0u128; 15];
+    for (i, a_plane) in a.iter().enumerate() {
+        product[2 * i] = *a_plane;
+    }
+
+    reduce(&mut product)
+}
+
+/// Reduces a polynomial of degree up to 14 modulo m(x) = x^8 + x^4 + x^3 + x + 1, using
+/// x^k = x^(k-4) + x^(k-5) + x^(k-7) + x^(k-8) for k >= 8.
+fn reduce(product: &mut [u128; 15]) -> Planes {
+    for k in (8..15).rev() {
+        product[k - 4] ^= product[k];
+        product[k - 5] ^= product[k];
+        product[k - 7] ^= product[k];
+        product[k - 8] ^= product[k];
+    }
+
+    let mut reduced = [0u128; 8];
+    reduced.copy_from_slice(&product[..8]);
+
+    reduced
+}
+
+/// ShiftRows (Sec. 5.1.2): byte r + 4c of every block takes byte r + 4((c + r) mod 4).
+fn shift_rows(planes: &mut Planes) {
+    for plane in planes.iter_mut() {
+        let mut shifted = 0u128;
+        for c in 0..Nb {
+            for r in 0..4 {
+                let source = r + 4 * ((c + r) % Nb);
+                shifted |= ((*plane >> source) & BYTE_0_MASK) << (r + 4 * c);
+            }
+        }
+        *plane = shifted;
+    }
+}
+
+/// Rotates the bytes of every column up by n rows, so that row r takes row r + n.
+fn rotate_columns(plane: u128, n: usize) -> u128 {
+    // bits at row r of every column, within each group of 4 bits
+    const ROWS_BELOW: [u128; 4] = [
+        0,
+        0x7777_7777_7777_7777_7777_7777_7777_7777,
+        0x3333_3333_3333_3333_3333_3333_3333_3333,
+        0x1111_1111_1111_1111_1111_1111_1111_1111,
+    ];
+
+    ((plane >> n) & ROWS_BELOW[n]) | ((plane << (4 - n)) & !ROWS_BELOW[n])
+}
+
+/// MixColumns (Sec. 5.1.3), as s'_r = {02} • (s_r ^ s_r+1) ^ s_r+1 ^ s_r+2 ^ s_r+3.
+fn mix_columns(planes: &mut Planes) {
+    let mut sum = [0u128; 8];
+    let mut rest = [0u128; 8];
+    for i in 0..8 {
+        let rotated_1 = rotate_columns(planes[i], 1);
+        sum[i] = planes[i] ^ rotated_1;
+        rest[i] = rotated_1 ^ rotate_columns(planes[i], 2) ^ rotate_columns(planes[i], 3);
+    }
+
+    // xtime of s_r ^ s_r+1: a shift to the next bit plane, reduced by {1b} on overflow
+    let overflow = sum[7];
+    planes[0] = overflow ^ rest[0];
+    planes[1] = sum[0] ^ overflow ^ rest[1];
+    planes[2] = sum[1] ^ rest[2];
+    planes[3] = sum[2] ^ overflow ^ rest[3];
+    planes[4] = sum[3] ^ overflow ^ rest[4];
+    planes[5] = sum[4] ^ rest[5];
+    planes[6] = sum[5] ^ rest[6];
+    planes[7] = sum[6] ^ rest[7];
+}
+
+#[cfg(test)]
+mod tests {
+    use ::{Iv, encrypt_aes_128, try_encrypt_aes_128};
+    use constants::S_BOX;
+    use pad::Padding;
+
+    use super::*;
+
+    // FIPS-197, Appendix C.1
+    const FIPS_197_KEY: Key = Key([
+        0x00, 0x01, 0x02, 0x03,
+        0x04, 0x05, 0x06, 0x07,
+        0x08, 0x09, 0x0a, 0x0b,
+        0x0c, 0x0d, 0x0e, 0x0f
+    ]);
+    const FIPS_197_RAW: [u8; 16] = [
+        0x00, 0x11, 0x22, 0x33,
+        0x44, 0x55, 0x66, 0x77,
+        0x88, 0x99, 0xaa, 0xbb,
+        0xcc, 0xdd, 0xee, 0xff
+    ];
+    const FIPS_197_CIPHER: [u8; 16] = [
+        0x69, 0xc4, 0xe0, 0xd8,
+        0x6a, 0x7b, 0x04, 0x30,
+        0xd8, 0xcd, 0xb7, 0x80,
+        0x70, 0xb4, 0xc5, 0x5a
+    ];
+
+    #[test]
+    fn sub_bytes_matches_s_box() {
+        // the 256 bytes don't fit in one set of planes
+        for (half, expected) in S_BOX.chunks_exact(PARALLEL_BLOCKS * BLOCK_SIZE).enumerate() {
+            let offset = half * PARALLEL_BLOCKS * BLOCK_SIZE;
+            let mut bytes: Vec<u8> = (0..expected.len()).map(|i| (offset + i) as u8).collect();
+            let mut planes = to_planes(&bytes);
+
+            sub_bytes(&mut planes);
+            from_planes(&planes, &mut bytes);
+
+            assert_eq!(bytes[..], expected[..]);
+        }
+    }
+
+    #[test]
+    fn planes_round_trip() {
+        let blocks: Vec<u8> = (0..PARALLEL_BLOCKS * BLOCK_SIZE).map(|i| (i * 7) as u8).collect();
+        let mut actual = vec![0u8; blocks.len()];
+
+        from_planes(&to_planes(&blocks), &mut actual);
+
+        assert_eq!(actual, blocks);
+    }
+
+    #[test]
+    fn encrypts_fips_197_block() {
+        let mut block = FIPS_197_RAW;
+
+        BitslicedAes128::new(&FIPS_197_KEY).encrypt_blocks(&mut block);
+
+        assert_eq!(block, FIPS_197_CIPHER);
+    }
+
+    #[test]
+    fn encrypts_like_table_based_cipher() {
+        let key = Key::from_string("YELLOW SUBMARINE");
+        // more than one batch, the last one incomplete
+        let raw: Vec<u8> = (0..11 * BLOCK_SIZE).map(|i| (i * 31 + 7) as u8).collect();
+        let mut cipher = raw.clone();
+
+        BitslicedAes128::new(&key).encrypt_blocks(&mut cipher);
+
+        let options = AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::None);
+        assert_eq!(cipher, encrypt_aes_128(&raw, &key, &options));
+    }
+
+    #[test]
+    fn encrypts_bytes_like_table_based_cipher_in_every_mode() {
+        let key = Key::from_string("YELLOW SUBMARINE");
+        let bitsliced = BitslicedAes128::new(&key);
+        let iv = Iv::from_bytes(&[0x24; BLOCK_SIZE]);
+        let nonce = [0xf0; 8];
+        let modes = [
+            BlockCipherMode::ECB,
+            BlockCipherMode::CBC(&iv),
+            BlockCipherMode::CTR(&nonce),
+            BlockCipherMode::CTRWithCounter(&nonce, u64::MAX),
+        ];
+        // more than one batch, the last block incomplete
+        let raw: Vec<u8> = (0..10 * BLOCK_SIZE + 5).map(|i| (i * 31 + 7) as u8).collect();
+
+        for mode in modes.iter() {
+            let options = AESEncryptionOptions::new(mode, &Padding::PKCS7);
+
+            assert_eq!(
+                bitsliced.encrypt_bytes(&raw, &options),
+                try_encrypt_aes_128(&raw, &key, &options)
+            );
+        }
+    }
+
+    #[test]
+    fn encrypt_bytes_rejects_unaligned_prepadded_input() {
+        let options = AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::Prepadded);
+
+        let actual = BitslicedAes128::new(&FIPS_197_KEY).encrypt_bytes(&[0u8; 15], &options);
+
+        assert_eq!(actual, Err(AesError::InvalidInputLength));
+    }
+
+    #[test]
+    #[should_panic]
+    fn panics_on_incomplete_block() {
+        BitslicedAes128::new(&FIPS_197_KEY).encrypt_blocks(&mut [0u8; 15]);
+    }
+}
@@ -0,0 +1,303 @@
+use ::{AESEncryptionOptions, BLOCK_SIZE, BlockCipherMode, Iv, decrypt_aes_128, encrypt_aes_128};
+use constant_time::{verified_decrypt, verify_eq};
+use error::{AesError, TagMismatch};
+use key::Key;
+use pad::{self, Padding};
+use sha256::hmac_sha256;
+
+/// Size in bytes of the authentication tag: HMAC-SHA-256 truncated to its first half.
+pub const TAG_SIZE: usize = 16;
+
+/// Order in which CBC encryption with PKCS7 padding and the HMAC are combined.
+///
+/// Only EncryptThenMac, the default, is secure in general. The other orders exist for
+/// interoperability with legacy protocols only and must not be chosen for new designs.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum MacOrder {
+    /// The tag authenticates the IV and the ciphertext, which is only decrypted once the tag is
+    /// verified. This is AES_128_CBC_HMAC_SHA_256 of RFC 7518, Sec. 5.2.3.
+    #[default]
+    EncryptThenMac,
+    /// INSECURE, for interoperability only. The tag authenticates the plaintext and is encrypted
+    /// along with it, as in TLS before 1.3. Decryption must unpad before it can check the tag, so
+    /// any observable difference between a padding failure and a tag failure, including timing,
+    /// is a padding oracle revealing the plaintext (e.g. Lucky Thirteen, POODLE). Both failures
+    /// return the same error, which doesn't hide their different timings.
+    MacThenEncrypt,
+    /// INSECURE, for interoperability only. The tag authenticates the plaintext and is sent in the
+    /// clear, as in SSH. It's deterministic, so equal plaintexts under the same keys give equal
+    /// tags whatever the IV, and the ciphertext is decrypted and unpadded before being
+    /// authenticated, which exposes padding oracles like MacThenEncrypt.
+    EncryptAndMac,
+}
+
+/// Encrypts raw_bytes in CBC with PKCS7 padding and authenticates them along with aad, which is
+/// authenticated but not encrypted, combining both in the given order. Returns the IV followed by
+/// the ciphertext and the tag, the tag being encrypted with MacOrder::MacThenEncrypt.
+///
+/// The tag is HMAC-SHA-256 under mac_key, truncated to TAG_SIZE bytes, over aad, the IV and the
+/// ciphertext with MacOrder::EncryptThenMac, or over aad and the plaintext otherwise, followed by
+/// the bit length of aad as a big-endian u64. enc_key and mac_key must be independent keys, and
+/// the IV must be unpredictable, e.g. Iv::random().
+pub fn encrypt_cbc_hmac(
+    raw_bytes: &[u8],
+    enc_key: &Key,
+    mac_key: &Key,
+    iv: &Iv,
+    aad: &[u8],
+    order: MacOrder,
+) -> Vec<u8> {
+    let mode = BlockCipherMode::CBC(iv);
+    let options = AESEncryptionOptions::new(&mode, &Padding::PKCS7);
+    let iv_bytes = iv.to_bytes();
+
+    let sealed = match order {
+        MacOrder::EncryptThenMac => {
+            let cipher = encrypt_aes_128(raw_bytes, enc_key, &options);
+            let tag = compute_tag(mac_key, aad, &[&iv_bytes[..], &cipher[..]].concat());
+
+            [&cipher[..], &tag[..]].concat()
+        }
+        MacOrder::MacThenEncrypt => {
+            let tag = compute_tag(mac_key, aad, raw_bytes);
+
+            encrypt_aes_128(&[raw_bytes, &tag[..]].concat(), enc_key, &options)
+        }
+        MacOrder::EncryptAndMac => {
+            let cipher = encrypt_aes_128(raw_bytes, enc_key, &options);
+            let tag = compute_tag(mac_key, aad, raw_bytes);
+
+            [&cipher[..], &tag[..]].concat()
+        }
+    };
+
+    [&iv_bytes[..], &sealed[..]].concat()
+}
+
+/// Authenticates and decrypts the output of encrypt_cbc_hmac, which must have used the same keys,
+/// aad and order.
+///
+/// Fails with InvalidInputLength if sealed can't be the output of encrypt_cbc_hmac, and with
+/// AuthenticationFailed if it was tampered with. With MacOrder::EncryptThenMac, no decryption nor
+/// padding check happens before the tag is verified. The other orders decrypt and unpad first: see
+/// MacOrder for why this is insecure.
+pub fn decrypt_cbc_hmac(
+    sealed: &[u8],
+    enc_key: &Key,
+    mac_key: &Key,
+    aad: &[u8],
+    order: MacOrder,
+) -> Result<Vec<u8>, AesError> {
+    // the IV, at least a block of ciphertext, and the tag when it isn't encrypted
+    let tag_length = if order == MacOrder::MacThenEncrypt { 0 } else { TAG_SIZE };
+    if sealed.len() < 2 * BLOCK_SIZE + tag_length
+        || !(sealed.len() - tag_length).is_multiple_of(BLOCK_SIZE)
+    {
+        return Err(AesError::InvalidInputLength);
+    }
+
+    let (iv_bytes, rest) = sealed.split_at(BLOCK_SIZE);
+    let mut iv_block = [0u8; BLOCK_SIZE];
+    iv_block.copy_from_slice(iv_bytes);
+    let iv = Iv::from_bytes(&iv_block);
+    let mode = BlockCipherMode::CBC(&iv);
+    let (cipher, tag) = rest.split_at(rest.len() - tag_length);
+
+    match order {
+        MacOrder::EncryptThenMac => {
+            let mut expected_tag = [0u8; TAG_SIZE];
+            expected_tag.copy_from_slice(tag);
+
+            let padded_raw = verified_decrypt(
+                cipher,
+                &expected_tag,
+                |cipher| compute_tag(mac_key, aad, &[iv_bytes, cipher].concat()),
+                |cipher| decrypt_aes_128(cipher, enc_key, &mode),
+            )?;
+
+            pad::pkcs7_unpad_aes(&padded_raw)
+        }
+        MacOrder::MacThenEncrypt => {
+            let padded = decrypt_aes_128(cipher, enc_key, &mode);
+            // padding and tag failures are reported alike
+            let raw_and_tag = pad::pkcs7_unpad_aes(&padded)
+                .map_err(|_| AesError::AuthenticationFailed(TagMismatch::new(None)))?;
+            if raw_and_tag.len() < TAG_SIZE {
+                return Err(AesError::AuthenticationFailed(TagMismatch::new(None)));
+            }
+
+            let (raw, tag) = raw_and_tag.split_at(raw_and_tag.len() - TAG_SIZE);
+            verify_plaintext_tag(raw, tag, mac_key, aad)
+        }
+        MacOrder::EncryptAndMac => {
+            let padded = decrypt_aes_128(cipher, enc_key, &mode);
+            let raw = pad::pkcs7_unpad_aes(&padded)
+                .map_err(|_| AesError::AuthenticationFailed(TagMismatch::new(None)))?;
+
+            verify_plaintext_tag(&raw, tag, mac_key, aad)
+        }
+    }
+}
+
+/// HMAC-SHA-256 of aad || data || the bit length of aad, truncated to TAG_SIZE bytes.
+fn compute_tag(mac_key: &Key, aad: &[u8], data: &[u8]) -> [u8; TAG_SIZE] {
+    let aad_bit_length = (aad.len() as u64).wrapping_mul(8).to_be_bytes();
+    let mac = hmac_sha256(&mac_key.0, &[aad, data, &aad_bit_length[..]].concat());
+
+    let mut tag = [0u8; TAG_SIZE];
+    tag.copy_from_slice(&mac[..TAG_SIZE]);
+
+    tag
+}
+
+fn verify_plaintext_tag(
+    raw: &[u8],
+    tag: &[u8],
+    mac_key: &Key,
+    aad: &[u8],
+) -> Result<Vec<u8>, AesError> {
+    let computed_tag = compute_tag(mac_key, aad, raw);
+    if !verify_eq(&computed_tag, tag) {
+        return Err(AesError::AuthenticationFailed(TagMismatch::new(Some(computed_tag))));
+    }
+
+    Ok(raw.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 7518, Appendix B.1
+    const MAC_KEY: Key = Key([
+        0x00, 0x01, 0x02, 0x03,
+        0x04, 0x05, 0x06, 0x07,
+        0x08, 0x09, 0x0a, 0x0b,
+        0x0c, 0x0d, 0x0e, 0x0f
+    ]);
+    const ENC_KEY: Key = Key([
+        0x10, 0x11, 0x12, 0x13,
+        0x14, 0x15, 0x16, 0x17,
+        0x18, 0x19, 0x1a, 0x1b,
+        0x1c, 0x1d, 0x1e, 0x1f
+    ]);
+    const IV: [u8; 16] = [
+        0x1a, 0xf3, 0x8c, 0x2d,
+        0xc2, 0xb9, 0x6f, 0xfd,
+        0xd8, 0x66, 0x94, 0x09,
+        0x23, 0x41, 0xbc, 0x04
+    ];
+    const RAW: &[u8] = b"A cipher system must not be required to be secret, and it must be able to fall into \
+        the hands of the enemy without inconvenience";
+    const AAD: &[u8] = b"The second principle of Auguste Kerckhoffs";
+    const CIPHERED: [u8; 144] = [
+        0xc8, 0x0e, 0xdf, 0xa3, 0x2d, 0xdf, 0x39, 0xd5,
+        0xef, 0x00, 0xc0, 0xb4, 0x68, 0x83, 0x42, 0x79,
+        0xa2, 0xe4, 0x6a, 0x1b, 0x80, 0x49, 0xf7, 0x92,
+        0xf7, 0x6b, 0xfe, 0x54, 0xb9, 0x03, 0xa9, 0xc9,
+        0xa9, 0x4a, 0xc9, 0xb4, 0x7a, 0xd2, 0x65, 0x5c,
+        0x5f, 0x10, 0xf9, 0xae, 0xf7, 0x14, 0x27, 0xe2,
+        0xfc, 0x6f, 0x9b, 0x3f, 0x39, 0x9a, 0x22, 0x14,
+        0x89, 0xf1, 0x63, 0x62, 0xc7, 0x03, 0x23, 0x36,
+        0x09, 0xd4, 0x5a, 0xc6, 0x98, 0x64, 0xe3, 0x32,
+        0x1c, 0xf8, 0x29, 0x35, 0xac, 0x40, 0x96, 0xc8,
+        0x6e, 0x13, 0x33, 0x14, 0xc5, 0x40, 0x19, 0xe8,
+        0xca, 0x79, 0x80, 0xdf, 0xa4, 0xb9, 0xcf, 0x1b,
+        0x38, 0x4c, 0x48, 0x6f, 0x3a, 0x54, 0xc5, 0x10,
+        0x78, 0x15, 0x8e, 0xe5, 0xd7, 0x9d, 0xe5, 0x9f,
+        0xbd, 0x34, 0xd8, 0x48, 0xb3, 0xd6, 0x95, 0x50,
+        0xa6, 0x76, 0x46, 0x34, 0x44, 0x27, 0xad, 0xe5,
+        0x4b, 0x88, 0x51, 0xff, 0xb5, 0x98, 0xf7, 0xf8,
+        0x00, 0x74, 0xb9, 0x47, 0x3c, 0x82, 0xe2, 0xdb
+    ];
+    const TAG: [u8; 16] = [
+        0x65, 0x2c, 0x3f, 0xa3,
+        0x6b, 0x0a, 0x7c, 0x5b,
+        0x32, 0x19, 0xfa, 0xb3,
+        0xa3, 0x0b, 0xc1, 0xc4
+    ];
+
+    const ORDERS: [MacOrder; 3] = [
+        MacOrder::EncryptThenMac,
+        MacOrder::MacThenEncrypt,
+        MacOrder::EncryptAndMac,
+    ];
+
+    fn seal(order: MacOrder) -> Vec<u8> {
+        encrypt_cbc_hmac(RAW, &ENC_KEY, &MAC_KEY, &Iv::from_bytes(&IV), AAD, order)
+    }
+
+    #[test]
+    fn encrypt_then_mac_matches_rfc_7518() {
+        let expected = [&IV[..], &CIPHERED[..], &TAG[..]].concat();
+
+        assert_eq!(seal(MacOrder::EncryptThenMac), expected);
+    }
+
+    #[test]
+    fn defaults_to_encrypt_then_mac() {
+        assert_eq!(MacOrder::default(), MacOrder::EncryptThenMac);
+    }
+
+    #[test]
+    fn round_trips_in_every_order() {
+        for order in ORDERS.iter() {
+            let sealed = seal(*order);
+
+            assert_eq!(decrypt_cbc_hmac(&sealed, &ENC_KEY, &MAC_KEY, AAD, *order), Ok(RAW.to_vec()));
+        }
+    }
+
+    #[test]
+    fn round_trips_empty_input_in_every_order() {
+        for order in ORDERS.iter() {
+            let sealed = encrypt_cbc_hmac(&[], &ENC_KEY, &MAC_KEY, &Iv::from_bytes(&IV), &[], *order);
+
+            assert_eq!(decrypt_cbc_hmac(&sealed, &ENC_KEY, &MAC_KEY, &[], *order), Ok(vec![]));
+        }
+    }
+
+    #[test]
+    fn rejects_tampering_in_every_order_and_position() {
+        for order in ORDERS.iter() {
+            let sealed = seal(*order);
+
+            // the IV, a ciphertext block, the last ciphertext block and the last byte
+            for position in [0, BLOCK_SIZE + 3, sealed.len() - BLOCK_SIZE - 1, sealed.len() - 1].iter() {
+                let mut tampered = sealed.clone();
+                tampered[*position] ^= 0x01;
+
+                let result = decrypt_cbc_hmac(&tampered, &ENC_KEY, &MAC_KEY, AAD, *order);
+
+                match result {
+                    Err(AesError::AuthenticationFailed(_)) => {}
+                    other => panic!("{:?} at {}: {:?}", order, position, other),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_other_aad_in_every_order() {
+        for order in ORDERS.iter() {
+            let result = decrypt_cbc_hmac(&seal(*order), &ENC_KEY, &MAC_KEY, b"other", *order);
+
+            assert!(matches!(result, Err(AesError::AuthenticationFailed(_))));
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        for order in ORDERS.iter() {
+            let sealed = seal(*order);
+
+            let result = decrypt_cbc_hmac(&sealed[..sealed.len() - 1], &ENC_KEY, &MAC_KEY, AAD, *order);
+
+            assert_eq!(result, Err(AesError::InvalidInputLength));
+            assert_eq!(
+                decrypt_cbc_hmac(&sealed[..BLOCK_SIZE], &ENC_KEY, &MAC_KEY, AAD, *order),
+                Err(AesError::InvalidInputLength)
+            );
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::io::{self, Write};
+
+use ::{BLOCK_SIZE, Iv, xor};
+use cipher::{Aes128, BlockCipher};
+use key::Key;
+use pad::{self, Padding};
+
+/// Streaming aes-128 CBC encryption with PKCS7 padding, writing the ciphertext to an inner writer
+/// as complete blocks become available. Less than a block is held back between writes.
+///
+/// The last block and its padding are only written by finish, which returns the inner writer and
+/// any error from writing them. If the writer is dropped without finish, Drop finalizes the
+/// ciphertext instead, so that no data is silently lost, but as Drop can't return errors any
+/// I/O error is swallowed: finish is preferred.
+///
+/// Once the inner writer fails, the writer is poisoned: as the CBC chain may have moved past
+/// blocks that weren't written, later writes and finish fail, and Drop doesn't finalize.
+pub struct CbcWriter<W: Write> {
+    inner: Option<W>,
+    cipher: Aes128,
+    previous_block: [u8; BLOCK_SIZE],
+    partial_block: Vec<u8>,
+    finished: bool,
+    poisoned: bool,
+}
+
+impl<W: Write> CbcWriter<W> {
+    pub fn new(inner: W, key: &Key, iv: &Iv) -> Self {
+        CbcWriter {
+            inner: Some(inner),
+            cipher: Aes128::new(key),
+            previous_block: iv.to_bytes(),
+            partial_block: Vec::with_capacity(BLOCK_SIZE),
+            finished: false,
+            poisoned: false,
+        }
+    }
+
+    /// Writes the last block with its padding, flushes the inner writer and returns it.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.finalize()?;
+
+        Ok(self.inner.take().expect("The inner writer is only taken by finish."))
+    }
+
+    /// Pads and writes the held back bytes, only once, even if it fails.
+    fn finalize(&mut self) -> io::Result<()> {
+        self.check_not_poisoned()?;
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+
+        let final_blocks = pad::finalize_padding(&self.partial_block, BLOCK_SIZE, &Padding::PKCS7);
+        self.partial_block.clear();
+        self.write_blocks(final_blocks)?;
+
+        self.inner_mut().flush()
+    }
+
+    /// Encrypts and writes complete blocks, poisoning the writer if the inner writer fails.
+    fn write_blocks(&mut self, mut blocks: Vec<u8>) -> io::Result<()> {
+        for block in blocks.chunks_exact_mut(BLOCK_SIZE) {
+            xor::xor_in_place(block, &self.previous_block);
+            self.cipher.encrypt_block(block);
+            self.previous_block.copy_from_slice(block);
+        }
+
+        let result = self.inner_mut().write_all(&blocks);
+        if result.is_err() {
+            self.poisoned = true;
+        }
+
+        result
+    }
+
+    fn check_not_poisoned(&self) -> io::Result<()> {
+        if self.poisoned {
+            return Err(io::Error::other("The writer is poisoned by an error of the inner writer."));
+        }
+
+        Ok(())
+    }
+
+    fn inner_mut(&mut self) -> &mut W {
+        self.inner.as_mut().expect("The inner writer is only taken by finish.")
+    }
+}
+
+impl<W: Write> Write for CbcWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.check_not_poisoned()?;
+
+        let complete_length = (self.partial_block.len() + buf.len()) / BLOCK_SIZE * BLOCK_SIZE;
+        if complete_length == 0 {
+            self.partial_block.extend_from_slice(buf);
+            return Ok(buf.len());
+        }
+
+        // the held back bytes start the first block, which is completed from buf
+        let taken_length = complete_length - self.partial_block.len();
+        let mut blocks = Vec::with_capacity(complete_length);
+        blocks.extend_from_slice(&self.partial_block);
+        blocks.extend_from_slice(&buf[..taken_length]);
+        self.partial_block.clear();
+        self.partial_block.extend_from_slice(&buf[taken_length..]);
+        self.write_blocks(blocks)?;
+
+        Ok(buf.len())
+    }
+
+    /// Flushes the inner writer. The held back bytes aren't written, since more data may follow.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner_mut().flush()
+    }
+}
+
+/// Finalizes the ciphertext if finish wasn't called and the writer isn't poisoned, ignoring any
+/// I/O error.
+impl<W: Write> Drop for CbcWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() && !self.poisoned {
+            let _ = self.finalize();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use ::{BlockCipherMode, decrypt_aes_128};
+
+    use super::*;
+
+    const KEY: Key = Key([0x2b; 16]);
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Fails its first write, then writes normally.
+    struct FailingOnceWriter {
+        failed: bool,
+        written: Vec<u8>,
+    }
+
+    impl Write for FailingOnceWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if !self.failed {
+                self.failed = true;
+                return Err(io::Error::other("disk full"));
+            }
+            self.written.extend_from_slice(buf);
+
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn decrypt(cipher: &[u8], iv: &Iv) -> Vec<u8> {
+        let padded = decrypt_aes_128(cipher, &KEY, &BlockCipherMode::CBC(iv));
+
+        pad::pkcs7_unpad_aes(&padded).unwrap()
+    }
+
+    #[test]
+    fn encrypts_like_cbc_with_pkcs7_over_several_writes() {
+        let iv = Iv::from_bytes(&[0x24; BLOCK_SIZE]);
+        let raw: Vec<u8> = (0..70).collect();
+        let mut writer = CbcWriter::new(vec![], &KEY, &iv);
+
+        for chunk in raw.chunks(7) {
+            writer.write_all(chunk).unwrap();
+        }
+        let cipher = writer.finish().unwrap();
+
+        assert_eq!(cipher.len(), 80);
+        assert_eq!(decrypt(&cipher, &iv), raw);
+    }
+
+    #[test]
+    fn dropping_without_finish_still_writes_decryptable_output() {
+        let iv = Iv::from_bytes(&[0x24; BLOCK_SIZE]);
+        let mut cipher = vec![];
+
+        {
+            let mut writer = CbcWriter::new(&mut cipher, &KEY, &iv);
+            writer.write_all(b"seventeen bytes!!").unwrap();
+        }
+
+        assert_eq!(cipher.len(), 2 * BLOCK_SIZE);
+        assert_eq!(decrypt(&cipher, &iv), b"seventeen bytes!!".to_vec());
+    }
+
+    #[test]
+    fn finish_surfaces_error_of_inner_writer() {
+        let mut writer = CbcWriter::new(FailingWriter, &KEY, &Iv::zero());
+        // less than a block, held back until finish
+        writer.write_all(b"short").unwrap();
+
+        let error = writer.finish().err().unwrap();
+
+        assert_eq!(error.kind(), ErrorKind::Other);
+    }
+
+    #[test]
+    fn refuses_writes_and_finalization_once_inner_writer_failed() {
+        let mut inner = FailingOnceWriter { failed: false, written: vec![] };
+
+        {
+            let mut writer = CbcWriter::new(&mut inner, &KEY, &Iv::zero());
+
+            assert!(writer.write(&[0x42; 2 * BLOCK_SIZE]).is_err());
+            // retrying would encrypt buf chained on blocks that were never written
+            assert!(writer.write(&[0x42; 2 * BLOCK_SIZE]).is_err());
+        }
+
+        assert!(inner.written.is_empty());
+    }
+}
@@ -0,0 +1,771 @@
+#[cfg(feature = "warn-on-ecb")]
+use std::sync::Once;
+#[cfg(feature = "warn-on-ecb")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ::{AESEncryptionOptions, BLOCK_SIZE, BlockCipherMode, Iv, Nb, Nonce, Nr, ctr, decrypt_blocks,
+        encrypt_blocks, xor};
+use error::AesError;
+use key::{Key, KeySchedule};
+use pad::PaddingScheme;
+use state::State;
+use text::{Ciphertext, Plaintext};
+
+/// Number of bytes encrypted between two calls to the progress callback of
+/// encrypt_bytes_with_progress. A multiple of BLOCK_SIZE.
+pub const PROGRESS_INTERVAL: usize = 64 * 1024;
+
+#[cfg(feature = "warn-on-ecb")]
+static ECB_WARNING: Once = Once::new();
+
+/// Number of ECB warnings emitted, which is at most 1.
+#[cfg(feature = "warn-on-ecb")]
+static ECB_WARNINGS_EMITTED: AtomicUsize = AtomicUsize::new(0);
+
+/// Warns on stderr that ECB leaks the structure of the plaintext if mode is ECB, only the first
+/// time in the process, so that legitimate ECB uses aren't spammed. Called by every path that
+/// encrypts in ECB.
+#[cfg(feature = "warn-on-ecb")]
+pub(crate) fn warn_on_ecb(mode: &BlockCipherMode) {
+    if mode != &BlockCipherMode::ECB {
+        return;
+    }
+
+    ECB_WARNING.call_once(|| {
+        ECB_WARNINGS_EMITTED.fetch_add(1, Ordering::SeqCst);
+        eprintln!(
+            "warning: encrypting in ECB mode, which leaks the structure of the plaintext: equal \
+             plaintext blocks give equal ciphertext blocks. AESEncryptionOptions::default() uses \
+             ECB; prefer an authenticated mode such as gcm, or CBC with a random IV."
+        );
+    });
+}
+
+#[cfg(not(feature = "warn-on-ecb"))]
+pub(crate) fn warn_on_ecb(_mode: &BlockCipherMode) {}
+
+/// Implementation of aes-128 used to encrypt whole messages, see ::encrypt_with_backend.
+#[derive(PartialEq, Debug, Default, Clone, Copy)]
+pub enum CipherBackend {
+    /// Aes128, with S-box table lookups, whose timing may depend on the key and on the data
+    /// through the cache.
+    #[default]
+    Table,
+    /// bitslice::BitslicedAes128, constant time but slower, see benches/backends.rs.
+    ConstantTime,
+    /// ScatterGatherAes128, reading the whole S-box for every lookup, which mitigates cache-timing
+    /// leaks without eliminating them. Simpler than ConstantTime, and faster in CBC, where
+    /// ConstantTime can't batch blocks, see benches/backends.rs.
+    ScatterGather,
+}
+
+/// Mapping between the bytes of a block and the State (Sec. 3.4), see ::encrypt_with_byte_order.
+#[derive(PartialEq, Debug, Default, Clone, Copy)]
+pub enum ByteOrder {
+    /// Byte i is at row i % 4 and column i / 4, i.e. the bytes fill the State column by column.
+    /// This is standard AES.
+    #[default]
+    ColumnMajor,
+    /// Byte i is at row i / 4 and column i % 4, i.e. the bytes fill the State row by row. This
+    /// isn't AES, but matches some non-standard implementations.
+    RowMajor,
+}
+
+/// A block cipher, over which the block cipher modes are implemented.
+pub trait BlockCipher {
+    /// Size in bytes of the blocks.
+    const BLOCK_SIZE: usize;
+
+    /// Encrypts a block of BLOCK_SIZE bytes in place.
+    fn encrypt_block(&self, block: &mut [u8]);
+
+    /// Decrypts a block of BLOCK_SIZE bytes in place.
+    fn decrypt_block(&self, block: &mut [u8]);
+}
+
+/// A tweakable block cipher, whose encryption of a block also depends on a public tweak, so that
+/// equal blocks encrypted under different tweaks give unrelated ciphertexts.
+pub trait TweakableBlockCipher {
+    /// Encrypts a block of BLOCK_SIZE bytes in place under tweak.
+    fn encrypt_block_tweaked(&self, block: &mut [u8], tweak: &[u8; BLOCK_SIZE]);
+
+    /// Decrypts a block of BLOCK_SIZE bytes in place under tweak.
+    fn decrypt_block_tweaked(&self, block: &mut [u8], tweak: &[u8; BLOCK_SIZE]);
+}
+
+/// aes-128, holding the key schedule so that the key is only expanded once: create it once and use
+/// it for many messages. The free functions of the crate are wrappers creating one per call.
+/// Single blocks are encrypted and decrypted through BlockCipher.
+pub struct Aes128 {
+    schedule: KeySchedule,
+    /// Schedule of the equivalent inverse cipher, used to decrypt.
+    decryption_schedule: KeySchedule,
+}
+
+impl Aes128 {
+    pub fn new(key: &Key) -> Self {
+        let schedule = key.do_key_expansion();
+        let decryption_schedule = schedule.for_decryption();
+
+        Aes128 {
+            schedule,
+            decryption_schedule,
+        }
+    }
+
+    /// Encrypts plaintext with the given mode and padding.
+    ///
+    /// With Padding::Prepadded, and with Padding::None outside of CTR, the input must already be a
+    /// multiple of BLOCK_SIZE.
+    pub fn encrypt(
+        &self,
+        plaintext: &Plaintext,
+        options: &AESEncryptionOptions,
+    ) -> Result<Ciphertext, AesError> {
+        self.encrypt_bytes(plaintext, options).map(Ciphertext)
+    }
+
+    /// Decrypts ciphertext without removing any padding. Panics with BlockCipherMode::CTR, whose
+    /// decryption is encryption.
+    pub fn decrypt(&self, ciphertext: &Ciphertext, mode: &BlockCipherMode) -> Plaintext {
+        Plaintext(self.decrypt_bytes(ciphertext, mode))
+    }
+
+    /// Encrypts like encrypt, over raw bytes.
+    pub fn encrypt_bytes(
+        &self,
+        raw_bytes: &[u8],
+        options: &AESEncryptionOptions,
+    ) -> Result<Vec<u8>, AesError> {
+        self.encrypt_bytes_with_progress(raw_bytes, options, |_| {})
+    }
+
+    /// Encrypts like encrypt_bytes, calling progress with the total number of bytes encrypted so
+    /// far after every PROGRESS_INTERVAL bytes. The last call is at completion, with the length of
+    /// the cipher, which is 0 for an empty cipher.
+    pub fn encrypt_bytes_with_progress<F: FnMut(usize)>(
+        &self,
+        raw_bytes: &[u8],
+        options: &AESEncryptionOptions,
+        mut progress: F,
+    ) -> Result<Vec<u8>, AesError> {
+        options.check_input_length(raw_bytes)?;
+
+        let counter_start = options.block_cipher_mode.counter_start();
+        let mut cipher = if counter_start.is_some() {
+            // CTR ignores padding
+            raw_bytes.to_vec()
+        } else {
+            options.padding.pad(raw_bytes, BLOCK_SIZE)
+        };
+        if cipher.is_empty() {
+            progress(0);
+        }
+
+        // CBC chains from the last cipher block of the previous chunk
+        let mut previous_block = match options.block_cipher_mode {
+            BlockCipherMode::CBC(iv) => iv.to_bytes(),
+            _ => [0u8; BLOCK_SIZE],
+        };
+        for (i, chunk) in cipher.chunks_mut(PROGRESS_INTERVAL).enumerate() {
+            let offset = i * PROGRESS_INTERVAL;
+
+            if let Some((nonce, initial_counter)) = counter_start {
+                // the counter blocks are encrypted to a keystream, of which exactly chunk.len()
+                // bytes are XORed with the chunk, the rest of the last block being unused
+                let counter = initial_counter.wrapping_add((offset / BLOCK_SIZE) as u64);
+                let keystream = self.ctr_keystream_from(nonce, counter, chunk.len());
+                let ciphered = xor::xor_bytes(chunk, &keystream);
+                chunk.copy_from_slice(&ciphered);
+            } else {
+                let iv = Iv::from_bytes(&previous_block);
+                let mode = match options.block_cipher_mode {
+                    BlockCipherMode::CBC(_) => BlockCipherMode::CBC(&iv),
+                    _ => BlockCipherMode::ECB,
+                };
+                encrypt_blocks(self, chunk, &mode);
+                previous_block.copy_from_slice(&chunk[chunk.len() - BLOCK_SIZE..]);
+            }
+
+            progress(offset + chunk.len());
+        }
+
+        Ok(cipher)
+    }
+
+    /// Decrypts like decrypt, over raw bytes.
+    pub fn decrypt_bytes(&self, cipher: &[u8], mode: &BlockCipherMode) -> Vec<u8> {
+        if mode.counter_start().is_some() {
+            panic!("Cannot decrypt using CTR block cipher mode. Use encryption instead.");
+        }
+
+        let mut deciphered = cipher[..cipher.len() / BLOCK_SIZE * BLOCK_SIZE].to_vec();
+
+        decrypt_blocks(self, &mut deciphered, mode);
+
+        deciphered
+    }
+
+    /// Generates length bytes of CTR keystream, like ::ctr_keystream.
+    pub fn ctr_keystream(&self, nonce: &Nonce, length: usize) -> Vec<u8> {
+        self.ctr_keystream_from(nonce, ctr::DEFAULT_INITIAL_COUNTER, length)
+    }
+
+    /// Generates length bytes of CTR keystream whose first block uses initial_counter.
+    pub fn ctr_keystream_from(&self, nonce: &Nonce, initial_counter: u64, length: usize) -> Vec<u8> {
+        let mut keystream = ctr::generate_ctr_byte_stream_for_length(length, nonce, initial_counter);
+
+        for block in keystream.chunks_exact_mut(BLOCK_SIZE) {
+            self.encrypt_block(block);
+        }
+        keystream.truncate(length);
+
+        keystream
+    }
+}
+
+impl BlockCipher for Aes128 {
+    const BLOCK_SIZE: usize = BLOCK_SIZE;
+
+    /// Panics if block isn't BLOCK_SIZE bytes long.
+    fn encrypt_block(&self, block: &mut [u8]) {
+        assert_eq!(block.len(), BLOCK_SIZE);
+        let mut state = State::empty();
+
+        state.load_part(block);
+        cipher(&mut state, &self.schedule.0);
+        state.write_block(block);
+    }
+
+    /// Panics if block isn't BLOCK_SIZE bytes long.
+    fn decrypt_block(&self, block: &mut [u8]) {
+        assert_eq!(block.len(), BLOCK_SIZE);
+        let mut state = State::empty();
+
+        state.load_part(block);
+        eq_inv_cipher(&mut state, &self.decryption_schedule.0);
+        state.write_block(block);
+    }
+}
+
+/// aes-128 where every S-box lookup reads all 256 entries of the S-box and selects the right one
+/// with a mask (constant_time::masked_lookup), so that which cache lines are loaded doesn't depend
+/// on the key nor on the data. This mitigates cache-timing attacks on the table lookups, but isn't
+/// constant time: the key expansion still uses direct lookups, and the timing of the instructions
+/// themselves isn't guaranteed. bitslice::BitslicedAes128 has no lookups at all.
+pub struct ScatterGatherAes128 {
+    schedule: KeySchedule,
+    decryption_schedule: KeySchedule,
+}
+
+impl ScatterGatherAes128 {
+    pub fn new(key: &Key) -> Self {
+        let schedule = key.do_key_expansion();
+        let decryption_schedule = schedule.for_decryption();
+
+        ScatterGatherAes128 {
+            schedule,
+            decryption_schedule,
+        }
+    }
+}
+
+impl BlockCipher for ScatterGatherAes128 {
+    const BLOCK_SIZE: usize = BLOCK_SIZE;
+
+    /// Panics if block isn't BLOCK_SIZE bytes long.
+    fn encrypt_block(&self, block: &mut [u8]) {
+        assert_eq!(block.len(), BLOCK_SIZE);
+        let mut state = State::empty();
+
+        state.load_part(block);
+        cipher_traced(
+            &mut state,
+            &self.schedule.0,
+            &CipherParams::default(),
+            State::masked_sub_bytes,
+            |_, _| {},
+        );
+        state.write_block(block);
+    }
+
+    /// Panics if block isn't BLOCK_SIZE bytes long.
+    fn decrypt_block(&self, block: &mut [u8]) {
+        assert_eq!(block.len(), BLOCK_SIZE);
+        let mut state = State::empty();
+
+        state.load_part(block);
+        eq_inv_cipher_with(&mut state, &self.decryption_schedule.0, State::masked_inv_sub_bytes);
+        state.write_block(block);
+    }
+}
+
+/// Parameters of the round function, used to build reduced-round or otherwise modified variants
+/// of the cipher for teaching and cryptanalysis. The default parameters are those of aes-128.
+#[derive(PartialEq, Debug)]
+pub struct CipherParams {
+    /// Number of rounds, at most Nr.
+    pub rounds: usize,
+    /// Whether the MixColumns step is applied in the rounds that normally use it.
+    pub mix_columns: bool,
+}
+
+impl Default for CipherParams {
+    fn default() -> Self {
+        CipherParams {
+            rounds: Nr,
+            mix_columns: true,
+        }
+    }
+}
+
+/// Cipher (Sec. 5.1) transforming the State using the key schedule w.
+pub fn cipher(state: &mut State, w: &[[u8; 4]]) {
+    cipher_with_params(state, w, &CipherParams::default())
+}
+
+/// Cipher (Sec. 5.1) where the number of rounds and the use of MixColumns are configurable. The
+/// final round never uses MixColumns, as in the full cipher.
+pub fn cipher_with_params(state: &mut State, w: &[[u8; 4]], params: &CipherParams) {
+    cipher_traced(state, w, params, State::sub_bytes, |_, _| {})
+}
+
+/// Cipher (Sec. 5.1) calling on_round with the State after the initial AddRoundKey (round 0) and
+/// after each round, with the given implementation of SubBytes.
+fn cipher_traced<F: FnMut(usize, &State)>(
+    state: &mut State,
+    w: &[[u8; 4]],
+    params: &CipherParams,
+    sub_bytes: fn(&mut State),
+    mut on_round: F,
+) {
+    assert!(params.rounds >= 1 && params.rounds <= Nr, "Rounds must be between 1 and Nr.");
+
+    state.add_round_key(&w[0..Nb]);
+    on_round(0, state);
+
+    for round in 1..params.rounds {
+        sub_bytes(state);
+        state.shift_rows();
+        if params.mix_columns {
+            state.mix_columns();
+        }
+        state.add_round_key(&w[round * Nb..(round + 1) * Nb]);
+        on_round(round, state);
+    }
+
+    sub_bytes(state);
+    state.shift_rows();
+    state.add_round_key(&w[params.rounds * Nb..(params.rounds + 1) * Nb]);
+    on_round(params.rounds, state);
+}
+
+/// Inverse Cipher (Sec. 5.3) transforming the State using the key schedule w.
+pub fn inv_cipher(state: &mut State, w: &[[u8; 4]]) {
+    state.add_round_key(&w[Nr * Nb..(Nr + 1) * Nb]);
+
+    for round in (1..Nr).rev() {
+        state.inv_shift_rows();
+        state.inv_sub_bytes();
+        state.add_round_key(&w[round * Nb..(round + 1) * Nb]);
+        state.inv_mix_columns();
+    }
+
+    state.inv_shift_rows();
+    state.inv_sub_bytes();
+    state.add_round_key(&w[0..Nb]);
+}
+
+/// Equivalent Inverse Cipher (Sec. 5.3.5), with the same sequence of transformations as the
+/// Cipher, using the decryption key schedule dw from KeySchedule::for_decryption.
+pub fn eq_inv_cipher(state: &mut State, dw: &[[u8; 4]]) {
+    eq_inv_cipher_with(state, dw, State::inv_sub_bytes)
+}
+
+/// Equivalent Inverse Cipher with the given implementation of InvSubBytes.
+fn eq_inv_cipher_with(state: &mut State, dw: &[[u8; 4]], inv_sub_bytes: fn(&mut State)) {
+    state.add_round_key(&dw[Nr * Nb..(Nr + 1) * Nb]);
+
+    for round in (1..Nr).rev() {
+        inv_sub_bytes(state);
+        state.inv_shift_rows();
+        state.inv_mix_columns();
+        state.add_round_key(&dw[round * Nb..(round + 1) * Nb]);
+    }
+
+    inv_sub_bytes(state);
+    state.inv_shift_rows();
+    state.add_round_key(&dw[0..Nb]);
+}
+
+/// Encrypts a single block using the key schedule w.
+pub fn encrypt_block(block: &[u8; BLOCK_SIZE], w: &[[u8; 4]]) -> [u8; BLOCK_SIZE] {
+    let mut state = State::from_part(block);
+
+    cipher(&mut state, w);
+
+    to_array(&state)
+}
+
+/// Decrypts a single block using the key schedule w.
+pub fn decrypt_block(block: &[u8; BLOCK_SIZE], w: &[[u8; 4]]) -> [u8; BLOCK_SIZE] {
+    let mut state = State::from_part(block);
+
+    inv_cipher(&mut state, w);
+
+    to_array(&state)
+}
+
+/// Encrypts a single block using a key schedule computed elsewhere, e.g. by a reference
+/// implementation, bypassing the key expansion. The schedule must have Nb * (Nr + 1) words.
+pub fn encrypt_block_with_schedule(
+    block: &[u8; BLOCK_SIZE],
+    w: &[[u8; 4]],
+) -> Result<[u8; BLOCK_SIZE], AesError> {
+    validate_schedule_length(w)?;
+
+    Ok(encrypt_block(block, w))
+}
+
+/// Decrypts a single block using a key schedule computed elsewhere. The schedule must have
+/// Nb * (Nr + 1) words.
+pub fn decrypt_block_with_schedule(
+    block: &[u8; BLOCK_SIZE],
+    w: &[[u8; 4]],
+) -> Result<[u8; BLOCK_SIZE], AesError> {
+    validate_schedule_length(w)?;
+
+    Ok(decrypt_block(block, w))
+}
+
+fn validate_schedule_length(w: &[[u8; 4]]) -> Result<(), AesError> {
+    if w.len() != Nb * (Nr + 1) {
+        return Err(AesError::InvalidKeyScheduleLength);
+    }
+
+    Ok(())
+}
+
+/// Encrypts a single block with the round function configured by params. Reduced-round variants
+/// use the first params.rounds + 1 round keys of the aes-128 key schedule.
+pub fn encrypt_block_with_params(
+    block: &[u8; BLOCK_SIZE],
+    key: &Key,
+    params: &CipherParams,
+) -> [u8; BLOCK_SIZE] {
+    let w = &key.do_key_expansion().0;
+    let mut state = State::from_part(block);
+
+    cipher_with_params(&mut state, w, params);
+
+    to_array(&state)
+}
+
+/// Encrypts a single block, calling on_round with the State after the initial AddRoundKey
+/// (round 0) and after each of the Nr rounds, e.g. to compare against the intermediate values of
+/// Appendix B.
+pub fn encrypt_block_traced<F: FnMut(usize, &State)>(
+    block: &[u8; BLOCK_SIZE],
+    key: &Key,
+    on_round: F,
+) -> [u8; BLOCK_SIZE] {
+    let w = &key.do_key_expansion().0;
+    let mut state = State::from_part(block);
+
+    cipher_traced(&mut state, w, &CipherParams::default(), State::sub_bytes, on_round);
+
+    to_array(&state)
+}
+
+fn to_array(state: &State) -> [u8; BLOCK_SIZE] {
+    let mut out = [0u8; BLOCK_SIZE];
+    state.write_block(&mut out);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use pad::{self, Padding};
+
+    use super::*;
+
+    const KEY: Key = Key([
+        0x00, 0x01, 0x02, 0x03,
+        0x04, 0x05, 0x06, 0x07,
+        0x08, 0x09, 0x0a, 0x0b,
+        0x0c, 0x0d, 0x0e, 0x0f
+    ]);
+    const RAW: [u8; 16] = [
+        0x00, 0x11, 0x22, 0x33,
+        0x44, 0x55, 0x66, 0x77,
+        0x88, 0x99, 0xaa, 0xbb,
+        0xcc, 0xdd, 0xee, 0xff
+    ];
+
+    // Appendix A.1, for the key 2b7e151628aed2a6abf7158809cf4f3c
+    const FIPS_197_KEY_SCHEDULE: [[u8; 4]; 44] = [
+        [0x2b, 0x7e, 0x15, 0x16], [0x28, 0xae, 0xd2, 0xa6],
+        [0xab, 0xf7, 0x15, 0x88], [0x09, 0xcf, 0x4f, 0x3c],
+        [0xa0, 0xfa, 0xfe, 0x17], [0x88, 0x54, 0x2c, 0xb1],
+        [0x23, 0xa3, 0x39, 0x39], [0x2a, 0x6c, 0x76, 0x05],
+        [0xf2, 0xc2, 0x95, 0xf2], [0x7a, 0x96, 0xb9, 0x43],
+        [0x59, 0x35, 0x80, 0x7a], [0x73, 0x59, 0xf6, 0x7f],
+        [0x3d, 0x80, 0x47, 0x7d], [0x47, 0x16, 0xfe, 0x3e],
+        [0x1e, 0x23, 0x7e, 0x44], [0x6d, 0x7a, 0x88, 0x3b],
+        [0xef, 0x44, 0xa5, 0x41], [0xa8, 0x52, 0x5b, 0x7f],
+        [0xb6, 0x71, 0x25, 0x3b], [0xdb, 0x0b, 0xad, 0x00],
+        [0xd4, 0xd1, 0xc6, 0xf8], [0x7c, 0x83, 0x9d, 0x87],
+        [0xca, 0xf2, 0xb8, 0xbc], [0x11, 0xf9, 0x15, 0xbc],
+        [0x6d, 0x88, 0xa3, 0x7a], [0x11, 0x0b, 0x3e, 0xfd],
+        [0xdb, 0xf9, 0x86, 0x41], [0xca, 0x00, 0x93, 0xfd],
+        [0x4e, 0x54, 0xf7, 0x0e], [0x5f, 0x5f, 0xc9, 0xf3],
+        [0x84, 0xa6, 0x4f, 0xb2], [0x4e, 0xa6, 0xdc, 0x4f],
+        [0xea, 0xd2, 0x73, 0x21], [0xb5, 0x8d, 0xba, 0xd2],
+        [0x31, 0x2b, 0xf5, 0x60], [0x7f, 0x8d, 0x29, 0x2f],
+        [0xac, 0x77, 0x66, 0xf3], [0x19, 0xfa, 0xdc, 0x21],
+        [0x28, 0xd1, 0x29, 0x41], [0x57, 0x5c, 0x00, 0x6e],
+        [0xd0, 0x14, 0xf9, 0xa8], [0xc9, 0xee, 0x25, 0x89],
+        [0xe1, 0x3f, 0x0c, 0xc8], [0xb6, 0x63, 0x0c, 0xa6]
+    ];
+
+    #[test]
+    fn aes_128_encrypts_block_in_place() {
+        let mut block = RAW;
+
+        Aes128::new(&KEY).encrypt_block(&mut block);
+
+        assert_eq!(block, [
+            0x69, 0xc4, 0xe0, 0xd8,
+            0x6a, 0x7b, 0x04, 0x30,
+            0xd8, 0xcd, 0xb7, 0x80,
+            0x70, 0xb4, 0xc5, 0x5a
+        ]);
+    }
+
+    #[test]
+    fn aes_128_decrypts_block_in_place() {
+        let aes = Aes128::new(&KEY);
+        let mut block = RAW;
+
+        aes.encrypt_block(&mut block);
+        aes.decrypt_block(&mut block);
+
+        assert_eq!(block, RAW);
+    }
+
+    #[test]
+    fn eq_inv_cipher_matches_inv_cipher() {
+        let key_schedule = KEY.do_key_expansion();
+        let dw = key_schedule.for_decryption();
+        let cipher = encrypt_block(&RAW, &key_schedule.0);
+        let mut state = State::from_part(&cipher);
+        let mut eq_state = State::from_part(&cipher);
+
+        inv_cipher(&mut state, &key_schedule.0);
+        eq_inv_cipher(&mut eq_state, &dw.0);
+
+        assert_eq!(eq_state, state);
+        assert_eq!(to_array(&eq_state), RAW);
+    }
+
+    #[test]
+    fn aes_128_encrypts_and_decrypts_many_messages() {
+        let aes = Aes128::new(&KEY);
+        let iv = ::Block::from_bytes(&[0x24; BLOCK_SIZE]);
+        let mode = BlockCipherMode::CBC(&iv);
+        let options = AESEncryptionOptions::new(&mode, &Padding::PKCS7);
+
+        for raw in [&b"first message"[..], &b"a second, longer message"[..]].iter() {
+            let cipher = aes.encrypt(&Plaintext::from(*raw), &options).unwrap();
+
+            assert_eq!(Ok(cipher.clone().into_inner()), ::try_encrypt_aes_128(raw, &KEY, &options));
+            assert_eq!(aes.decrypt(&cipher, &mode).into_inner(), pad::pad_aes(raw, &Padding::PKCS7));
+        }
+    }
+
+    #[test]
+    fn aes_128_ctr_keystream_matches_free_function() {
+        let nonce = [0xf0; 8];
+
+        assert_eq!(Aes128::new(&KEY).ctr_keystream(&nonce, 37), ::ctr_keystream(&KEY, &nonce, 37));
+    }
+
+    #[test]
+    #[should_panic]
+    fn aes_128_panics_on_incomplete_block() {
+        Aes128::new(&KEY).encrypt_block(&mut [0u8; 15]);
+    }
+
+    #[test]
+    fn encrypt_block_traced_reports_appendix_b_round_states() {
+        let input = [
+            0x32, 0x43, 0xf6, 0xa8,
+            0x88, 0x5a, 0x30, 0x8d,
+            0x31, 0x31, 0x98, 0xa2,
+            0xe0, 0x37, 0x07, 0x34
+        ];
+        let key = Key([
+            0x2b, 0x7e, 0x15, 0x16,
+            0x28, 0xae, 0xd2, 0xa6,
+            0xab, 0xf7, 0x15, 0x88,
+            0x09, 0xcf, 0x4f, 0x3c
+        ]);
+        let mut round_states = vec![];
+
+        let output = encrypt_block_traced(&input, &key, |round, state| {
+            round_states.push((round, state.to_block()));
+        });
+
+        assert_eq!(round_states.len(), Nr + 1);
+        assert_eq!(round_states[0], (0, vec![
+            0x19, 0x3d, 0xe3, 0xbe,
+            0xa0, 0xf4, 0xe2, 0x2b,
+            0x9a, 0xc6, 0x8d, 0x2a,
+            0xe9, 0xf8, 0x48, 0x08
+        ]));
+        assert_eq!(round_states[1], (1, vec![
+            0xa4, 0x9c, 0x7f, 0xf2,
+            0x68, 0x9f, 0x35, 0x2b,
+            0x6b, 0x5b, 0xea, 0x43,
+            0x02, 0x6a, 0x50, 0x49
+        ]));
+        assert_eq!(round_states[Nr], (Nr, output.to_vec()));
+        assert_eq!(output, [
+            0x39, 0x25, 0x84, 0x1d,
+            0x02, 0xdc, 0x09, 0xfb,
+            0xdc, 0x11, 0x85, 0x97,
+            0x19, 0x6a, 0x0b, 0x32
+        ]);
+    }
+
+    #[test]
+    fn default_params_are_aes_128() {
+        let params = CipherParams::default();
+
+        assert_eq!(params.rounds, 10);
+        assert!(params.mix_columns);
+    }
+
+    #[test]
+    fn encrypts_block_with_default_params() {
+        let expected = [
+            0x69, 0xc4, 0xe0, 0xd8,
+            0x6a, 0x7b, 0x04, 0x30,
+            0xd8, 0xcd, 0xb7, 0x80,
+            0x70, 0xb4, 0xc5, 0x5a
+        ];
+
+        assert_eq!(encrypt_block_with_params(&RAW, &KEY, &CipherParams::default()), expected);
+    }
+
+    #[test]
+    fn encrypts_block_with_4_rounds() {
+        let params = CipherParams { rounds: 4, mix_columns: true };
+        let expected = [
+            0x6a, 0x9a, 0x89, 0x4c,
+            0xaa, 0x06, 0xdd, 0x37,
+            0xf0, 0x5a, 0x30, 0x61,
+            0xa6, 0xfe, 0x9f, 0x3a
+        ];
+
+        assert_eq!(encrypt_block_with_params(&RAW, &KEY, &params), expected);
+    }
+
+    #[test]
+    fn encrypts_block_with_4_rounds_without_mix_columns() {
+        let params = CipherParams { rounds: 4, mix_columns: false };
+        let expected = [
+            0xa4, 0xa4, 0x5d, 0xde,
+            0xe8, 0x4b, 0xef, 0xe8,
+            0xe3, 0x70, 0xd3, 0x41,
+            0x86, 0xe4, 0x62, 0xf5
+        ];
+
+        assert_eq!(encrypt_block_with_params(&RAW, &KEY, &params), expected);
+    }
+
+    #[test]
+    fn encrypt_block_and_decrypt_block_are_inverses() {
+        let w = &KEY.do_key_expansion().0;
+
+        let cipher = encrypt_block(&RAW, w);
+
+        assert_eq!(cipher, encrypt_block_with_params(&RAW, &KEY, &CipherParams::default()));
+        assert_eq!(decrypt_block(&cipher, w), RAW);
+    }
+
+    #[test]
+    fn encrypts_and_decrypts_block_with_fips_197_schedule() {
+        // Appendix B
+        let raw = [
+            0x32, 0x43, 0xf6, 0xa8,
+            0x88, 0x5a, 0x30, 0x8d,
+            0x31, 0x31, 0x98, 0xa2,
+            0xe0, 0x37, 0x07, 0x34
+        ];
+        let expected_cipher = [
+            0x39, 0x25, 0x84, 0x1d,
+            0x02, 0xdc, 0x09, 0xfb,
+            0xdc, 0x11, 0x85, 0x97,
+            0x19, 0x6a, 0x0b, 0x32
+        ];
+
+        let cipher = encrypt_block_with_schedule(&raw, &FIPS_197_KEY_SCHEDULE);
+
+        assert_eq!(cipher, Ok(expected_cipher));
+        assert_eq!(decrypt_block_with_schedule(&expected_cipher, &FIPS_197_KEY_SCHEDULE), Ok(raw));
+    }
+
+    #[test]
+    fn rejects_schedule_of_wrong_length() {
+        let too_short = &FIPS_197_KEY_SCHEDULE[..Nb * Nr];
+        let too_long = [&FIPS_197_KEY_SCHEDULE[..], &FIPS_197_KEY_SCHEDULE[..Nb]].concat();
+
+        assert_eq!(
+            encrypt_block_with_schedule(&RAW, too_short),
+            Err(AesError::InvalidKeyScheduleLength)
+        );
+        assert_eq!(
+            decrypt_block_with_schedule(&RAW, &too_long),
+            Err(AesError::InvalidKeyScheduleLength)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Rounds must be between 1 and Nr.")]
+    fn encrypting_with_more_rounds_than_nr_should_panic() {
+        let params = CipherParams { rounds: Nr + 1, mix_columns: true };
+
+        encrypt_block_with_params(&RAW, &KEY, &params);
+    }
+
+    #[test]
+    #[cfg(feature = "warn-on-ecb")]
+    fn warns_on_ecb_once_per_process() {
+        let aes = Aes128::new(&KEY);
+        let ecb = AESEncryptionOptions::default();
+
+        aes.encrypt_bytes(&RAW, &ecb).unwrap();
+        aes.encrypt_bytes(&RAW, &ecb).unwrap();
+
+        assert_eq!(ECB_WARNINGS_EMITTED.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn scatter_gather_matches_table_lookups() {
+        let table = Aes128::new(&KEY);
+        let scatter_gather = ScatterGatherAes128::new(&KEY);
+
+        for i in 0..=255u8 {
+            let block = [i.wrapping_mul(17); BLOCK_SIZE];
+            let (mut expected, mut actual) = (block, block);
+            table.encrypt_block(&mut expected);
+            scatter_gather.encrypt_block(&mut actual);
+            assert_eq!(actual, expected);
+
+            scatter_gather.decrypt_block(&mut actual);
+            assert_eq!(actual, block);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use BLOCK_SIZE;
+use cipher::encrypt_block;
+use key::Key;
+use tag::Tag;
+use xor::xor_in_place;
+
+/// Cipher-based Message Authentication Code, as specified in:
+/// https://nvlpubs.nist.gov/nistpubs/SpecialPublications/NIST.SP.800-38b.pdf
+/// and RFC 4493 (AES-CMAC).
+pub fn cmac(key: &Key, message: &[u8]) -> Tag {
+    let w = &key.do_key_expansion().0;
+    let (k1, k2) = generate_subkeys(w);
+
+    // the last block, complete or not, is handled separately
+    let last_block_start = if message.is_empty() {
+        0
+    } else {
+        (message.len() - 1) / BLOCK_SIZE * BLOCK_SIZE
+    };
+    let (complete_blocks, last_part) = message.split_at(last_block_start);
+
+    let mut mac = [0u8; BLOCK_SIZE];
+    for part in complete_blocks.chunks_exact(BLOCK_SIZE) {
+        xor_in_place(&mut mac, part);
+        mac = encrypt_block(&mac, w);
+    }
+
+    let mut last_block = [0u8; BLOCK_SIZE];
+    last_block[..last_part.len()].copy_from_slice(last_part);
+    if last_part.len() == BLOCK_SIZE {
+        xor_in_place(&mut last_block, &k1);
+    } else {
+        last_block[last_part.len()] = 0x80;
+        xor_in_place(&mut last_block, &k2);
+    }
+    xor_in_place(&mut mac, &last_block);
+
+    Tag::from(encrypt_block(&mac, w))
+}
+
+/// Subkeys K1 and K2, derived by doubling E(K, 0^128) in GF(2^128) (Sec. 6.1).
+fn generate_subkeys(w: &[[u8; 4]]) -> ([u8; BLOCK_SIZE], [u8; BLOCK_SIZE]) {
+    let l = u128::from_be_bytes(encrypt_block(&[0u8; BLOCK_SIZE], w));
+    let k1 = double(l);
+    let k2 = double(k1);
+
+    (k1.to_be_bytes(), k2.to_be_bytes())
+}
+
+/// Multiplication by x in GF(2^128), reducing by x^128 + x^7 + x^2 + x + 1 without branching.
+fn double(value: u128) -> u128 {
+    let msb_mask = 0u128.wrapping_sub(value >> 127);
+
+    (value << 1) ^ (0x87 & msb_mask)
+}
+
+/// Test vectors from RFC 4493, Sec. 4.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: Key = Key([
+        0x2b, 0x7e, 0x15, 0x16,
+        0x28, 0xae, 0xd2, 0xa6,
+        0xab, 0xf7, 0x15, 0x88,
+        0x09, 0xcf, 0x4f, 0x3c
+    ]);
+    const MESSAGE: [u8; 64] = [
+        0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96,
+        0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93, 0x17, 0x2a,
+        0xae, 0x2d, 0x8a, 0x57, 0x1e, 0x03, 0xac, 0x9c,
+        0x9e, 0xb7, 0x6f, 0xac, 0x45, 0xaf, 0x8e, 0x51,
+        0x30, 0xc8, 0x1c, 0x46, 0xa3, 0x5c, 0xe4, 0x11,
+        0xe5, 0xfb, 0xc1, 0x19, 0x1a, 0x0a, 0x52, 0xef,
+        0xf6, 0x9f, 0x24, 0x45, 0xdf, 0x4f, 0x9b, 0x17,
+        0xad, 0x2b, 0x41, 0x7b, 0xe6, 0x6c, 0x37, 0x10
+    ];
+
+    #[test]
+    fn generates_subkeys() {
+        let (k1, k2) = generate_subkeys(&KEY.do_key_expansion().0);
+
+        assert_eq!(k1, [
+            0xfb, 0xee, 0xd6, 0x18,
+            0x35, 0x71, 0x33, 0x66,
+            0x7c, 0x85, 0xe0, 0x8f,
+            0x72, 0x36, 0xa8, 0xde
+        ]);
+        assert_eq!(k2, [
+            0xf7, 0xdd, 0xac, 0x30,
+            0x6a, 0xe2, 0x66, 0xcc,
+            0xf9, 0x0b, 0xc1, 0x1e,
+            0xe4, 0x6d, 0x51, 0x3b
+        ]);
+    }
+
+    #[test]
+    fn cmac_of_empty_message() {
+        assert_eq!(cmac(&KEY, &[]), Tag::from([
+            0xbb, 0x1d, 0x69, 0x29,
+            0xe9, 0x59, 0x37, 0x28,
+            0x7f, 0xa3, 0x7d, 0x12,
+            0x9b, 0x75, 0x67, 0x46
+        ]));
+    }
+
+    #[test]
+    fn cmac_of_one_block_message() {
+        assert_eq!(cmac(&KEY, &MESSAGE[..16]), Tag::from([
+            0x07, 0x0a, 0x16, 0xb4,
+            0x6b, 0x4d, 0x41, 0x44,
+            0xf7, 0x9b, 0xdd, 0x9d,
+            0xd0, 0x4a, 0x28, 0x7c
+        ]));
+    }
+
+    #[test]
+    fn cmac_of_partial_last_block_message() {
+        assert_eq!(cmac(&KEY, &MESSAGE[..40]), Tag::from([
+            0xdf, 0xa6, 0x67, 0x47,
+            0xde, 0x9a, 0xe6, 0x30,
+            0x30, 0xca, 0x32, 0x61,
+            0x14, 0x97, 0xc8, 0x27
+        ]));
+    }
+
+    #[test]
+    fn cmac_of_four_block_message() {
+        assert_eq!(cmac(&KEY, &MESSAGE), Tag::from([
+            0x51, 0xf0, 0xbe, 0xbf,
+            0x7e, 0x3b, 0x9d, 0x92,
+            0xfc, 0x49, 0x74, 0x17,
+            0x79, 0x36, 0x3c, 0xfe
+        ]));
+    }
+}
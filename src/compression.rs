@@ -0,0 +1,51 @@
+use ::{BLOCK_SIZE, encrypt_block_arr};
+use key::Key;
+use xor::xor_in_place;
+
+/// Davies-Meyer compression function, E_block(state) ^ state: the message block is used as the
+/// key to encrypt the chaining state. Iterating it over the blocks of a padded message, e.g. with
+/// Merkle-Damgård, builds a hash function from the block cipher.
+///
+/// This is for experimentation: with a 128-bit state, collisions are found in about 2^64 work.
+pub fn dm_compress(state: &[u8; BLOCK_SIZE], block: &[u8; BLOCK_SIZE]) -> [u8; BLOCK_SIZE] {
+    let mut compressed = encrypt_block_arr(*state, &Key(*block));
+    xor_in_place(&mut compressed, state);
+
+    compressed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn differing_bits(a: &[u8], b: &[u8]) -> u32 {
+        a.iter().zip(b.iter()).map(|(a_byte, b_byte)| (a_byte ^ b_byte).count_ones()).sum()
+    }
+
+    #[test]
+    fn dm_compress_is_encryption_under_block_xored_with_state() {
+        let state = [0x24; BLOCK_SIZE];
+        let block = *b"YELLOW SUBMARINE";
+        let mut expected = encrypt_block_arr(state, &Key(block));
+        xor_in_place(&mut expected, &state);
+
+        assert_eq!(dm_compress(&state, &block), expected);
+        assert_eq!(dm_compress(&state, &block), dm_compress(&state, &block));
+    }
+
+    #[test]
+    fn dm_compress_avalanches() {
+        let state = [0x24; BLOCK_SIZE];
+        let block = *b"YELLOW SUBMARINE";
+        let compressed = dm_compress(&state, &block);
+
+        let mut flipped_state = state;
+        flipped_state[7] ^= 0x01;
+        let mut flipped_block = block;
+        flipped_block[0] ^= 0x80;
+
+        // about half of the 128 bits are expected to flip
+        assert!(differing_bits(&compressed, &dm_compress(&flipped_state, &block)) > 32);
+        assert!(differing_bits(&compressed, &dm_compress(&state, &flipped_block)) > 32);
+    }
+}
@@ -0,0 +1,158 @@
+use std::hint::black_box;
+
+use BLOCK_SIZE;
+use error::{AesError, TagMismatch};
+
+/// Compares two byte slices in constant time with regard to their content: every byte is
+/// inspected regardless of where the first difference is. The lengths are not secret and slices
+/// of different lengths are unequal.
+pub fn verify_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut difference = 0u8;
+    for (a_byte, b_byte) in a.iter().zip(b.iter()) {
+        difference |= black_box(a_byte ^ b_byte);
+    }
+
+    difference == 0
+}
+
+/// Whether last_block ends with valid PKCS7 padding, in constant time with regard to its content:
+/// every byte is inspected and there are no branches on the padding length, so that the timing
+/// doesn't tell where the padding is invalid.
+pub fn pkcs7_padding_is_valid(last_block: &[u8; BLOCK_SIZE]) -> bool {
+    let pad_length = last_block[BLOCK_SIZE - 1] as i16;
+    // 0xff where the comparison holds, from the sign bit of the difference
+    let mask_if_negative = |difference: i16| ((difference as u16 >> 15) as u8).wrapping_neg();
+
+    // a padding length of 0 or over BLOCK_SIZE is invalid
+    let mut difference =
+        mask_if_negative(pad_length - 1) | mask_if_negative(BLOCK_SIZE as i16 - pad_length);
+    for (i, byte) in last_block.iter().enumerate() {
+        let distance_from_end = (BLOCK_SIZE - i) as i16;
+        let in_padding = mask_if_negative(distance_from_end - pad_length - 1);
+        difference |= black_box(in_padding & (byte ^ pad_length as u8));
+    }
+
+    difference == 0
+}
+
+/// Looks up table[index] reading every entry of table, and selecting the right one with a mask,
+/// so that the memory accessed, and so the cache lines loaded, don't depend on index. The table
+/// is read 8 entries at a time, and the entry is then shifted out of the selected word.
+pub fn masked_lookup(table: &[u8; 256], index: u8) -> u8 {
+    let word_index = (index >> 3) as u64;
+    let mut word = 0u64;
+    for (i, entries) in table.chunks_exact(8).enumerate() {
+        // all ones when i is word_index, as only i ^ word_index = 0 wraps around when subtracting 1
+        let mask = ((i as u64 ^ word_index).wrapping_sub(1) >> 63).wrapping_neg();
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(entries);
+        word |= u64::from_le_bytes(bytes) & mask;
+    }
+
+    (black_box(word) >> ((index & 7) * 8)) as u8
+}
+
+/// Verifies then decrypts: computes the tag of cipher with expected_tag_fn, compares it to tag in
+/// constant time and only calls decrypt_fn on a match, so that no plaintext is produced from
+/// unauthenticated data. Modes whose tag is computed over the plaintext, such as GCM-SIV, can't
+/// use it.
+pub fn verified_decrypt<T, D>(
+    cipher: &[u8],
+    tag: &[u8; BLOCK_SIZE],
+    expected_tag_fn: T,
+    decrypt_fn: D,
+) -> Result<Vec<u8>, AesError>
+where
+    T: FnOnce(&[u8]) -> [u8; BLOCK_SIZE],
+    D: FnOnce(&[u8]) -> Vec<u8>,
+{
+    let computed_tag = expected_tag_fn(cipher);
+    if !verify_eq(&computed_tag, tag) {
+        return Err(AesError::AuthenticationFailed(TagMismatch::new(Some(computed_tag))));
+    }
+
+    Ok(decrypt_fn(cipher))
+}
+
+#[cfg(test)]
+mod tests {
+    use sbox::s_box;
+
+    use super::*;
+
+    #[test]
+    fn masked_lookup_matches_table_lookup() {
+        for index in 0..=255u8 {
+            assert_eq!(masked_lookup(s_box(), index), s_box()[index as usize]);
+        }
+    }
+
+    #[test]
+    fn verify_eq_accepts_equal_slices() {
+        assert!(verify_eq(&[0x01, 0x02, 0x03], &[0x01, 0x02, 0x03]));
+        assert!(verify_eq(&[], &[]));
+    }
+
+    #[test]
+    fn verify_eq_rejects_different_slices() {
+        assert!(!verify_eq(&[0x01, 0x02, 0x03], &[0x01, 0x02, 0x04]));
+        assert!(!verify_eq(&[0x01, 0x02, 0x03], &[0x01, 0x02]));
+    }
+
+    #[test]
+    fn accepts_valid_pkcs7_padding_of_every_length() {
+        for pad_length in 1..=BLOCK_SIZE {
+            let mut last_block = [0x61; BLOCK_SIZE];
+            for byte in last_block[BLOCK_SIZE - pad_length..].iter_mut() {
+                *byte = pad_length as u8;
+            }
+
+            assert!(pkcs7_padding_is_valid(&last_block));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_pkcs7_padding() {
+        let mut last_block = [0x61; BLOCK_SIZE];
+        last_block[13..].copy_from_slice(&[0x02, 0x03, 0x03]);
+
+        assert!(!pkcs7_padding_is_valid(&last_block));
+        assert!(!pkcs7_padding_is_valid(&[0x00; BLOCK_SIZE]));
+        assert!(!pkcs7_padding_is_valid(&[0x11; BLOCK_SIZE]));
+        assert!(!pkcs7_padding_is_valid(&[0xff; BLOCK_SIZE]));
+    }
+
+    #[test]
+    fn verified_decrypt_decrypts_on_matching_tag() {
+        let actual = verified_decrypt(
+            &[0x01, 0x02],
+            &[0x03; BLOCK_SIZE],
+            |_| [0x03; BLOCK_SIZE],
+            |cipher| cipher.iter().map(|byte| byte ^ 0xff).collect(),
+        );
+
+        assert_eq!(actual, Ok(vec![0xfe, 0xfd]));
+    }
+
+    #[test]
+    fn verified_decrypt_never_decrypts_on_wrong_tag() {
+        let mut decrypted = false;
+
+        let actual = verified_decrypt(
+            &[0x01, 0x02],
+            &[0x03; BLOCK_SIZE],
+            |_| [0x04; BLOCK_SIZE],
+            |cipher| {
+                decrypted = true;
+                cipher.to_vec()
+            },
+        );
+
+        assert!(matches!(actual, Err(AesError::AuthenticationFailed(_))));
+        assert!(!decrypted);
+    }
+}
@@ -1,5 +1,6 @@
 /// Non-linear substitution table used in several byte substitution transformations and in the
 /// Key Expansion routine to perform a one-for-one substitution of a byte value.
+#[cfg(any(test, feature = "precomputed-tables"))]
 pub const S_BOX: [u8; 256] = [
     0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
     0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
@@ -21,6 +22,7 @@ pub const S_BOX: [u8; 256] = [
 
 /// Inverse of the S-BOX. Used in the InvSubBytes step to perform reverse one-for-one substitution
 /// of a byte.
+#[cfg(any(test, feature = "precomputed-tables"))]
 pub const INVERSE_S_BOX: [u8; 256] = [
     0x52, 0x09, 0x6a, 0xd5, 0x30, 0x36, 0xa5, 0x38, 0xbf, 0x40, 0xa3, 0x9e, 0x81, 0xf3, 0xd7, 0xfb,
     0x7c, 0xe3, 0x39, 0x82, 0x9b, 0x2f, 0xff, 0x87, 0x34, 0x8e, 0x43, 0x44, 0xc4, 0xde, 0xe9, 0xcb,
@@ -44,6 +46,9 @@ pub const INVERSE_S_BOX: [u8; 256] = [
 #[allow(non_upper_case_globals)]
 pub const Nb: usize = 4;
 
+/// Size in bytes of a block, which is also the size of the State (4 * Nb).
+pub const BLOCK_SIZE: usize = 4 * Nb;
+
 /// Number of rounds, which is a function of Nk and Nb (which is fixed). For this
 /// aes-128 implementation, Nr = 10.
 #[allow(non_upper_case_globals)]
@@ -53,7 +58,9 @@ pub const Nr: usize = 10;
 #[allow(non_upper_case_globals)]
 pub const Nk: usize = 4;
 
-/// Round constant word array. Used in the key expansion routine.
+/// Round constant word array. Used in the key expansion routine, where Rcon[i / Nk - 1] is used
+/// for every Nk-th word. The 10 entries cover the 44, 52 and 60 word schedules of 128, 192 and
+/// 256-bit keys, which need 10, 8 and 7 of them.
 #[allow(non_upper_case_globals)]
 pub const Rcon: [[u8; 4]; 10] = [
     [0x01, 0x00, 0x00, 0x00],
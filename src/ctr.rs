@@ -1,41 +1,229 @@
-use Nonce;
+use ::{BLOCK_SIZE, Nonce};
+use cipher::{Aes128, BlockCipher};
+use error::AesError;
+use key::Key;
+
+/// Counter of the first block of BlockCipherMode::CTR.
+pub const DEFAULT_INITIAL_COUNTER: u64 = 1;
 
 /// Generates a byte stream of the form:
-/// Nonce + {C} + Nonce + {C+1} + Nonce + {C+2}... etc. where C is an 8 byte counter
+/// Nonce + {C} + Nonce + {C+1} + Nonce + {C+2}... etc. where C is an 8 byte big-endian counter
+/// starting at initial_counter, which is used as is by the first block, and wrapping around to 0
+/// after u64::MAX. BlockCipherMode::CTR starts at DEFAULT_INITIAL_COUNTER.
+///
+/// Each Nonce + {C} block is a full 128-bit counter block, so this layout matches OpenSSL's
+/// aes-128-ctr when OpenSSL is given the first counter block (Nonce + 0x0000000000000001) as
+/// its IV. The two only diverge if the 64-bit counter overflows, where OpenSSL carries into the
+/// nonce bytes.
 /// Source:
 /// https://web.archive.org/web/20150226072817/http://csrc.nist.gov/groups/ST/toolkit/BCM/documents/proposedmodes/ctr/ctr-spec.pdf
-pub fn generate_ctr_byte_stream_for_length(length: usize, nonce: &Nonce) -> Vec<u8> {
-    let block_size = 16;
-    let mut counter = 0u8;
-    let byte_stream_length_padding = if length % block_size != 0 {
-        block_size - (length % block_size)
-    } else {
-        0
-    };
-
-    (0..length + byte_stream_length_padding).collect::<Vec<usize>>()
-        .iter()
-        .enumerate()
-        .map(|(i, _)|
-            if (i % block_size) < nonce.len() {
-                nonce[i % block_size]
-            } else if block_size - (i % block_size) == 1 {
-                counter += if counter < 0xff { 1 } else { 0 };
-
-                counter
-            } else {
-                0u8
+pub fn generate_ctr_byte_stream_for_length(
+    length: usize,
+    nonce: &Nonce,
+    initial_counter: u64,
+) -> Vec<u8> {
+    let block_count = length.div_ceil(BLOCK_SIZE);
+    let mut byte_stream = Vec::with_capacity(block_count * BLOCK_SIZE);
+    let mut counter = initial_counter;
+
+    for _ in 0..block_count {
+        byte_stream.extend_from_slice(nonce);
+        byte_stream.extend_from_slice(&counter.to_be_bytes());
+        counter = counter.wrapping_add(1);
+    }
+
+    byte_stream
+}
+
+/// Increments the rightmost 32 bits of the block modulo 2^32, leaving the other bits unchanged.
+/// This is inc32 of GCM's GCTR (SP 800-38D, Sec. 6.2), where the upper 96 bits, e.g. the nonce,
+/// stay fixed, unlike the 64-bit counter of generate_ctr_byte_stream_for_length.
+pub fn inc32(block: &mut [u8; BLOCK_SIZE]) {
+    let mut low = [0u8; 4];
+    low.copy_from_slice(&block[BLOCK_SIZE - 4..]);
+    let incremented = u32::from_be_bytes(low).wrapping_add(1);
+    block[BLOCK_SIZE - 4..].copy_from_slice(&incremented.to_be_bytes());
+}
+
+/// Stateful CTR cipher, encrypting or decrypting a message passed in any number of parts with the
+/// same keystream as BlockCipherMode::CTR over the whole message.
+pub struct CtrCipher {
+    cipher: Aes128,
+    state: CtrState,
+    keystream_block: [u8; BLOCK_SIZE],
+}
+
+/// Position of a CtrCipher in its keystream, which can be saved and restored, e.g. to resume the
+/// encryption of a large file after a restart. It doesn't hold the key.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct CtrState {
+    pub nonce: Nonce,
+    /// Counter of the block the next keystream byte comes from.
+    pub counter: u64,
+    /// Position of the next keystream byte in its block, less than BLOCK_SIZE.
+    pub offset: usize,
+}
+
+/// Length of a serialized CtrState: the nonce, the big-endian counter and the offset byte.
+const CTR_STATE_SIZE: usize = 8 + 8 + 1;
+
+impl CtrState {
+    /// Serializes the state as nonce || big-endian counter || offset.
+    pub fn to_bytes(&self) -> [u8; CTR_STATE_SIZE] {
+        let mut bytes = [0u8; CTR_STATE_SIZE];
+        bytes[..8].copy_from_slice(&self.nonce);
+        bytes[8..16].copy_from_slice(&self.counter.to_be_bytes());
+        bytes[16] = self.offset as u8;
+
+        bytes
+    }
+
+    /// Rebuilds a state from the bytes produced by to_bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<CtrState, AesError> {
+        if bytes.len() != CTR_STATE_SIZE || bytes[16] as usize >= BLOCK_SIZE {
+            return Err(AesError::InvalidInputLength);
+        }
+
+        let mut nonce = [0u8; 8];
+        nonce.copy_from_slice(&bytes[..8]);
+        let mut counter = [0u8; 8];
+        counter.copy_from_slice(&bytes[8..16]);
+
+        Ok(CtrState {
+            nonce,
+            counter: u64::from_be_bytes(counter),
+            offset: bytes[16] as usize,
+        })
+    }
+}
+
+impl CtrCipher {
+    /// Starts at the beginning of the keystream, i.e. at counter 1.
+    pub fn new(key: &Key, nonce: &Nonce) -> Self {
+        CtrCipher::with_counter(key, nonce, DEFAULT_INITIAL_COUNTER)
+    }
+
+    /// Starts at the beginning of the keystream of BlockCipherMode::CTRWithCounter.
+    pub fn with_counter(key: &Key, nonce: &Nonce, initial_counter: u64) -> Self {
+        CtrCipher::from_state(key, CtrState {
+            nonce: *nonce,
+            counter: initial_counter,
+            offset: 0,
+        })
+    }
+
+    /// Resumes at a state saved with save_state. Panics if the offset isn't less than BLOCK_SIZE.
+    pub fn from_state(key: &Key, state: CtrState) -> Self {
+        assert!(state.offset < BLOCK_SIZE, "Offset must be less than BLOCK_SIZE.");
+
+        let mut ctr_cipher = CtrCipher {
+            cipher: Aes128::new(key),
+            state,
+            keystream_block: [0u8; BLOCK_SIZE],
+        };
+        ctr_cipher.refresh_keystream_block();
+
+        ctr_cipher
+    }
+
+    pub fn save_state(&self) -> CtrState {
+        self.state
+    }
+
+    /// XORs the next data.len() keystream bytes into data, which encrypts or decrypts it.
+    pub fn apply_keystream(&mut self, data: &mut [u8]) {
+        for byte in data.iter_mut() {
+            *byte ^= self.keystream_block[self.state.offset];
+            self.state.offset += 1;
+
+            if self.state.offset == BLOCK_SIZE {
+                self.state.counter = self.state.counter.wrapping_add(1);
+                self.state.offset = 0;
+                self.refresh_keystream_block();
             }
-        )
-        .collect::<Vec<u8>>()
+        }
+    }
+
+    fn refresh_keystream_block(&mut self) {
+        self.keystream_block[..8].copy_from_slice(&self.state.nonce);
+        self.keystream_block[8..].copy_from_slice(&self.state.counter.to_be_bytes());
+        self.cipher.encrypt_block(&mut self.keystream_block);
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::u16;
+    use ::ctr_keystream;
 
     use super::*;
 
+    const KEY: Key = Key([0x2b; BLOCK_SIZE]);
+    const NONCE: Nonce = [0xf0, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7];
+
+    #[test]
+    fn inc32_wraps_only_the_low_32_bits() {
+        let mut block = [0xff; BLOCK_SIZE];
+
+        inc32(&mut block);
+
+        assert_eq!(block[..12], [0xff; 12]);
+        assert_eq!(block[12..], [0x00; 4]);
+    }
+
+    #[test]
+    fn inc32_carries_within_the_low_32_bits() {
+        let mut block = [0x00; BLOCK_SIZE];
+        block[BLOCK_SIZE - 1] = 0xff;
+
+        inc32(&mut block);
+
+        assert_eq!(block[BLOCK_SIZE - 2..], [0x01, 0x00]);
+    }
+
+    #[test]
+    fn ctr_cipher_matches_one_shot_keystream_across_parts() {
+        let mut data = [0u8; 3 * BLOCK_SIZE + 5];
+        let mut ctr_cipher = CtrCipher::new(&KEY, &NONCE);
+
+        let (first, rest) = data.split_at_mut(7);
+        ctr_cipher.apply_keystream(first);
+        let (second, third) = rest.split_at_mut(BLOCK_SIZE + 9);
+        ctr_cipher.apply_keystream(second);
+        ctr_cipher.apply_keystream(third);
+
+        assert_eq!(data.to_vec(), ctr_keystream(&KEY, &NONCE, data.len()));
+    }
+
+    #[test]
+    fn resuming_from_saved_state_continues_keystream() {
+        let message = [0x42; 4 * BLOCK_SIZE + 3];
+        let mut uninterrupted = message;
+        CtrCipher::new(&KEY, &NONCE).apply_keystream(&mut uninterrupted);
+
+        let mut resumed = message;
+        let saved_bytes = {
+            let mut ctr_cipher = CtrCipher::new(&KEY, &NONCE);
+            ctr_cipher.apply_keystream(&mut resumed[..2 * BLOCK_SIZE + 11]);
+            ctr_cipher.save_state().to_bytes()
+        };
+
+        let state = CtrState::from_bytes(&saved_bytes).unwrap();
+        assert_eq!(state, CtrState { nonce: NONCE, counter: 3, offset: 11 });
+        CtrCipher::from_state(&KEY, state).apply_keystream(&mut resumed[2 * BLOCK_SIZE + 11..]);
+
+        assert_eq!(resumed[..], uninterrupted[..]);
+    }
+
+    #[test]
+    fn ctr_state_from_bytes_rejects_invalid_bytes() {
+        let bytes = CtrCipher::new(&KEY, &NONCE).save_state().to_bytes();
+        let mut offset_too_large = bytes;
+        offset_too_large[16] = BLOCK_SIZE as u8;
+
+        assert_eq!(CtrState::from_bytes(&bytes[1..]), Err(AesError::InvalidInputLength));
+        assert_eq!(CtrState::from_bytes(&offset_too_large), Err(AesError::InvalidInputLength));
+    }
+
     #[test]
     fn generates_ctr_bytes_for_length() {
         struct TestCase {
@@ -53,7 +241,7 @@ mod tests {
             TestCase {
                 length: 16,
                 nonce: [0xff; 8],
-                expected: vec![
+                expected: [
                     &[0xff; 8][..],
                     &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01][..]
                 ].concat(),
@@ -61,7 +249,7 @@ mod tests {
             TestCase {
                 length: 17,
                 nonce: [0xff; 8],
-                expected: vec![
+                expected: [
                     &[0xff; 8][..],
                     &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01][..],
                     &[0xff; 8][..],
@@ -73,27 +261,62 @@ mod tests {
         for test_case in test_cases.iter() {
             let generated_bytes = generate_ctr_byte_stream_for_length(
                 test_case.length,
-                &test_case.nonce);
+                &test_case.nonce,
+                DEFAULT_INITIAL_COUNTER);
 
             assert_eq!(generated_bytes, test_case.expected);
         }
     }
 
     #[test]
-    fn generates_ctr_bytes_for_counter_up_to_1_byte() {
+    fn generates_ctr_bytes_for_counter_past_1_byte() {
         let max_length = u16::MAX as usize;
         let nonce = [0xff; 8];
 
         let generated_bytes = generate_ctr_byte_stream_for_length(
             max_length,
             &nonce,
+            DEFAULT_INITIAL_COUNTER,
         );
 
-        let expected = vec![
+        let expected = [
             &nonce[..],
-            &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff][..]
+            &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00][..]
         ].concat();
 
-        assert_eq!(generated_bytes[generated_bytes.len() - 16..], expected[..]);
+        assert_eq!(generated_bytes[generated_bytes.len() - BLOCK_SIZE..], expected[..]);
+    }
+
+    #[test]
+    fn first_block_uses_initial_counter_unchanged() {
+        let nonce = [0xff; 8];
+
+        assert_eq!(generate_ctr_byte_stream_for_length(BLOCK_SIZE, &nonce, 0)[8..], [0x00; 8]);
+        assert_eq!(
+            generate_ctr_byte_stream_for_length(2 * BLOCK_SIZE, &nonce, 0x0102)[..],
+            [
+                &nonce[..],
+                &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x02][..],
+                &nonce[..],
+                &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x03][..],
+            ].concat()[..]
+        );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn counter_wraps_around_to_zero() {
+        let generated_bytes = generate_ctr_byte_stream_for_length(2 * BLOCK_SIZE, &[0xff; 8], u64::MAX);
+
+        assert_eq!(generated_bytes[8..BLOCK_SIZE], [0xff; 8]);
+        assert_eq!(generated_bytes[BLOCK_SIZE + 8..], [0x00; 8]);
+    }
+
+    #[test]
+    fn ctr_cipher_with_counter_matches_ctr_with_counter_keystream() {
+        let mut data = [0u8; 2 * BLOCK_SIZE];
+
+        CtrCipher::with_counter(&KEY, &NONCE, 42).apply_keystream(&mut data);
+
+        assert_eq!(data.to_vec(), Aes128::new(&KEY).ctr_keystream_from(&NONCE, 42, data.len()));
+    }
+}
@@ -0,0 +1,196 @@
+use ::{BLOCK_SIZE, BlockCipherMode, Iv, decrypt_blocks, encrypt_blocks};
+use cipher::{Aes128, BlockCipher};
+use error::AesError;
+use key::Key;
+use xor::xor_in_place;
+
+/// Encrypts in aes-128 CBC with ciphertext stealing, in the CS3 ordering used by Kerberos
+/// (RFC 3962) and some Microsoft protocols: the plaintext is zero padded and CBC encrypted, then
+/// the last two ciphertext blocks are always swapped, the former last block being truncated to the
+/// length of the last plaintext block. The ciphertext is as long as the plaintext.
+///
+/// CS1 and CS2, the other ciphertext stealing variants, order the blocks differently and aren't
+/// compatible. Plaintexts shorter than BLOCK_SIZE can't be encrypted, and a single block is
+/// encrypted as is.
+pub fn encrypt_cbc_cts_kerberos(raw_bytes: &[u8], key: &Key, iv: &Iv) -> Result<Vec<u8>, AesError> {
+    if raw_bytes.len() < BLOCK_SIZE {
+        return Err(AesError::InvalidInputLength);
+    }
+
+    let block_count = raw_bytes.len().div_ceil(BLOCK_SIZE);
+    let mut cipher = raw_bytes.to_vec();
+    cipher.resize(block_count * BLOCK_SIZE, 0x00);
+    encrypt_blocks(&Aes128::new(key), &mut cipher, &BlockCipherMode::CBC(iv));
+
+    if block_count > 1 {
+        let (_, last_two_blocks) = cipher.split_at_mut((block_count - 2) * BLOCK_SIZE);
+        let (next_to_last, last) = last_two_blocks.split_at_mut(BLOCK_SIZE);
+        next_to_last.swap_with_slice(last);
+    }
+    cipher.truncate(raw_bytes.len());
+
+    Ok(cipher)
+}
+
+/// Decrypts a ciphertext produced by encrypt_cbc_cts_kerberos.
+pub fn decrypt_cbc_cts_kerberos(cipher: &[u8], key: &Key, iv: &Iv) -> Result<Vec<u8>, AesError> {
+    if cipher.len() < BLOCK_SIZE {
+        return Err(AesError::InvalidInputLength);
+    }
+
+    let block_cipher = Aes128::new(key);
+    let block_count = cipher.len().div_ceil(BLOCK_SIZE);
+    if block_count == 1 {
+        let mut raw_bytes = cipher.to_vec();
+        decrypt_blocks(&block_cipher, &mut raw_bytes, &BlockCipherMode::CBC(iv));
+
+        return Ok(raw_bytes);
+    }
+
+    let swapped_start = (block_count - 2) * BLOCK_SIZE;
+    let (leading_blocks, swapped_blocks) = cipher.split_at(swapped_start);
+    let (last_block, stolen_part) = swapped_blocks.split_at(BLOCK_SIZE);
+
+    // the last block decrypts to the zero padded last plaintext block XOR the next to last
+    // ciphertext block, whose stolen tail is therefore the tail of the decrypted block
+    let mut decrypted_last_block = [0u8; BLOCK_SIZE];
+    decrypted_last_block.copy_from_slice(last_block);
+    block_cipher.decrypt_block(&mut decrypted_last_block);
+
+    let mut next_to_last_block = [0u8; BLOCK_SIZE];
+    next_to_last_block[..stolen_part.len()].copy_from_slice(stolen_part);
+    next_to_last_block[stolen_part.len()..]
+        .copy_from_slice(&decrypted_last_block[stolen_part.len()..]);
+
+    let mut last_raw_part = stolen_part.to_vec();
+    xor_in_place(&mut last_raw_part, &decrypted_last_block[..stolen_part.len()]);
+
+    let mut raw_bytes = [leading_blocks, &next_to_last_block[..]].concat();
+    decrypt_blocks(&block_cipher, &mut raw_bytes, &BlockCipherMode::CBC(iv));
+    raw_bytes.extend_from_slice(&last_raw_part);
+
+    Ok(raw_bytes)
+}
+
+/// Test vectors from RFC 3962, Appendix B.
+#[cfg(test)]
+mod tests {
+    use ::Block;
+
+    use super::*;
+
+    const KEY: Key = Key([
+        0x63, 0x68, 0x69, 0x63,
+        0x6b, 0x65, 0x6e, 0x20,
+        0x74, 0x65, 0x72, 0x69,
+        0x79, 0x61, 0x6b, 0x69
+    ]);
+    const RAW: &[u8; 64] = b"I would like the General Gau's Chicken, please, and wonton soup.";
+
+    struct TestCase {
+        length: usize,
+        expected: Vec<u8>,
+    }
+
+    fn rfc_3962_test_cases() -> Vec<TestCase> {
+        vec![
+            TestCase {
+                length: 17,
+                expected: vec![
+                    0xc6, 0x35, 0x35, 0x68, 0xf2, 0xbf, 0x8c, 0xb4,
+                    0xd8, 0xa5, 0x80, 0x36, 0x2d, 0xa7, 0xff, 0x7f,
+                    0x97
+                ],
+            },
+            TestCase {
+                length: 31,
+                expected: vec![
+                    0xfc, 0x00, 0x78, 0x3e, 0x0e, 0xfd, 0xb2, 0xc1,
+                    0xd4, 0x45, 0xd4, 0xc8, 0xef, 0xf7, 0xed, 0x22,
+                    0x97, 0x68, 0x72, 0x68, 0xd6, 0xec, 0xcc, 0xc0,
+                    0xc0, 0x7b, 0x25, 0xe2, 0x5e, 0xcf, 0xe5
+                ],
+            },
+            TestCase {
+                length: 32,
+                expected: vec![
+                    0x39, 0x31, 0x25, 0x23, 0xa7, 0x86, 0x62, 0xd5,
+                    0xbe, 0x7f, 0xcb, 0xcc, 0x98, 0xeb, 0xf5, 0xa8,
+                    0x97, 0x68, 0x72, 0x68, 0xd6, 0xec, 0xcc, 0xc0,
+                    0xc0, 0x7b, 0x25, 0xe2, 0x5e, 0xcf, 0xe5, 0x84
+                ],
+            },
+            TestCase {
+                length: 47,
+                expected: vec![
+                    0x97, 0x68, 0x72, 0x68, 0xd6, 0xec, 0xcc, 0xc0,
+                    0xc0, 0x7b, 0x25, 0xe2, 0x5e, 0xcf, 0xe5, 0x84,
+                    0xb3, 0xff, 0xfd, 0x94, 0x0c, 0x16, 0xa1, 0x8c,
+                    0x1b, 0x55, 0x49, 0xd2, 0xf8, 0x38, 0x02, 0x9e,
+                    0x39, 0x31, 0x25, 0x23, 0xa7, 0x86, 0x62, 0xd5,
+                    0xbe, 0x7f, 0xcb, 0xcc, 0x98, 0xeb, 0xf5
+                ],
+            },
+            TestCase {
+                length: 48,
+                expected: vec![
+                    0x97, 0x68, 0x72, 0x68, 0xd6, 0xec, 0xcc, 0xc0,
+                    0xc0, 0x7b, 0x25, 0xe2, 0x5e, 0xcf, 0xe5, 0x84,
+                    0x9d, 0xad, 0x8b, 0xbb, 0x96, 0xc4, 0xcd, 0xc0,
+                    0x3b, 0xc1, 0x03, 0xe1, 0xa1, 0x94, 0xbb, 0xd8,
+                    0x39, 0x31, 0x25, 0x23, 0xa7, 0x86, 0x62, 0xd5,
+                    0xbe, 0x7f, 0xcb, 0xcc, 0x98, 0xeb, 0xf5, 0xa8
+                ],
+            },
+            TestCase {
+                length: 64,
+                expected: vec![
+                    0x97, 0x68, 0x72, 0x68, 0xd6, 0xec, 0xcc, 0xc0,
+                    0xc0, 0x7b, 0x25, 0xe2, 0x5e, 0xcf, 0xe5, 0x84,
+                    0x39, 0x31, 0x25, 0x23, 0xa7, 0x86, 0x62, 0xd5,
+                    0xbe, 0x7f, 0xcb, 0xcc, 0x98, 0xeb, 0xf5, 0xa8,
+                    0x48, 0x07, 0xef, 0xe8, 0x36, 0xee, 0x89, 0xa5,
+                    0x26, 0x73, 0x0d, 0xbc, 0x2f, 0x7b, 0xc8, 0x40,
+                    0x9d, 0xad, 0x8b, 0xbb, 0x96, 0xc4, 0xcd, 0xc0,
+                    0x3b, 0xc1, 0x03, 0xe1, 0xa1, 0x94, 0xbb, 0xd8
+                ],
+            },
+        ]
+    }
+
+    #[test]
+    fn encrypts_rfc_3962_vectors() {
+        for test_case in rfc_3962_test_cases() {
+            let actual = encrypt_cbc_cts_kerberos(&RAW[..test_case.length], &KEY, &Iv::zero());
+
+            assert_eq!(actual, Ok(test_case.expected), "length {}", test_case.length);
+        }
+    }
+
+    #[test]
+    fn decrypts_rfc_3962_vectors() {
+        for test_case in rfc_3962_test_cases() {
+            let actual = decrypt_cbc_cts_kerberos(&test_case.expected, &KEY, &Iv::zero());
+
+            assert_eq!(actual, Ok(RAW[..test_case.length].to_vec()), "length {}", test_case.length);
+        }
+    }
+
+    #[test]
+    fn single_block_round_trips() {
+        let iv = Block::from_bytes(&[0x24; BLOCK_SIZE]);
+
+        let cipher = encrypt_cbc_cts_kerberos(&RAW[..BLOCK_SIZE], &KEY, &iv).unwrap();
+
+        assert_eq!(cipher.len(), BLOCK_SIZE);
+        assert_eq!(decrypt_cbc_cts_kerberos(&cipher, &KEY, &iv), Ok(RAW[..BLOCK_SIZE].to_vec()));
+    }
+
+    #[test]
+    fn rejects_input_shorter_than_a_block() {
+        let iv = Iv::zero();
+
+        assert_eq!(encrypt_cbc_cts_kerberos(&RAW[..15], &KEY, &iv), Err(AesError::InvalidInputLength));
+        assert_eq!(decrypt_cbc_cts_kerberos(&RAW[..15], &KEY, &iv), Err(AesError::InvalidInputLength));
+    }
+}
@@ -0,0 +1,150 @@
+use ::{BLOCK_SIZE, Iv, secure_cbc};
+use cbc_hmac::TAG_SIZE;
+use error::AesError;
+use key::Key;
+
+/// Size of the big-endian u32 plaintext length prefixing a length-prefixed envelope.
+pub const LENGTH_SIZE: usize = 4;
+
+/// Seals raw_bytes into an envelope with secure_cbc: iv || ciphertext || tag, authenticating aad.
+pub fn seal(raw_bytes: &[u8], enc_key: &Key, mac_key: &Key, iv: &Iv, aad: &[u8]) -> Vec<u8> {
+    secure_cbc::encrypt_with_iv(raw_bytes, enc_key, mac_key, iv, aad)
+}
+
+/// Verifies and opens an envelope produced by seal.
+pub fn open(sealed: &[u8], enc_key: &Key, mac_key: &Key, aad: &[u8]) -> Result<Vec<u8>, AesError> {
+    secure_cbc::decrypt(sealed, enc_key, mac_key, aad)
+}
+
+/// Size of the envelope of a plaintext of plaintext_length bytes, without its length prefix.
+pub fn sealed_length(plaintext_length: usize) -> usize {
+    BLOCK_SIZE + (plaintext_length / BLOCK_SIZE + 1) * BLOCK_SIZE + TAG_SIZE
+}
+
+/// Seals raw_bytes like seal, prefixed with its length as a big-endian u32 so that a receiver
+/// knows how much of a stream to read. The length is authenticated along with aad: it can't be
+/// altered to truncate or extend the envelope without failing authentication.
+///
+/// Fails with InputTooLong if raw_bytes is longer than u32::MAX bytes.
+pub fn seal_length_prefixed(
+    raw_bytes: &[u8],
+    enc_key: &Key,
+    mac_key: &Key,
+    iv: &Iv,
+    aad: &[u8],
+) -> Result<Vec<u8>, AesError> {
+    if raw_bytes.len() > u32::MAX as usize {
+        return Err(AesError::InputTooLong);
+    }
+    let length = (raw_bytes.len() as u32).to_be_bytes();
+    let sealed = seal(raw_bytes, enc_key, mac_key, iv, &[aad, &length].concat());
+
+    Ok([&length[..], &sealed].concat())
+}
+
+/// Opens the length-prefixed envelope at the start of stream, returning its plaintext and the
+/// number of bytes of stream it took, after which the next envelope starts.
+///
+/// Fails with InvalidFraming if stream ends within the envelope, and with AuthenticationFailed if
+/// the length, the envelope or aad were tampered with.
+pub fn open_length_prefixed(
+    stream: &[u8],
+    enc_key: &Key,
+    mac_key: &Key,
+    aad: &[u8],
+) -> Result<(Vec<u8>, usize), AesError> {
+    if stream.len() < LENGTH_SIZE {
+        return Err(AesError::InvalidFraming);
+    }
+    let (length, rest) = stream.split_at(LENGTH_SIZE);
+    let plaintext_length = u32::from_be_bytes([length[0], length[1], length[2], length[3]]);
+    let envelope_length = sealed_length(plaintext_length as usize);
+    if rest.len() < envelope_length {
+        return Err(AesError::InvalidFraming);
+    }
+
+    let raw_bytes = open(&rest[..envelope_length], enc_key, mac_key, &[aad, length].concat())?;
+    if raw_bytes.len() != plaintext_length as usize {
+        return Err(AesError::InvalidFraming);
+    }
+
+    Ok((raw_bytes, LENGTH_SIZE + envelope_length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENC_KEY: Key = Key([0x11; 16]);
+    const MAC_KEY: Key = Key([0x22; 16]);
+    const AAD: &[u8] = b"header";
+
+    fn iv() -> Iv {
+        Iv::from_bytes(&[0x33; BLOCK_SIZE])
+    }
+
+    #[test]
+    fn envelope_round_trips() {
+        let sealed = seal(b"attack at dawn", &ENC_KEY, &MAC_KEY, &iv(), AAD);
+
+        assert_eq!(sealed.len(), sealed_length(14));
+        assert_eq!(open(&sealed, &ENC_KEY, &MAC_KEY, AAD), Ok(b"attack at dawn".to_vec()));
+    }
+
+    #[test]
+    fn length_prefixed_envelopes_are_read_one_after_the_other() {
+        let first = seal_length_prefixed(b"first", &ENC_KEY, &MAC_KEY, &iv(), AAD).unwrap();
+        let second = seal_length_prefixed(&[0x42; 40], &ENC_KEY, &MAC_KEY, &iv(), AAD).unwrap();
+        let stream = [&first[..], &second[..]].concat();
+
+        assert_eq!(first[..LENGTH_SIZE], [0x00, 0x00, 0x00, 0x05]);
+
+        let (message, read) = open_length_prefixed(&stream, &ENC_KEY, &MAC_KEY, AAD).unwrap();
+        assert_eq!((message, read), (b"first".to_vec(), first.len()));
+
+        let (message, read) = open_length_prefixed(&stream[read..], &ENC_KEY, &MAC_KEY, AAD)
+            .unwrap();
+        assert_eq!((message, read), (vec![0x42; 40], second.len()));
+    }
+
+    #[test]
+    fn detects_truncation_with_adjusted_length() {
+        let framed = seal_length_prefixed(&[0x42; 40], &ENC_KEY, &MAC_KEY, &iv(), AAD).unwrap();
+        let tag = &framed[framed.len() - TAG_SIZE..];
+
+        // drops the last ciphertext block and announces a plaintext that fits in the rest
+        let iv_and_cipher = &framed[LENGTH_SIZE..framed.len() - TAG_SIZE - BLOCK_SIZE];
+        let truncated = [&[0x00, 0x00, 0x00, 0x18], iv_and_cipher, tag].concat();
+
+        assert!(matches!(
+            open_length_prefixed(&truncated, &ENC_KEY, &MAC_KEY, AAD),
+            Err(AesError::AuthenticationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn detects_altered_length() {
+        let mut framed = seal_length_prefixed(b"first", &ENC_KEY, &MAC_KEY, &iv(), AAD).unwrap();
+        // still within the same single block envelope
+        framed[LENGTH_SIZE - 1] = 0x03;
+
+        assert!(matches!(
+            open_length_prefixed(&framed, &ENC_KEY, &MAC_KEY, AAD),
+            Err(AesError::AuthenticationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_stream_ending_within_envelope() {
+        let framed = seal_length_prefixed(b"first", &ENC_KEY, &MAC_KEY, &iv(), AAD).unwrap();
+
+        assert_eq!(
+            open_length_prefixed(&framed[..framed.len() - 1], &ENC_KEY, &MAC_KEY, AAD),
+            Err(AesError::InvalidFraming)
+        );
+        assert_eq!(
+            open_length_prefixed(&framed[..2], &ENC_KEY, &MAC_KEY, AAD),
+            Err(AesError::InvalidFraming)
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use std::error::Error;
+use std::fmt;
+use std::io::ErrorKind;
+
+use ::{BLOCK_SIZE, Nb, Nr};
+
+/// Errors returned by the fallible encryption and decryption functions.
+#[derive(PartialEq, Debug)]
+pub enum AesError {
+    /// The input length is not a multiple of BLOCK_SIZE.
+    InvalidInputLength,
+    /// The input exceeds the maximum length of the mode.
+    InputTooLong,
+    /// The nonce was already used with the same key.
+    NonceReused,
+    /// The counter range doesn't start after the highest counter already used with the same key
+    /// and nonce.
+    CounterRangeReused,
+    /// The authentication tag doesn't match the data.
+    AuthenticationFailed(TagMismatch),
+    /// The padding of the decrypted data is malformed.
+    InvalidPadding,
+    /// The output buffer is shorter than the data written to it.
+    BufferTooSmall,
+    /// The data doesn't start with the expected header.
+    InvalidHeader,
+    /// The decrypted data isn't a sequence of complete length-prefixed records.
+    InvalidFraming,
+    /// The radix is unsupported, or a digit isn't less than the radix.
+    InvalidDigit,
+    /// The keystream is shorter than the data it is applied to.
+    KeystreamTooShort,
+    /// The input isn't valid base 64.
+    InvalidBase64,
+    /// The block order isn't a permutation of the block indices.
+    InvalidPermutation,
+    /// The key schedule doesn't have Nb * (Nr + 1) words.
+    InvalidKeyScheduleLength,
+    /// The environment variable holding the key isn't set.
+    MissingEnvVar(String),
+    /// The key isn't hex encoded.
+    InvalidHex,
+    /// The key isn't of a supported length: 16 bytes, or 24 and 32 bytes for key::expand_key.
+    InvalidKeyLength,
+    /// Reading the input or writing the output failed.
+    Io(ErrorKind),
+}
+
+/// Details of an authentication failure. Debug builds keep the tag computed over the data, to
+/// help troubleshoot mismatches; release builds leave it out so as not to leak tag bits.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct TagMismatch {
+    /// Computed tag, if the data was well-formed enough to compute one.
+    #[cfg(debug_assertions)]
+    pub computed_tag: Option<[u8; BLOCK_SIZE]>,
+}
+
+impl TagMismatch {
+    /// Mismatch with computed_tag, which is dropped in release builds.
+    pub fn new(computed_tag: Option<[u8; BLOCK_SIZE]>) -> Self {
+        #[cfg(not(debug_assertions))]
+        let _ = computed_tag;
+
+        TagMismatch {
+            #[cfg(debug_assertions)]
+            computed_tag,
+        }
+    }
+}
+
+/// Displays the computed tag in debug builds, and nothing in release builds.
+impl fmt::Display for TagMismatch {
+    #[cfg(debug_assertions)]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(computed_tag) = self.computed_tag {
+            write!(f, " Computed tag: ")?;
+            for byte in computed_tag.iter() {
+                write!(f, "{:02x}", byte)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn fmt(&self, _f: &mut fmt::Formatter) -> fmt::Result {
+        Ok(())
+    }
+}
+
+impl fmt::Display for AesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AesError::InvalidInputLength => write!(
+                f,
+                "Input length must be a multiple of the block size ({} bytes).",
+                BLOCK_SIZE
+            ),
+            AesError::InputTooLong => write!(f, "Input exceeds the maximum length of the mode."),
+            AesError::NonceReused => write!(f, "Nonce was already used with this key."),
+            AesError::CounterRangeReused => {
+                write!(f, "Counter range doesn't start after the highest counter used with this key and nonce.")
+            }
+            AesError::AuthenticationFailed(mismatch) => write!(f, "Authentication failed.{}", mismatch),
+            AesError::InvalidPadding => write!(f, "Invalid padding."),
+            AesError::BufferTooSmall => write!(f, "Output buffer is too small."),
+            AesError::InvalidHeader => write!(f, "Invalid header."),
+            AesError::InvalidFraming => write!(f, "Records are not correctly length framed."),
+            AesError::InvalidDigit => write!(f, "Digit is not valid in the radix."),
+            AesError::KeystreamTooShort => write!(f, "Keystream is shorter than the data."),
+            AesError::InvalidBase64 => write!(f, "Input is not valid base 64."),
+            AesError::InvalidPermutation => write!(f, "Order is not a permutation of the blocks."),
+            AesError::InvalidKeyScheduleLength => write!(
+                f,
+                "Key schedule must have Nb * (Nr + 1) = {} words.",
+                Nb * (Nr + 1)
+            ),
+            AesError::MissingEnvVar(name) => write!(f, "Environment variable {} is not set.", name),
+            AesError::InvalidHex => write!(f, "Key must be hex encoded."),
+            AesError::InvalidKeyLength => write!(f, "Key length is not supported."),
+            AesError::Io(kind) => write!(f, "I/O error: {:?}.", kind),
+        }
+    }
+}
+
+impl Error for AesError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn displays_computed_tag_in_debug_builds() {
+        let error = AesError::AuthenticationFailed(TagMismatch::new(Some([0xab; BLOCK_SIZE])));
+
+        assert_eq!(
+            error.to_string(),
+            "Authentication failed. Computed tag: abababababababababababababababab"
+        );
+    }
+
+    #[test]
+    fn displays_opaque_authentication_failure_without_computed_tag() {
+        let error = AesError::AuthenticationFailed(TagMismatch::new(None));
+
+        assert_eq!(error.to_string(), "Authentication failed.");
+    }
+}
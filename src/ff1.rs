@@ -0,0 +1,241 @@
+use ::{BLOCK_SIZE, BlockCipherMode, Iv, encrypt_blocks};
+use cipher::{Aes128, BlockCipher};
+use error::AesError;
+use key::Key;
+use xor::xor_in_place;
+
+/// Number of Feistel rounds.
+const ROUNDS: u8 = 10;
+/// Largest supported radix, 2^16.
+const MAX_RADIX: u32 = 1 << 16;
+/// Smallest domain radix^n allowed by SP 800-38G Rev. 1.
+const MIN_DOMAIN_SIZE: u128 = 1_000_000;
+/// Largest supported radix^v, so that numbers of half the input can be reduced in u128 arithmetic.
+const MAX_HALF_DOMAIN_SIZE: u128 = 1 << 120;
+
+/// FF1 format-preserving encryption, as specified in:
+/// https://nvlpubs.nist.gov/nistpubs/SpecialPublications/NIST.SP.800-38Gr1-draft.pdf
+///
+/// input is a string of digits in the given radix, e.g. the digits of a credit card number with
+/// radix 10, and is encrypted to a string of digits of the same length and radix. The tweak is
+/// public, and changes the permutation like a nonce would.
+///
+/// The radix must be between 2 and 2^16 and every digit less than it, or InvalidDigit is returned.
+/// radix^len must be at least 1,000,000, or InvalidInputLength is returned. Halves of the input
+/// are handled as u128, so radix^(len / 2) must not exceed 2^120, or InputTooLong is returned,
+/// e.g. inputs of over 72 decimal digits.
+pub fn encrypt_ff1(key: &Key, tweak: &[u8], radix: u32, input: &[u16]) -> Result<Vec<u16>, AesError> {
+    let ff1 = Ff1::new(key, tweak, radix, input)?;
+    let (a, b) = input.split_at(ff1.u);
+    let (mut a, mut b) = (a.to_vec(), b.to_vec());
+
+    for i in 0..ROUNDS {
+        let m = if i % 2 == 0 { ff1.u } else { ff1.v };
+        let modulus = (radix as u128).pow(m as u32);
+        let y = ff1.round_value(i, &b, modulus);
+        let c = (num_radix(&a, radix) + y) % modulus;
+
+        a = b;
+        b = str_radix(c, radix, m);
+    }
+
+    Ok([a, b].concat())
+}
+
+/// Decrypts a string of digits encrypted with encrypt_ff1 under the same key, tweak and radix.
+pub fn decrypt_ff1(key: &Key, tweak: &[u8], radix: u32, input: &[u16]) -> Result<Vec<u16>, AesError> {
+    let ff1 = Ff1::new(key, tweak, radix, input)?;
+    let (a, b) = input.split_at(ff1.u);
+    let (mut a, mut b) = (a.to_vec(), b.to_vec());
+
+    for i in (0..ROUNDS).rev() {
+        let m = if i % 2 == 0 { ff1.u } else { ff1.v };
+        let modulus = (radix as u128).pow(m as u32);
+        let y = ff1.round_value(i, &a, modulus);
+        let c = (num_radix(&b, radix) + modulus - y) % modulus;
+
+        b = a;
+        a = str_radix(c, radix, m);
+    }
+
+    Ok([a, b].concat())
+}
+
+/// Parameters shared by the rounds of an encryption or a decryption.
+struct Ff1<'a> {
+    block_cipher: Aes128,
+    tweak: &'a [u8],
+    radix: u32,
+    u: usize,
+    v: usize,
+    /// Number of bytes of NUM_radix(B) in Q.
+    b: usize,
+    /// Number of bytes of S kept for y.
+    d: usize,
+    /// Encryption of the fixed block P, the start of every round's CBC-MAC.
+    encrypted_p: [u8; BLOCK_SIZE],
+}
+
+impl<'a> Ff1<'a> {
+    fn new(key: &Key, tweak: &'a [u8], radix: u32, input: &[u16]) -> Result<Ff1<'a>, AesError> {
+        if !(2..=MAX_RADIX).contains(&radix) || input.iter().any(|digit| *digit as u32 >= radix) {
+            return Err(AesError::InvalidDigit);
+        }
+        if input.len() < 2 || !domain_is_at_least(radix, input.len(), MIN_DOMAIN_SIZE) {
+            return Err(AesError::InvalidInputLength);
+        }
+        if tweak.len() > u32::MAX as usize {
+            return Err(AesError::InputTooLong);
+        }
+
+        let n = input.len();
+        let u = n / 2;
+        let v = n - u;
+        if domain_is_at_least(radix, v, MAX_HALF_DOMAIN_SIZE + 1) {
+            return Err(AesError::InputTooLong);
+        }
+
+        // ceil(v * log2(radix)) is the bit length of radix^v - 1
+        let half_domain_size = (radix as u128).pow(v as u32);
+        let b = (128 - (half_domain_size - 1).leading_zeros() as usize).div_ceil(8);
+        let d = 4 * b.div_ceil(4) + 4;
+
+        let mut p = [0u8; BLOCK_SIZE];
+        p[..3].copy_from_slice(&[0x01, 0x02, 0x01]);
+        p[3..6].copy_from_slice(&radix.to_be_bytes()[1..]);
+        p[6] = ROUNDS;
+        p[7] = (u % 256) as u8;
+        p[8..12].copy_from_slice(&(n as u32).to_be_bytes());
+        p[12..].copy_from_slice(&(tweak.len() as u32).to_be_bytes());
+
+        let block_cipher = Aes128::new(key);
+        block_cipher.encrypt_block(&mut p);
+
+        Ok(Ff1 { block_cipher, tweak, radix, u, v, b, d, encrypted_p: p })
+    }
+
+    /// y = NUM(S) mod radix^m for round i, S being derived from PRF(P || Q).
+    fn round_value(&self, i: u8, half: &[u16], modulus: u128) -> u128 {
+        // Q = T || [0]^((-t-b-1) mod 16) || [i]^1 || [NUM_radix(half)]^b
+        let zero_count = (BLOCK_SIZE - (self.tweak.len() + self.b + 1) % BLOCK_SIZE) % BLOCK_SIZE;
+        let mut q = self.tweak.to_vec();
+        q.resize(self.tweak.len() + zero_count, 0x00);
+        q.push(i);
+        q.extend_from_slice(&num_radix(half, self.radix).to_be_bytes()[BLOCK_SIZE - self.b..]);
+
+        // the CBC-MAC of P || Q is the CBC-MAC of Q with E(P) as IV
+        let iv = Iv::from_bytes(&self.encrypted_p);
+        encrypt_blocks(&self.block_cipher, &mut q, &BlockCipherMode::CBC(&iv));
+        let mut r = [0u8; BLOCK_SIZE];
+        r.copy_from_slice(&q[q.len() - BLOCK_SIZE..]);
+
+        // S = R || CIPH(R ^ [1]^16) || CIPH(R ^ [2]^16) ..., truncated to d bytes
+        let mut s = r.to_vec();
+        for j in 1..self.d.div_ceil(BLOCK_SIZE) {
+            let mut block = [0u8; BLOCK_SIZE];
+            block[BLOCK_SIZE - 8..].copy_from_slice(&(j as u64).to_be_bytes());
+            xor_in_place(&mut block, &r);
+            self.block_cipher.encrypt_block(&mut block);
+            s.extend_from_slice(&block);
+        }
+
+        s[..self.d].iter().fold(0u128, |y, byte| (y * 256 + *byte as u128) % modulus)
+    }
+}
+
+/// Whether radix^exponent >= minimum, without overflowing.
+fn domain_is_at_least(radix: u32, exponent: usize, minimum: u128) -> bool {
+    let mut domain_size = 1u128;
+    for _ in 0..exponent {
+        domain_size = domain_size.saturating_mul(radix as u128);
+        if domain_size >= minimum {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// NUM_radix: the number represented by digits, most significant digit first.
+fn num_radix(digits: &[u16], radix: u32) -> u128 {
+    digits.iter().fold(0u128, |number, digit| number * radix as u128 + *digit as u128)
+}
+
+/// STR^m_radix: the m digits representing number, most significant digit first.
+fn str_radix(mut number: u128, radix: u32, m: usize) -> Vec<u16> {
+    let mut digits = vec![0u16; m];
+    for digit in digits.iter_mut().rev() {
+        *digit = (number % radix as u128) as u16;
+        number /= radix as u128;
+    }
+
+    digits
+}
+
+/// Samples 1 to 3 of NIST's FF1 examples, for AES-128.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: Key = Key([
+        0x2b, 0x7e, 0x15, 0x16,
+        0x28, 0xae, 0xd2, 0xa6,
+        0xab, 0xf7, 0x15, 0x88,
+        0x09, 0xcf, 0x4f, 0x3c
+    ]);
+    const DIGITS: [u16; 10] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+
+    #[test]
+    fn encrypts_sample_1_without_tweak() {
+        let actual = encrypt_ff1(&KEY, &[], 10, &DIGITS);
+
+        assert_eq!(actual, Ok(vec![2, 4, 3, 3, 4, 7, 7, 4, 8, 4]));
+    }
+
+    #[test]
+    fn encrypts_sample_2_with_tweak() {
+        let tweak = [0x39, 0x38, 0x37, 0x36, 0x35, 0x34, 0x33, 0x32, 0x31, 0x30];
+
+        let actual = encrypt_ff1(&KEY, &tweak, 10, &DIGITS);
+
+        assert_eq!(actual, Ok(vec![6, 1, 2, 4, 2, 0, 0, 7, 7, 3]));
+    }
+
+    #[test]
+    fn encrypts_sample_3_in_radix_36() {
+        let tweak = [0x37, 0x37, 0x37, 0x37, 0x70, 0x71, 0x72, 0x73, 0x37, 0x37, 0x37];
+        // 0123456789abcdefghi
+        let input: Vec<u16> = (0..19).collect();
+
+        let actual = encrypt_ff1(&KEY, &tweak, 36, &input);
+
+        // a9tv40mll9kdu509eum
+        assert_eq!(actual, Ok(vec![
+            10, 9, 29, 31, 4, 0, 22, 21, 21, 9,
+            20, 13, 30, 5, 0, 9, 14, 30, 22
+        ]));
+    }
+
+    #[test]
+    fn decrypts_to_input() {
+        let tweak = [0x39, 0x38, 0x37, 0x36, 0x35, 0x34, 0x33, 0x32, 0x31, 0x30];
+        let card_number = [4, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1];
+
+        let cipher = encrypt_ff1(&KEY, &tweak, 10, &card_number).unwrap();
+
+        assert_eq!(cipher.len(), card_number.len());
+        assert!(cipher.iter().all(|digit| *digit < 10));
+        assert_eq!(decrypt_ff1(&KEY, &tweak, 10, &cipher), Ok(card_number.to_vec()));
+        assert_eq!(decrypt_ff1(&KEY, &[], 10, &[2, 4, 3, 3, 4, 7, 7, 4, 8, 4]), Ok(DIGITS.to_vec()));
+    }
+
+    #[test]
+    fn rejects_invalid_digits_and_lengths() {
+        assert_eq!(encrypt_ff1(&KEY, &[], 10, &[0, 1, 2, 3, 4, 10]), Err(AesError::InvalidDigit));
+        assert_eq!(encrypt_ff1(&KEY, &[], 1, &[0; 10]), Err(AesError::InvalidDigit));
+        // 10^5 is below the minimum domain size
+        assert_eq!(encrypt_ff1(&KEY, &[], 10, &[0; 5]), Err(AesError::InvalidInputLength));
+        assert_eq!(encrypt_ff1(&KEY, &[], 10, &[0; 73]), Err(AesError::InputTooLong));
+        assert!(encrypt_ff1(&KEY, &[], 10, &[0; 72]).is_ok());
+    }
+}
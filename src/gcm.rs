@@ -0,0 +1,772 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+use BLOCK_SIZE;
+use cipher::encrypt_block;
+use ctr::inc32;
+use constant_time::{verified_decrypt, verify_eq};
+use error::{AesError, TagMismatch};
+use key::{Key, KeySchedule, wipe};
+use math::multiply_in_gf128;
+use tag::Tag;
+
+/// Galois/Counter Mode, as specified in:
+/// https://nvlpubs.nist.gov/nistpubs/Legacy/SP/nistspecialpublication800-38d.pdf
+/// Only 96-bit nonces, the recommended length, are supported.
+pub type GcmNonce = [u8; 12];
+
+/// Size in bytes of the authentication tag.
+pub const TAG_SIZE: usize = 16;
+
+/// Maximum plaintext size in bytes, 2^39 - 256 bits (Sec. 5.2.1.1). Past it, the 32-bit counter
+/// would wrap around and reuse keystream.
+pub const MAX_PLAINTEXT_SIZE: u64 = (1 << 36) - 32;
+
+/// Maximum additional authenticated data size in bytes, 2^64 - 1 bits (Sec. 5.2.1.1), so that
+/// its bit length fits in the 64-bit length field.
+pub const MAX_AAD_SIZE: u64 = (1 << 61) - 1;
+
+/// Size in bytes of the chunks read by decrypt_gcm_streaming.
+pub const STREAMING_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Encrypts plaintext and authenticates it along with the additional authenticated data,
+/// returning the ciphertext and the tag separately, for wire formats that store the tag apart from
+/// the ciphertext. Errors with InputTooLong if the plaintext or the additional authenticated data
+/// exceed MAX_PLAINTEXT_SIZE or MAX_AAD_SIZE.
+pub fn encrypt_gcm_detached(
+    plaintext: &[u8],
+    key: &Key,
+    nonce: &GcmNonce,
+    aad: &[u8],
+) -> Result<(Vec<u8>, Tag), AesError> {
+    check_lengths(plaintext.len() as u64, aad.len() as u64)?;
+
+    let w = &key.do_key_expansion().0;
+    let j0 = pre_counter_block(nonce);
+
+    let mut ciphertext = plaintext.to_vec();
+    Gctr::new(w, &j0).apply(&mut ciphertext);
+
+    let mut ghash = Ghash::new(w, aad);
+    ghash.update(&ciphertext);
+    let tag = ghash.finalize(w, &j0);
+
+    Ok((ciphertext, Tag::from(tag)))
+}
+
+/// Verifies the tag, given apart from the ciphertext, over the ciphertext and the additional
+/// authenticated data, then decrypts the ciphertext. Nothing is decrypted if the tag doesn't
+/// match. Inputs longer than GCM's limits are rejected with InputTooLong.
+pub fn decrypt_gcm_detached(
+    ciphertext: &[u8],
+    tag: &Tag,
+    key: &Key,
+    nonce: &GcmNonce,
+    aad: &[u8],
+) -> Result<Vec<u8>, AesError> {
+    check_lengths(ciphertext.len() as u64, aad.len() as u64)?;
+
+    let w = &key.do_key_expansion().0;
+    let j0 = pre_counter_block(nonce);
+
+    verified_decrypt(
+        ciphertext,
+        tag.as_bytes(),
+        |ciphertext| {
+            let mut ghash = Ghash::new(w, aad);
+            ghash.update(ciphertext);
+            ghash.finalize(w, &j0)
+        },
+        |ciphertext| {
+            let mut plaintext = ciphertext.to_vec();
+            Gctr::new(w, &j0).apply(&mut plaintext);
+            plaintext
+        },
+    )
+}
+
+/// Encrypts like encrypt_gcm_detached, which it's the former name of.
+#[deprecated(note = "Use encrypt_gcm_detached, which takes the same arguments.")]
+pub fn encrypt_gcm(
+    plaintext: &[u8],
+    key: &Key,
+    nonce: &GcmNonce,
+    aad: &[u8],
+) -> Result<(Vec<u8>, Tag), AesError> {
+    encrypt_gcm_detached(plaintext, key, nonce, aad)
+}
+
+/// Verifies and decrypts like decrypt_gcm_detached, with the tag as the last argument.
+#[deprecated(note = "Use decrypt_gcm_detached, which takes the tag right after the ciphertext.")]
+pub fn decrypt_gcm(
+    ciphertext: &[u8],
+    key: &Key,
+    nonce: &GcmNonce,
+    aad: &[u8],
+    tag: &Tag,
+) -> Result<Vec<u8>, AesError> {
+    decrypt_gcm_detached(ciphertext, tag, key, nonce, aad)
+}
+
+/// Encrypts like encrypt_gcm_detached, returning the ciphertext with the tag appended:
+/// ciphertext || tag (TAG_SIZE bytes).
+pub fn encrypt_gcm_attached(
+    plaintext: &[u8],
+    key: &Key,
+    nonce: &GcmNonce,
+    aad: &[u8],
+) -> Result<Vec<u8>, AesError> {
+    let (ciphertext, tag) = encrypt_gcm_detached(plaintext, key, nonce, aad)?;
+
+    Ok([&ciphertext[..], tag.as_bytes()].concat())
+}
+
+/// Splits off the trailing TAG_SIZE bytes of the output of encrypt_gcm_attached as its tag, then
+/// verifies and decrypts like decrypt_gcm_detached. Fails with InvalidInputLength if ciphertext_and_tag is
+/// shorter than a tag.
+pub fn decrypt_gcm_attached(
+    ciphertext_and_tag: &[u8],
+    key: &Key,
+    nonce: &GcmNonce,
+    aad: &[u8],
+) -> Result<Vec<u8>, AesError> {
+    if ciphertext_and_tag.len() < TAG_SIZE {
+        return Err(AesError::InvalidInputLength);
+    }
+    let (ciphertext, tag_bytes) = ciphertext_and_tag.split_at(ciphertext_and_tag.len() - TAG_SIZE);
+    let mut tag = [0u8; TAG_SIZE];
+    tag.copy_from_slice(tag_bytes);
+
+    decrypt_gcm_detached(ciphertext, &Tag::from(tag), key, nonce, aad)
+}
+
+/// GMAC: GCM used only to authenticate data, sent in the clear, as additional authenticated data
+/// with an empty plaintext. As with GCM, a nonce must never be reused under the same key. data
+/// can't practically exceed MAX_AAD_SIZE, which isn't checked.
+pub fn gmac(key: &Key, nonce: &GcmNonce, data: &[u8]) -> Tag {
+    let mut gmac = Gmac::new(key);
+    gmac.update(data);
+
+    gmac.finalize(nonce)
+}
+
+/// Verifies in constant time that tag is the GMAC of data.
+pub fn gmac_verify(
+    key: &Key,
+    nonce: &GcmNonce,
+    data: &[u8],
+    tag: &Tag,
+) -> Result<(), AesError> {
+    let computed_tag = gmac(key, nonce, data);
+    if computed_tag != *tag {
+        let computed_tag = computed_tag.to_bytes();
+        return Err(AesError::AuthenticationFailed(TagMismatch::new(Some(computed_tag))));
+    }
+
+    Ok(())
+}
+
+/// Incremental GMAC, computing the same tag as gmac over data passed in any number of chunks,
+/// e.g. a file too large to hold in memory copied into it with io::copy.
+pub struct Gmac {
+    schedule: KeySchedule,
+    ghash: Ghash,
+}
+
+impl Gmac {
+    pub fn new(key: &Key) -> Self {
+        let schedule = key.do_key_expansion();
+        let ghash = Ghash::new(&schedule.0, &[]);
+
+        Gmac { schedule, ghash }
+    }
+
+    /// Authenticates data following the data of previous calls.
+    pub fn update(&mut self, data: &[u8]) {
+        self.ghash.update_aad(data);
+    }
+
+    /// Computes the tag of all the data passed to update. As with gmac, a nonce must never be
+    /// reused under the same key.
+    pub fn finalize(self, nonce: &GcmNonce) -> Tag {
+        Tag::from(self.ghash.finalize(&self.schedule.0, &pre_counter_block(nonce)))
+    }
+}
+
+/// Writing to a Gmac authenticates the bytes written, and never fails.
+impl Write for Gmac {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Decrypts a ciphertext read from input in chunks of STREAMING_CHUNK_SIZE, writing the
+/// plaintext to output and returning its length.
+///
+/// Plaintext can't be released before the tag is verified, which only happens once the whole
+/// ciphertext has been read. The decrypted chunks are therefore held back in memory, which grows
+/// with the size of the ciphertext, and are only written to output after successful
+/// verification. On failure, nothing is written to output and the held back plaintext is wiped.
+pub fn decrypt_gcm_streaming<R: Read, W: Write>(
+    mut input: R,
+    mut output: W,
+    key: &Key,
+    nonce: &GcmNonce,
+    aad: &[u8],
+    tag: &Tag,
+) -> Result<usize, AesError> {
+    check_lengths(0, aad.len() as u64)?;
+
+    let w = &key.do_key_expansion().0;
+    let j0 = pre_counter_block(nonce);
+    let mut ghash = Ghash::new(w, aad);
+    let mut gctr = Gctr::new(w, &j0);
+
+    let mut chunk = vec![0u8; STREAMING_CHUNK_SIZE];
+    let mut held_back_plaintext: Vec<u8> = Vec::new();
+    loop {
+        let read = match input.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(ref error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(error) => return Err(AesError::Io(error.kind())),
+        };
+
+        if let Err(error) = check_lengths((held_back_plaintext.len() + read) as u64, 0) {
+            wipe(&mut held_back_plaintext);
+            return Err(error);
+        }
+
+        ghash.update(&chunk[..read]);
+
+        let start = held_back_plaintext.len();
+        held_back_plaintext.extend_from_slice(&chunk[..read]);
+        gctr.apply(&mut held_back_plaintext[start..]);
+    }
+
+    let computed_tag = ghash.finalize(w, &j0);
+    if !verify_eq(&computed_tag, tag.as_bytes()) {
+        wipe(&mut held_back_plaintext);
+        return Err(AesError::AuthenticationFailed(TagMismatch::new(Some(computed_tag))));
+    }
+
+    output.write_all(&held_back_plaintext)
+        .map_err(|error| AesError::Io(error.kind()))?;
+
+    Ok(held_back_plaintext.len())
+}
+
+fn check_lengths(text_size: u64, aad_size: u64) -> Result<(), AesError> {
+    if text_size > MAX_PLAINTEXT_SIZE || aad_size > MAX_AAD_SIZE {
+        return Err(AesError::InputTooLong);
+    }
+
+    Ok(())
+}
+
+/// Last block absorbed by GHASH, [len(A)]64 || [len(C)]64 with the lengths in bits. An empty
+/// ciphertext, as when only authenticating aad, is encoded as 64 zero bits.
+fn length_block(aad_length: u64, ciphertext_length: u64) -> u128 {
+    let aad_bits = aad_length as u128 * 8;
+    let ciphertext_bits = ciphertext_length as u128 * 8;
+
+    (aad_bits << 64) | ciphertext_bits
+}
+
+/// Pre-counter block J0 for a 96-bit nonce: nonce || 0^31 || 1.
+fn pre_counter_block(nonce: &GcmNonce) -> [u8; BLOCK_SIZE] {
+    let mut j0 = [0u8; BLOCK_SIZE];
+    j0[..nonce.len()].copy_from_slice(nonce);
+    j0[BLOCK_SIZE - 1] = 1;
+
+    j0
+}
+
+/// GCTR keystream starting at inc32(J0), which can be applied to data in chunks of any length.
+struct Gctr<'a> {
+    w: &'a [[u8; 4]],
+    counter_block: [u8; BLOCK_SIZE],
+    keystream_block: [u8; BLOCK_SIZE],
+    offset: usize,
+}
+
+impl<'a> Gctr<'a> {
+    fn new(w: &'a [[u8; 4]], j0: &[u8; BLOCK_SIZE]) -> Self {
+        let mut counter_block = *j0;
+        inc32(&mut counter_block);
+
+        Gctr {
+            w,
+            counter_block,
+            keystream_block: [0u8; BLOCK_SIZE],
+            offset: BLOCK_SIZE,
+        }
+    }
+
+    fn apply(&mut self, data: &mut [u8]) {
+        for byte in data.iter_mut() {
+            if self.offset == BLOCK_SIZE {
+                self.keystream_block = encrypt_block(&self.counter_block, self.w);
+                inc32(&mut self.counter_block);
+                self.offset = 0;
+            }
+
+            *byte ^= self.keystream_block[self.offset];
+            self.offset += 1;
+        }
+    }
+}
+
+/// GHASH over the additional authenticated data and a ciphertext fed in chunks of any length.
+struct Ghash {
+    h: u128,
+    y: u128,
+    partial_block: Vec<u8>,
+    aad_length: u64,
+    ciphertext_length: u64,
+}
+
+impl Ghash {
+    fn new(w: &[[u8; 4]], aad: &[u8]) -> Self {
+        let h = u128::from_be_bytes(encrypt_block(&[0u8; BLOCK_SIZE], w));
+        let mut ghash = Ghash {
+            h,
+            y: 0,
+            partial_block: Vec::with_capacity(BLOCK_SIZE),
+            aad_length: aad.len() as u64,
+            ciphertext_length: 0,
+        };
+
+        ghash.absorb(aad);
+        ghash.absorb_partial_block();
+
+        ghash
+    }
+
+    /// Absorbs additional authenticated data following that given to new. This must come before
+    /// any ciphertext, as the data is only padded to a complete block at finalization.
+    fn update_aad(&mut self, aad: &[u8]) {
+        self.aad_length += aad.len() as u64;
+        self.absorb(aad);
+    }
+
+    fn update(&mut self, ciphertext: &[u8]) {
+        self.ciphertext_length += ciphertext.len() as u64;
+        self.absorb(ciphertext);
+    }
+
+    /// Computes the tag, E(K, J0) XOR GHASH(A || 0^v || C || 0^u || [len(A)]64 || [len(C)]64).
+    fn finalize(mut self, w: &[[u8; 4]], j0: &[u8; BLOCK_SIZE]) -> [u8; TAG_SIZE] {
+        self.absorb_partial_block();
+        self.absorb_block(length_block(self.aad_length, self.ciphertext_length));
+
+        let encrypted_j0 = u128::from_be_bytes(encrypt_block(j0, w));
+
+        (encrypted_j0 ^ self.y).to_be_bytes()
+    }
+
+    fn absorb(&mut self, mut data: &[u8]) {
+        if !self.partial_block.is_empty() {
+            let missing = (BLOCK_SIZE - self.partial_block.len()).min(data.len());
+            self.partial_block.extend_from_slice(&data[..missing]);
+            data = &data[missing..];
+
+            if self.partial_block.len() == BLOCK_SIZE {
+                self.absorb_partial_block();
+            }
+        }
+
+        let mut blocks = data.chunks_exact(BLOCK_SIZE);
+        for block in blocks.by_ref() {
+            let mut bytes = [0u8; BLOCK_SIZE];
+            bytes.copy_from_slice(block);
+            self.absorb_block(u128::from_be_bytes(bytes));
+        }
+        self.partial_block.extend_from_slice(blocks.remainder());
+    }
+
+    /// Absorbs the buffered bytes, if any, padded with zeros to a full block.
+    fn absorb_partial_block(&mut self) {
+        if self.partial_block.is_empty() {
+            return;
+        }
+
+        let mut bytes = [0u8; BLOCK_SIZE];
+        bytes[..self.partial_block.len()].copy_from_slice(&self.partial_block);
+        self.partial_block.clear();
+        self.absorb_block(u128::from_be_bytes(bytes));
+    }
+
+    fn absorb_block(&mut self, block: u128) {
+        self.y = multiply_in_gf128(self.y ^ block, self.h);
+    }
+}
+
+/// Test cases are taken from the GCM specification:
+/// https://csrc.nist.rip/groups/ST/toolkit/BCM/documents/proposedmodes/gcm/gcm-spec.pdf
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: Key = Key([
+        0xfe, 0xff, 0xe9, 0x92,
+        0x86, 0x65, 0x73, 0x1c,
+        0x6d, 0x6a, 0x8f, 0x94,
+        0x67, 0x30, 0x83, 0x08
+    ]);
+    const NONCE: GcmNonce = [
+        0xca, 0xfe, 0xba, 0xbe,
+        0xfa, 0xce, 0xdb, 0xad,
+        0xde, 0xca, 0xf8, 0x88
+    ];
+    const AAD: [u8; 20] = [
+        0xfe, 0xed, 0xfa, 0xce, 0xde, 0xad, 0xbe, 0xef,
+        0xfe, 0xed, 0xfa, 0xce, 0xde, 0xad, 0xbe, 0xef,
+        0xab, 0xad, 0xda, 0xd2
+    ];
+    const RAW: [u8; 60] = [
+        0xd9, 0x31, 0x32, 0x25, 0xf8, 0x84, 0x06, 0xe5,
+        0xa5, 0x59, 0x09, 0xc5, 0xaf, 0xf5, 0x26, 0x9a,
+        0x86, 0xa7, 0xa9, 0x53, 0x15, 0x34, 0xf7, 0xda,
+        0x2e, 0x4c, 0x30, 0x3d, 0x8a, 0x31, 0x8a, 0x72,
+        0x1c, 0x3c, 0x0c, 0x95, 0x95, 0x68, 0x09, 0x53,
+        0x2f, 0xcf, 0x0e, 0x24, 0x49, 0xa6, 0xb5, 0x25,
+        0xb1, 0x6a, 0xed, 0xf5, 0xaa, 0x0d, 0xe6, 0x57,
+        0xba, 0x63, 0x7b, 0x39
+    ];
+    const CIPHERED: [u8; 60] = [
+        0x42, 0x83, 0x1e, 0xc2, 0x21, 0x77, 0x74, 0x24,
+        0x4b, 0x72, 0x21, 0xb7, 0x84, 0xd0, 0xd4, 0x9c,
+        0xe3, 0xaa, 0x21, 0x2f, 0x2c, 0x02, 0xa4, 0xe0,
+        0x35, 0xc1, 0x7e, 0x23, 0x29, 0xac, 0xa1, 0x2e,
+        0x21, 0xd5, 0x14, 0xb2, 0x54, 0x66, 0x93, 0x1c,
+        0x7d, 0x8f, 0x6a, 0x5a, 0xac, 0x84, 0xaa, 0x05,
+        0x1b, 0xa3, 0x0b, 0x39, 0x6a, 0x0a, 0xac, 0x97,
+        0x3d, 0x58, 0xe0, 0x91
+    ];
+    const TAG: Tag = Tag::from_bytes(&[
+        0x5b, 0xc9, 0x4f, 0xbc, 0x32, 0x21, 0xa5, 0xdb,
+        0x94, 0xfa, 0xe9, 0x5a, 0xe7, 0x12, 0x1a, 0x47
+    ]);
+
+    #[test]
+    fn encrypts_empty_plaintext() {
+        // test case 1
+        let expected_tag = [
+            0x58, 0xe2, 0xfc, 0xce, 0xfa, 0x7e, 0x30, 0x61,
+            0x36, 0x7f, 0x1d, 0x57, 0xa4, 0xe7, 0x45, 0x5a
+        ];
+
+        let (ciphertext, tag) = encrypt_gcm_detached(&[], &Key([0; 16]), &[0; 12], &[]).unwrap();
+
+        assert!(ciphertext.is_empty());
+        assert_eq!(tag, Tag::from(expected_tag));
+    }
+
+    #[test]
+    fn encrypts_single_block() {
+        // test case 2
+        let expected_ciphertext = [
+            0x03, 0x88, 0xda, 0xce, 0x60, 0xb6, 0xa3, 0x92,
+            0xf3, 0x28, 0xc2, 0xb9, 0x71, 0xb2, 0xfe, 0x78
+        ];
+        let expected_tag = [
+            0xab, 0x6e, 0x47, 0xd4, 0x2c, 0xec, 0x13, 0xbd,
+            0xf5, 0x3a, 0x67, 0xb2, 0x12, 0x57, 0xbd, 0xdf
+        ];
+
+        let (ciphertext, tag) = encrypt_gcm_detached(&[0; 16], &Key([0; 16]), &[0; 12], &[]).unwrap();
+
+        assert_eq!(ciphertext, expected_ciphertext);
+        assert_eq!(tag, Tag::from(expected_tag));
+    }
+
+    #[test]
+    fn encrypts_with_aad() {
+        // test case 4
+        let (ciphertext, tag) = encrypt_gcm_detached(&RAW, &KEY, &NONCE, &AAD).unwrap();
+
+        assert_eq!(ciphertext, CIPHERED.to_vec());
+        assert_eq!(tag, TAG);
+    }
+
+    #[test]
+    fn decrypts_with_aad() {
+        assert_eq!(decrypt_gcm_detached(&CIPHERED, &TAG, &KEY, &NONCE, &AAD), Ok(RAW.to_vec()));
+    }
+
+    #[test]
+    fn decryption_fails_on_tampered_ciphertext_aad_or_tag() {
+        let mut tampered_ciphertext = CIPHERED;
+        tampered_ciphertext[0] ^= 0x01;
+        let mut tampered_tag = TAG.to_bytes();
+        tampered_tag[TAG_SIZE - 1] ^= 0x80;
+        let tampered_tag = Tag::from(tampered_tag);
+
+        assert!(matches!(
+            decrypt_gcm_detached(&tampered_ciphertext, &TAG, &KEY, &NONCE, &AAD),
+            Err(AesError::AuthenticationFailed(_))
+        ));
+        assert!(matches!(
+            decrypt_gcm_detached(&CIPHERED, &TAG, &KEY, &NONCE, &AAD[1..]),
+            Err(AesError::AuthenticationFailed(_))
+        ));
+        // the computed tag is that of the untampered data
+        assert_eq!(
+            decrypt_gcm_detached(&CIPHERED, &tampered_tag, &KEY, &NONCE, &AAD),
+            Err(AesError::AuthenticationFailed(TagMismatch::new(Some(TAG.to_bytes()))))
+        );
+    }
+
+    #[test]
+    fn detached_tag_round_trips() {
+        let (ciphertext, tag) = encrypt_gcm_detached(&RAW, &KEY, &NONCE, &AAD).unwrap();
+
+        assert_eq!((ciphertext.clone(), tag), (CIPHERED.to_vec(), TAG));
+        assert_eq!(decrypt_gcm_detached(&ciphertext, &tag, &KEY, &NONCE, &AAD), Ok(RAW.to_vec()));
+        assert!(matches!(
+            decrypt_gcm_detached(&ciphertext, &Tag::from([0x00; TAG_SIZE]), &KEY, &NONCE, &AAD),
+            Err(AesError::AuthenticationFailed(_))
+        ));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_names_work_like_detached_functions() {
+        assert_eq!(encrypt_gcm(&RAW, &KEY, &NONCE, &AAD), Ok((CIPHERED.to_vec(), TAG)));
+        assert_eq!(decrypt_gcm(&CIPHERED, &KEY, &NONCE, &AAD, &TAG), Ok(RAW.to_vec()));
+    }
+
+    #[test]
+    fn attached_tag_is_appended_to_ciphertext() {
+        let ciphertext_and_tag = encrypt_gcm_attached(&RAW, &KEY, &NONCE, &AAD).unwrap();
+
+        assert_eq!(ciphertext_and_tag, [&CIPHERED[..], TAG.as_bytes()].concat());
+        assert_eq!(decrypt_gcm_attached(&ciphertext_and_tag, &KEY, &NONCE, &AAD), Ok(RAW.to_vec()));
+    }
+
+    #[test]
+    fn attached_tag_of_empty_plaintext_is_whole_input() {
+        let tag_only = encrypt_gcm_attached(&[], &KEY, &NONCE, &AAD).unwrap();
+
+        assert_eq!(tag_only.len(), TAG_SIZE);
+        assert_eq!(decrypt_gcm_attached(&tag_only, &KEY, &NONCE, &AAD), Ok(vec![]));
+    }
+
+    #[test]
+    fn decrypt_attached_rejects_input_shorter_than_tag_or_truncated() {
+        let ciphertext_and_tag = encrypt_gcm_attached(&RAW, &KEY, &NONCE, &AAD).unwrap();
+
+        assert_eq!(
+            decrypt_gcm_attached(&ciphertext_and_tag[..TAG_SIZE - 1], &KEY, &NONCE, &AAD),
+            Err(AesError::InvalidInputLength)
+        );
+        // the last ciphertext byte is then read as part of the tag
+        assert!(matches!(
+            decrypt_gcm_attached(&ciphertext_and_tag[..ciphertext_and_tag.len() - 1], &KEY, &NONCE, &AAD),
+            Err(AesError::AuthenticationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn decrypts_streaming() {
+        let mut output = Vec::new();
+
+        let length = decrypt_gcm_streaming(&CIPHERED[..], &mut output, &KEY, &NONCE, &AAD, &TAG);
+
+        assert_eq!(length, Ok(RAW.len()));
+        assert_eq!(output, RAW.to_vec());
+    }
+
+    #[test]
+    fn decrypts_streaming_over_several_chunks() {
+        let raw: Vec<u8> = (0..STREAMING_CHUNK_SIZE + 7).map(|i| i as u8).collect();
+        let (ciphertext, tag) = encrypt_gcm_detached(&raw, &KEY, &NONCE, &AAD).unwrap();
+        let mut output = Vec::new();
+
+        let length = decrypt_gcm_streaming(&ciphertext[..], &mut output, &KEY, &NONCE, &AAD, &tag);
+
+        assert_eq!(length, Ok(raw.len()));
+        assert_eq!(output, raw);
+    }
+
+    #[test]
+    fn streaming_decryption_of_tampered_ciphertext_outputs_nothing() {
+        let raw = vec![0x42u8; STREAMING_CHUNK_SIZE + 1];
+        let (mut ciphertext, tag) = encrypt_gcm_detached(&raw, &KEY, &NONCE, &AAD).unwrap();
+        let last = ciphertext.len() - 1;
+        ciphertext[last] ^= 0x01;
+        let mut output = Vec::new();
+
+        let length = decrypt_gcm_streaming(&ciphertext[..], &mut output, &KEY, &NONCE, &AAD, &tag);
+
+        assert!(matches!(length, Err(AesError::AuthenticationFailed(_))));
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn authentication_failure_carries_computed_tag_in_debug_builds() {
+        let mut tampered_tag = TAG.to_bytes();
+        tampered_tag[0] ^= 0x01;
+        let tampered_tag = Tag::from(tampered_tag);
+
+        let result = decrypt_gcm_detached(&CIPHERED, &tampered_tag, &KEY, &NONCE, &AAD);
+
+        match result {
+            Err(AesError::AuthenticationFailed(mismatch)) => {
+                assert_eq!(mismatch.computed_tag, Some(TAG.to_bytes()))
+            }
+            _ => panic!("Expected an authentication failure."),
+        }
+    }
+
+    #[test]
+    fn limits_are_those_of_the_spec_in_bits() {
+        assert_eq!(MAX_PLAINTEXT_SIZE * 8, (1 << 39) - 256);
+        assert_eq!(MAX_AAD_SIZE * 8, u64::MAX - 7);
+    }
+
+    #[test]
+    fn accepts_lengths_up_to_limits() {
+        assert_eq!(check_lengths(MAX_PLAINTEXT_SIZE, 0), Ok(()));
+        assert_eq!(check_lengths(0, MAX_AAD_SIZE), Ok(()));
+        assert_eq!(check_lengths(MAX_PLAINTEXT_SIZE, MAX_AAD_SIZE), Ok(()));
+    }
+
+    #[test]
+    fn rejects_lengths_past_limits() {
+        assert_eq!(check_lengths(MAX_PLAINTEXT_SIZE + 1, 0), Err(AesError::InputTooLong));
+        assert_eq!(check_lengths(0, MAX_AAD_SIZE + 1), Err(AesError::InputTooLong));
+        assert_eq!(check_lengths(u64::MAX, u64::MAX), Err(AesError::InputTooLong));
+    }
+
+    /// gcmEncryptExtIV128.rsp, [PTlen = 0] [AADlen = 128], Count = 0 and 1.
+    #[test]
+    fn gmac_and_gcm_match_nist_aad_only_vectors() {
+        struct TestCase {
+            key: Key,
+            nonce: GcmNonce,
+            aad: [u8; 16],
+            tag: Tag,
+        }
+
+        let test_cases = [
+            TestCase {
+                key: Key([
+                    0x77, 0xbe, 0x63, 0x70, 0x89, 0x71, 0xc4, 0xe2,
+                    0x40, 0xd1, 0xcb, 0x79, 0xe8, 0xd7, 0x7f, 0xeb
+                ]),
+                nonce: [0xe0, 0xe0, 0x0f, 0x19, 0xfe, 0xd7, 0xba, 0x01, 0x36, 0xa7, 0x97, 0xf3],
+                aad: [
+                    0x7a, 0x43, 0xec, 0x1d, 0x9c, 0x0a, 0x5a, 0x78,
+                    0xa0, 0xb1, 0x65, 0x33, 0xa6, 0x21, 0x3c, 0xab
+                ],
+                tag: Tag::from([
+                    0x20, 0x9f, 0xcc, 0x8d, 0x36, 0x75, 0xed, 0x93,
+                    0x8e, 0x9c, 0x71, 0x66, 0x70, 0x9d, 0xd9, 0x46
+                ]),
+            },
+            TestCase {
+                key: Key([
+                    0x76, 0x80, 0xc5, 0xd3, 0xca, 0x61, 0x54, 0x75,
+                    0x8e, 0x51, 0x0f, 0x4d, 0x25, 0xb9, 0x88, 0x20
+                ]),
+                nonce: [0xf8, 0xf1, 0x05, 0xf9, 0xc3, 0xdf, 0x49, 0x65, 0x78, 0x03, 0x21, 0xf8],
+                aad: [
+                    0xc9, 0x4c, 0x41, 0x01, 0x94, 0xc7, 0x65, 0xe3,
+                    0xdc, 0xc7, 0x96, 0x43, 0x79, 0x75, 0x8e, 0xd3
+                ],
+                tag: Tag::from([
+                    0x94, 0xdc, 0xa8, 0xed, 0xfc, 0xf9, 0x0b, 0xb7,
+                    0x4b, 0x15, 0x3c, 0x8d, 0x48, 0xa1, 0x79, 0x30
+                ]),
+            },
+        ];
+
+        for test_case in test_cases.iter() {
+            assert_eq!(gmac(&test_case.key, &test_case.nonce, &test_case.aad), test_case.tag);
+            assert_eq!(gmac_verify(&test_case.key, &test_case.nonce, &test_case.aad, &test_case.tag), Ok(()));
+
+            let (ciphertext, tag) =
+                encrypt_gcm_detached(b"", &test_case.key, &test_case.nonce, &test_case.aad).unwrap();
+            assert!(ciphertext.is_empty());
+            assert_eq!(tag, test_case.tag);
+            assert_eq!(
+                decrypt_gcm_detached(&[], &test_case.tag, &test_case.key, &test_case.nonce, &test_case.aad),
+                Ok(Vec::new())
+            );
+        }
+    }
+
+    #[test]
+    fn length_block_encodes_lengths_in_bits() {
+        // 16 bytes of aad and no ciphertext: 128 bits, then 64 zero bits
+        assert_eq!(length_block(16, 0), 0x80 << 64);
+        assert_eq!(length_block(20, 60), (160 << 64) | 480);
+        assert_eq!(length_block(0, 0), 0);
+    }
+
+    #[test]
+    fn gmac_is_tag_of_gcm_with_empty_plaintext() {
+        let (_, tag) = encrypt_gcm_detached(&[], &KEY, &NONCE, &AAD).unwrap();
+
+        assert_eq!(gmac(&KEY, &NONCE, &AAD), tag);
+    }
+
+    #[test]
+    fn incremental_gmac_matches_one_shot_gmac_whatever_the_chunk_sizes() {
+        let data: Vec<u8> = (0..100).map(|i| (i * 7 + 3) as u8).collect();
+        let expected = gmac(&KEY, &NONCE, &data);
+
+        for chunk_size in [1, 5, 15, 16, 17, 32, 33, 99, 100].iter() {
+            let mut incremental = Gmac::new(&KEY);
+            for chunk in data.chunks(*chunk_size) {
+                incremental.update(chunk);
+            }
+
+            assert_eq!(incremental.finalize(&NONCE), expected, "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn incremental_gmac_handles_empty_updates_and_split_boundaries() {
+        for split in 0..=AAD.len() {
+            let mut incremental = Gmac::new(&KEY);
+            incremental.update(&[]);
+            incremental.update(&AAD[..split]);
+            incremental.update(&[]);
+            incremental.update(&AAD[split..]);
+
+            assert_eq!(incremental.finalize(&NONCE), gmac(&KEY, &NONCE, &AAD), "split at {}", split);
+        }
+    }
+
+    #[test]
+    fn gmac_of_reader_through_io_copy() {
+        let data = vec![0x42; 3 * STREAMING_CHUNK_SIZE + 5];
+        let mut incremental = Gmac::new(&KEY);
+
+        let copied = io::copy(&mut &data[..], &mut incremental).unwrap();
+
+        assert_eq!(copied, data.len() as u64);
+        assert_eq!(incremental.finalize(&NONCE), gmac(&KEY, &NONCE, &data));
+    }
+
+    #[test]
+    fn gmac_verify_rejects_tampered_data() {
+        let tag = gmac(&KEY, &NONCE, &AAD);
+        let mut tampered = AAD.to_vec();
+        tampered[0] ^= 0x01;
+
+        assert!(matches!(
+            gmac_verify(&KEY, &NONCE, &tampered, &tag),
+            Err(AesError::AuthenticationFailed(_))
+        ));
+    }
+}
@@ -0,0 +1,289 @@
+use BLOCK_SIZE;
+use cipher::encrypt_block;
+use constant_time::verify_eq;
+use error::{AesError, TagMismatch};
+use gcm::{GcmNonce, TAG_SIZE};
+use key::Key;
+use math::multiply_in_gf128;
+use tag::Tag;
+
+/// AES-GCM-SIV, nonce misuse-resistant authenticated encryption, as specified in:
+/// https://www.rfc-editor.org/rfc/rfc8452
+/// Repeating a nonce only reveals whether the same plaintext and additional authenticated data
+/// were encrypted again, instead of breaking confidentiality and authenticity as with GCM.
+pub fn encrypt_gcm_siv(
+    plaintext: &[u8],
+    key: &Key,
+    nonce: &GcmNonce,
+    aad: &[u8],
+) -> (Vec<u8>, Tag) {
+    let (authentication_key, encryption_key) = derive_keys(key, nonce);
+    let w = &encryption_key.do_key_expansion().0;
+
+    let tag = compute_tag(w, &authentication_key, nonce, plaintext, aad);
+
+    let mut ciphertext = plaintext.to_vec();
+    apply_ctr(w, &tag, &mut ciphertext);
+
+    (ciphertext, Tag::from(tag))
+}
+
+/// Decrypts the ciphertext, then checks the tag over the plaintext and the additional
+/// authenticated data. The plaintext is only returned if the tag matches.
+pub fn decrypt_gcm_siv(
+    ciphertext: &[u8],
+    key: &Key,
+    nonce: &GcmNonce,
+    aad: &[u8],
+    tag: &Tag,
+) -> Result<Vec<u8>, AesError> {
+    let (authentication_key, encryption_key) = derive_keys(key, nonce);
+    let w = &encryption_key.do_key_expansion().0;
+
+    let mut plaintext = ciphertext.to_vec();
+    apply_ctr(w, tag.as_bytes(), &mut plaintext);
+
+    let expected_tag = compute_tag(w, &authentication_key, nonce, &plaintext, aad);
+    if !verify_eq(&expected_tag, tag.as_bytes()) {
+        for byte in plaintext.iter_mut() {
+            *byte = 0;
+        }
+        return Err(AesError::AuthenticationFailed(TagMismatch::new(Some(expected_tag))));
+    }
+
+    Ok(plaintext)
+}
+
+/// Derives the per-nonce message-authentication and message-encryption keys from the first 8
+/// bytes of the encryptions of LE32(i) || nonce (Sec. 4).
+fn derive_keys(key: &Key, nonce: &GcmNonce) -> ([u8; BLOCK_SIZE], Key) {
+    let w = &key.do_key_expansion().0;
+    let mut derived = [0u8; 2 * BLOCK_SIZE];
+
+    for (i, half_block) in derived.chunks_exact_mut(BLOCK_SIZE / 2).enumerate() {
+        let mut block = [0u8; BLOCK_SIZE];
+        block[..4].copy_from_slice(&(i as u32).to_le_bytes());
+        block[4..].copy_from_slice(nonce);
+
+        half_block.copy_from_slice(&encrypt_block(&block, w)[..BLOCK_SIZE / 2]);
+    }
+
+    let mut authentication_key = [0u8; BLOCK_SIZE];
+    authentication_key.copy_from_slice(&derived[..BLOCK_SIZE]);
+    let mut encryption_key = [0u8; BLOCK_SIZE];
+    encryption_key.copy_from_slice(&derived[BLOCK_SIZE..]);
+
+    (authentication_key, Key(encryption_key))
+}
+
+/// Tag over the padded additional authenticated data, the padded plaintext and their bit
+/// lengths, masked with the nonce before being encrypted.
+fn compute_tag(
+    w: &[[u8; 4]],
+    authentication_key: &[u8; BLOCK_SIZE],
+    nonce: &GcmNonce,
+    plaintext: &[u8],
+    aad: &[u8],
+) -> [u8; BLOCK_SIZE] {
+    let mut length_block = [0u8; BLOCK_SIZE];
+    length_block[..8].copy_from_slice(&(aad.len() as u64 * 8).to_le_bytes());
+    length_block[8..].copy_from_slice(&(plaintext.len() as u64 * 8).to_le_bytes());
+
+    let mut polyval = Polyval::new(authentication_key);
+    polyval.update_padded(aad);
+    polyval.update_padded(plaintext);
+    polyval.update_padded(&length_block);
+
+    let mut s = polyval.finalize();
+    for (s_byte, nonce_byte) in s.iter_mut().zip(nonce.iter()) {
+        *s_byte ^= nonce_byte;
+    }
+    s[BLOCK_SIZE - 1] &= 0x7f;
+
+    encrypt_block(&s, w)
+}
+
+/// AES-CTR starting at the tag with its most significant bit set, where the counter is the first
+/// 32 bits of the block, read as a little-endian integer, wrapping around.
+fn apply_ctr(w: &[[u8; 4]], tag: &[u8; TAG_SIZE], data: &mut [u8]) {
+    let mut counter_block = *tag;
+    counter_block[BLOCK_SIZE - 1] |= 0x80;
+
+    for part in data.chunks_mut(BLOCK_SIZE) {
+        let keystream_block = encrypt_block(&counter_block, w);
+        for (byte, keystream_byte) in part.iter_mut().zip(keystream_block.iter()) {
+            *byte ^= keystream_byte;
+        }
+
+        let mut counter = [0u8; 4];
+        counter.copy_from_slice(&counter_block[..4]);
+        let incremented = u32::from_le_bytes(counter).wrapping_add(1);
+        counter_block[..4].copy_from_slice(&incremented.to_le_bytes());
+    }
+}
+
+/// POLYVAL (Sec. 3), computed through GHASH, as the two are related by (Appendix A):
+/// POLYVAL(H, X_1, ..., X_n) =
+///   ByteReverse(GHASH(mulX_GHASH(ByteReverse(H)), ByteReverse(X_1), ..., ByteReverse(X_n)))
+/// Reading little-endian bytes as the big-endian integers used by multiply_in_gf128 does the
+/// byte reversal.
+struct Polyval {
+    h: u128,
+    s: u128,
+}
+
+impl Polyval {
+    fn new(h: &[u8; BLOCK_SIZE]) -> Self {
+        Polyval {
+            h: mul_x_ghash(u128::from_le_bytes(*h)),
+            s: 0,
+        }
+    }
+
+    /// Absorbs data, zero padding its last block.
+    fn update_padded(&mut self, data: &[u8]) {
+        for part in data.chunks(BLOCK_SIZE) {
+            let mut block = [0u8; BLOCK_SIZE];
+            block[..part.len()].copy_from_slice(part);
+
+            self.s = multiply_in_gf128(self.s ^ u128::from_le_bytes(block), self.h);
+        }
+    }
+
+    fn finalize(&self) -> [u8; BLOCK_SIZE] {
+        self.s.to_le_bytes()
+    }
+}
+
+/// Multiplication by x in GHASH's bit order, where the most significant bit is the coefficient
+/// of x^0.
+fn mul_x_ghash(value: u128) -> u128 {
+    let lsb_mask = 0u128.wrapping_sub(value & 1);
+
+    (value >> 1) ^ ((0xe1u128 << 120) & lsb_mask)
+}
+
+/// Test vectors from RFC 8452, Appendix A and C.1.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: Key = Key([
+        0x01, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00
+    ]);
+    const NONCE: GcmNonce = [
+        0x03, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00
+    ];
+
+    #[test]
+    fn computes_polyval() {
+        let h = [
+            0x25, 0x62, 0x93, 0x47, 0x58, 0x92, 0x42, 0x76,
+            0x1d, 0x31, 0xf8, 0x26, 0xba, 0x4b, 0x75, 0x7b
+        ];
+        let x = [
+            0x4f, 0x4f, 0x95, 0x66, 0x8c, 0x83, 0xdf, 0xb6,
+            0x40, 0x17, 0x62, 0xbb, 0x2d, 0x01, 0xa2, 0x62,
+            0xd1, 0xa2, 0x4d, 0xdd, 0x27, 0x21, 0xd0, 0x06,
+            0xbb, 0xe4, 0x5f, 0x20, 0xd3, 0xc9, 0xf3, 0x62
+        ];
+        let mut polyval = Polyval::new(&h);
+
+        polyval.update_padded(&x);
+
+        assert_eq!(polyval.finalize(), [
+            0xf7, 0xa3, 0xb4, 0x7b, 0x84, 0x61, 0x19, 0xfa,
+            0xe5, 0xb7, 0x86, 0x6c, 0xf5, 0xe5, 0xb7, 0x7e
+        ]);
+    }
+
+    #[test]
+    fn derives_keys() {
+        let (authentication_key, encryption_key) = derive_keys(&KEY, &NONCE);
+
+        assert_eq!(authentication_key, [
+            0xd9, 0xb3, 0x60, 0x27, 0x96, 0x94, 0x94, 0x1a,
+            0xc5, 0xdb, 0xc6, 0x98, 0x7a, 0xda, 0x73, 0x77
+        ]);
+        assert_eq!(encryption_key, Key([
+            0x40, 0x04, 0xa0, 0xdc, 0xd8, 0x62, 0xf2, 0xa5,
+            0x73, 0x60, 0x21, 0x9d, 0x2d, 0x44, 0xef, 0x6c
+        ]));
+    }
+
+    #[test]
+    fn encrypts_empty_plaintext() {
+        let (ciphertext, tag) = encrypt_gcm_siv(&[], &KEY, &NONCE, &[]);
+
+        assert_eq!(ciphertext, vec![]);
+        assert_eq!(tag, Tag::from([
+            0xdc, 0x20, 0xe2, 0xd8, 0x3f, 0x25, 0x70, 0x5b,
+            0xb4, 0x9e, 0x43, 0x9e, 0xca, 0x56, 0xde, 0x25
+        ]));
+        assert_eq!(decrypt_gcm_siv(&ciphertext, &KEY, &NONCE, &[], &tag), Ok(vec![]));
+    }
+
+    #[test]
+    fn encrypts_partial_block_plaintext() {
+        let plaintext = [0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+
+        let (ciphertext, tag) = encrypt_gcm_siv(&plaintext, &KEY, &NONCE, &[]);
+
+        assert_eq!(ciphertext, vec![0xb5, 0xd8, 0x39, 0x33, 0x0a, 0xc7, 0xb7, 0x86]);
+        assert_eq!(tag, Tag::from([
+            0x57, 0x87, 0x82, 0xff, 0xf6, 0x01, 0x3b, 0x81,
+            0x5b, 0x28, 0x7c, 0x22, 0x49, 0x3a, 0x36, 0x4c
+        ]));
+    }
+
+    #[test]
+    fn encrypts_and_decrypts_with_aad() {
+        let plaintext = [
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+        ];
+        let aad = [0x01];
+
+        let (ciphertext, tag) = encrypt_gcm_siv(&plaintext, &KEY, &NONCE, &aad);
+
+        assert_eq!(ciphertext, vec![
+            0x7b, 0x5e, 0xb6, 0x76, 0xdf, 0x9e, 0x42, 0x8f,
+            0xaf, 0x05, 0x27, 0x05, 0x0d, 0x1a, 0x91, 0xb8,
+            0xb8, 0x05, 0x1f, 0x65, 0xe2, 0xe8, 0x11, 0x20,
+            0x88, 0x09, 0xda, 0x87, 0xa8, 0x91, 0xdc, 0x0a,
+            0x9e, 0xa5, 0x3e, 0x85, 0xaf, 0x26, 0xf5, 0xd9
+        ]);
+        assert_eq!(tag, Tag::from([
+            0xa3, 0x30, 0xc3, 0x7f, 0xff, 0x2b, 0xc6, 0xb5,
+            0x38, 0xe7, 0xd3, 0x38, 0x43, 0xdd, 0x13, 0x4e
+        ]));
+        assert_eq!(decrypt_gcm_siv(&ciphertext, &KEY, &NONCE, &aad, &tag), Ok(plaintext.to_vec()));
+    }
+
+    #[test]
+    fn decrypt_rejects_tampered_ciphertext() {
+        let (mut ciphertext, tag) = encrypt_gcm_siv(&[0x42; 20], &KEY, &NONCE, &[]);
+        ciphertext[0] ^= 1;
+
+        let plaintext = decrypt_gcm_siv(&ciphertext, &KEY, &NONCE, &[], &tag);
+
+        assert!(matches!(plaintext, Err(AesError::AuthenticationFailed(_))));
+    }
+
+    #[test]
+    fn decrypt_rejects_wrong_aad() {
+        let (ciphertext, tag) = encrypt_gcm_siv(&[0x42; 20], &KEY, &NONCE, &[0x01]);
+
+        let plaintext = decrypt_gcm_siv(&ciphertext, &KEY, &NONCE, &[0x02], &tag);
+
+        assert!(matches!(plaintext, Err(AesError::AuthenticationFailed(_))));
+    }
+}
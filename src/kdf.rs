@@ -0,0 +1,95 @@
+use BLOCK_SIZE;
+use cmac::cmac;
+use error::AesError;
+use key::Key;
+
+/// Derives num_keys independent aes-128 keys from a master key with the KDF in counter mode of:
+/// https://nvlpubs.nist.gov/nistpubs/SpecialPublications/NIST.SP.800-108r1.pdf
+/// using CMAC as the PRF, e.g. to derive separate encryption and MAC keys from a single secret.
+///
+/// Key i (starting at 1) is CMAC(master, [i]_32 || info || 0x00 || [L]_32), where info is the
+/// label describing the purpose of the keys, the context is empty, and L is the total length of
+/// the derived keys in bits. Integers are big-endian.
+///
+/// The master key must be 16 bytes long, or InvalidKeyLength is returned. L must fit in 32 bits,
+/// or InputTooLong is returned.
+pub fn derive_keys(master: &[u8], info: &[u8], num_keys: usize) -> Result<Vec<Key>, AesError> {
+    if master.len() != BLOCK_SIZE {
+        return Err(AesError::InvalidKeyLength);
+    }
+    let derived_bits = num_keys
+        .checked_mul(8 * BLOCK_SIZE)
+        .filter(|bits| *bits <= u32::MAX as usize)
+        .ok_or(AesError::InputTooLong)?;
+
+    let mut master_key = Key([0u8; BLOCK_SIZE]);
+    master_key.0.copy_from_slice(master);
+    let fixed_input = [info, &[0x00], &(derived_bits as u32).to_be_bytes()].concat();
+
+    Ok((1..=num_keys as u32)
+        .map(|i| Key(cmac(&master_key, &[&i.to_be_bytes()[..], &fixed_input].concat()).to_bytes()))
+        .collect())
+}
+
+/// Test vectors generated with the KBKDFCMAC of pyca/cryptography, in counter mode with 32-bit
+/// counter and length placed before the fixed input.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER: [u8; 16] = [
+        0x00, 0x01, 0x02, 0x03,
+        0x04, 0x05, 0x06, 0x07,
+        0x08, 0x09, 0x0a, 0x0b,
+        0x0c, 0x0d, 0x0e, 0x0f
+    ];
+
+    #[test]
+    fn derives_encryption_and_mac_keys() {
+        let keys = derive_keys(&MASTER, b"encrypt-then-mac", 2);
+
+        assert_eq!(keys, Ok(vec![
+            Key([
+                0x2f, 0x64, 0x37, 0x26, 0x88, 0x46, 0x7d, 0xe1,
+                0x2c, 0x49, 0xc5, 0xff, 0x11, 0x31, 0x70, 0x2f
+            ]),
+            Key([
+                0x66, 0xda, 0xcf, 0xb1, 0x9c, 0x50, 0x57, 0x2f,
+                0xec, 0x92, 0x40, 0xba, 0xa0, 0xd4, 0xf3, 0xb1
+            ]),
+        ]));
+    }
+
+    #[test]
+    fn derives_single_key_with_empty_info() {
+        let keys = derive_keys(&MASTER, &[], 1);
+
+        assert_eq!(keys, Ok(vec![Key([
+            0xac, 0x01, 0x1b, 0x6d, 0x6a, 0x11, 0x6f, 0x5a,
+            0x60, 0x72, 0xee, 0x40, 0x70, 0x59, 0xac, 0x35
+        ])]));
+    }
+
+    #[test]
+    fn derived_keys_depend_on_their_number() {
+        // L is part of the PRF input, so asking for more keys changes the first ones
+        let keys = derive_keys(&MASTER, b"session keys", 3).unwrap();
+
+        assert_eq!(keys[2], Key([
+            0x98, 0x19, 0x07, 0xaa, 0x2d, 0x98, 0xf8, 0xef,
+            0x9d, 0x36, 0x7f, 0xdf, 0x8a, 0xfc, 0x18, 0xba
+        ]));
+        assert_ne!(keys[0], derive_keys(&MASTER, b"session keys", 1).unwrap()[0]);
+    }
+
+    #[test]
+    fn rejects_master_key_of_wrong_length() {
+        assert_eq!(derive_keys(&MASTER[..15], b"info", 1), Err(AesError::InvalidKeyLength));
+        assert_eq!(derive_keys(&[0u8; 32], b"info", 1), Err(AesError::InvalidKeyLength));
+    }
+
+    #[test]
+    fn rejects_too_many_keys() {
+        assert_eq!(derive_keys(&MASTER, b"info", 1 << 25), Err(AesError::InputTooLong));
+    }
+}
@@ -1,13 +1,86 @@
-use ::{Nb, Nk, Nr, xor};
-use word::{rot_word, sub_word};
+use std::env;
+use std::ptr;
+
+use ::{math, Nb, Nk, Nr, sbox, xor};
+use constant_time::verify_eq;
+use error::AesError;
+use state::State;
+use word::{inv_mix_column, rot_word, sub_word};
 use Rcon;
 
-#[derive(PartialEq, Debug)]
-pub struct Key(pub [u8; 16]);
+/// Cipher key. It is Copy for ergonomic passing, since the crate doesn't wipe keys from memory on
+/// drop (Copy and Drop being mutually exclusive); copies are as long-lived as the caller keeps
+/// them.
+#[derive(Debug, Clone, Copy)]
+pub struct Key(pub [u8; 4 * Nk]);
+
+/// Keys are compared in constant time with regard to their bytes, so that the time taken doesn't
+/// reveal how many leading bytes match.
+impl PartialEq for Key {
+    fn eq(&self, other: &Key) -> bool {
+        verify_eq(&self.0, &other.0)
+    }
+}
 
 /// Key schedule generated by the key expansion routine.
+#[derive(PartialEq, Debug)]
 pub struct KeySchedule(pub [[u8; 4]; Nb * (Nr + 1)]);
 
+impl KeySchedule {
+    /// Number of words in the schedule, Nb * (Nr + 1).
+    pub fn word_count(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Flattens the words of the schedule to bytes, e.g. to cache it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.concat()
+    }
+
+    /// Rebuilds a schedule from the bytes produced by to_bytes, which must be exactly
+    /// 4 * Nb * (Nr + 1) bytes long.
+    pub fn from_bytes(bytes: &[u8]) -> Result<KeySchedule, AesError> {
+        if bytes.len() != 4 * Nb * (Nr + 1) {
+            return Err(AesError::InvalidKeyScheduleLength);
+        }
+
+        let mut w = [[0u8; 4]; Nb * (Nr + 1)];
+        for (word, bytes_part) in w.iter_mut().zip(bytes.chunks_exact(4)) {
+            word.copy_from_slice(bytes_part);
+        }
+
+        Ok(KeySchedule(w))
+    }
+
+    /// Whether this is the schedule of key, e.g. to check a cached schedule rebuilt with
+    /// from_bytes before using it. The whole schedule is expanded again from the key and compared
+    /// in constant time: comparing only the first Nk words, which are the key itself, would miss a
+    /// corruption of the other round keys.
+    pub fn matches_key(&self, key: &Key) -> bool {
+        verify_eq(&self.to_bytes(), &key.do_key_expansion().to_bytes())
+    }
+
+    /// Decryption key schedule of the equivalent inverse cipher (Sec. 5.3.5), where InvMixColumns
+    /// is applied to the round keys of rounds 1 to Nr - 1. It is computed once, so that repeated
+    /// decryptions under the same key don't transform the schedule again.
+    pub fn for_decryption(&self) -> KeySchedule {
+        let mut dw = self.0;
+
+        for round_key in dw[Nb..Nr * Nb].chunks_exact_mut(Nb) {
+            let mut state = State::from_part(&round_key.concat());
+            state.inv_mix_columns();
+
+            let mut bytes = [0u8; 4 * Nb];
+            state.write_block(&mut bytes);
+            for (word, bytes_part) in round_key.iter_mut().zip(bytes.chunks_exact(4)) {
+                word.copy_from_slice(bytes_part);
+            }
+        }
+
+        KeySchedule(dw)
+    }
+}
+
 impl Key {
     pub fn from_string(string: &str) -> Self {
         let mut out = [0u8; 16];
@@ -19,36 +92,178 @@ impl Key {
         Key(out)
     }
 
+    /// Decodes a key from 32 hex digits, upper or lower case.
+    pub fn from_hex(hex: &str) -> Result<Self, AesError> {
+        let hex = hex.as_bytes();
+        if hex.len() != 2 * 16 {
+            return Err(AesError::InvalidKeyLength);
+        }
+
+        let mut out = [0u8; 16];
+        for (byte, digits) in out.iter_mut().zip(hex.chunks_exact(2)) {
+            *byte = hex_digit_value(digits[0])? << 4 | hex_digit_value(digits[1])?;
+        }
+
+        Ok(Key(out))
+    }
+
+    /// Reads a hex encoded key from the environment variable var_name, ignoring surrounding
+    /// whitespace. The variable's value is wiped from the copy read by this function, but not from
+    /// the environment itself.
+    ///
+    /// Environment variables aren't a high-security key source: they're inherited by child
+    /// processes, can be read by other processes of the same user (e.g. /proc/<pid>/environ) and
+    /// often end up in shell histories or logs.
+    pub fn from_env(var_name: &str) -> Result<Self, AesError> {
+        let value = match env::var(var_name) {
+            Ok(value) => value,
+            Err(env::VarError::NotPresent) => {
+                return Err(AesError::MissingEnvVar(var_name.to_string()));
+            }
+            Err(env::VarError::NotUnicode(_)) => return Err(AesError::InvalidHex),
+        };
+        let mut value = value.into_bytes();
+
+        let key = match ::std::str::from_utf8(&value) {
+            Ok(hex) => Key::from_hex(hex.trim()),
+            Err(_) => Err(AesError::InvalidHex),
+        };
+        wipe(&mut value);
+
+        key
+    }
+
     /// Generates a series of Round Keys from the Cipher Key.
     /// The Key Expansion generates a total of Nb * (Nr + 1) words: the algorithm requires
     /// an initial set of Nb words, and each of the Nr rounds requires Nb words of key data. The
     /// resulting key schedule consists of a linear array of 4-byte words, denoted [w_i ], with
     /// i in the range 0 <= i < Nb * (Nr + 1).
+    ///
+    /// Unlike expand_key, this is specialized for aes-128 and done on the stack, without any heap
+    /// allocation, as it's done for every key. The loop bounds are those of the schedule array, so
+    /// the two can't mismatch.
     pub fn do_key_expansion(&self) -> KeySchedule {
-        let mut w = [[0u8; Nk]; Nb * (Nr + 1)];
+        let s_box = sbox::s_box();
+        let mut w = [[0u8; 4]; Nb * (Nr + 1)];
 
-        for i in 0..Nk {
-            let key_part = &self.0[4 * i..4 * i + 4];
-            w[i] = [key_part[0], key_part[1], key_part[2], key_part[3]];
+        for (word, key_part) in w.iter_mut().zip(self.0.chunks_exact(4)) {
+            word.copy_from_slice(key_part);
         }
 
         for i in Nk..(Nb * (Nr + 1)) {
-            let mut temp = w[i - 1].to_vec();
-            if i % Nk == 0 {
-                let xored = xor::fixed_key_xor(
-                    &sub_word(&rot_word(&temp)),
-                    &Rcon[(i / Nk) - 1],
-                );
-                temp = xored;
-            } else if Nk > 6 && i % Nk == 4 {
-                temp = sub_word(&temp);
-            }
-            let key = xor::fixed_key_xor(&w[i - Nk][..], &temp);
-            w[i] = [key[0], key[1], key[2], key[3]];
+            w[i] = next_word(i, &w[i - 1], &w[i - Nk], s_box);
         }
 
         KeySchedule(w)
     }
+
+    /// Key Expansion producing the decryption key schedule of the equivalent inverse cipher
+    /// (Sec. 5.3.5) directly, the same as do_key_expansion().for_decryption(): InvMixColumns is
+    /// applied to each word of rounds 1 to Nr - 1 as it is generated, in a single pass.
+    pub fn do_inv_key_expansion(&self) -> KeySchedule {
+        let s_box = sbox::s_box();
+        // w is the forward schedule, which the next words are derived from
+        let mut w = [[0u8; 4]; Nb * (Nr + 1)];
+        let mut dw = [[0u8; 4]; Nb * (Nr + 1)];
+
+        for (word, key_part) in w.iter_mut().zip(self.0.chunks_exact(4)) {
+            word.copy_from_slice(key_part);
+        }
+        dw[..Nk].copy_from_slice(&w[..Nk]);
+
+        for i in Nk..(Nb * (Nr + 1)) {
+            w[i] = next_word(i, &w[i - 1], &w[i - Nk], s_box);
+            dw[i] = if i < Nr * Nb { inv_mix_column(&w[i]) } else { w[i] };
+        }
+
+        KeySchedule(dw)
+    }
+}
+
+/// Word i of the aes-128 key schedule, from the previous word and the word Nk positions before.
+#[inline]
+fn next_word(i: usize, previous: &[u8; 4], nk_before: &[u8; 4], s_box: &[u8; 256]) -> [u8; 4] {
+    let mut temp = *previous;
+    if i.is_multiple_of(Nk) {
+        // SubWord(RotWord(temp)) xor Rcon[i / Nk - 1]
+        temp = [
+            s_box[temp[1] as usize] ^ Rcon[i / Nk - 1][0],
+            s_box[temp[2] as usize],
+            s_box[temp[3] as usize],
+            s_box[temp[0] as usize],
+        ];
+    }
+    for (byte, nk_before_byte) in temp.iter_mut().zip(nk_before.iter()) {
+        *byte ^= nk_before_byte;
+    }
+
+    temp
+}
+
+/// Key Expansion (Sec. 5.2) for 128, 192 and 256-bit keys, generating Nb * (Nr + 1) words where
+/// Nk = 4, 6 or 8 is the number of words of the key and Nr = Nk + 6. That's 44, 52 and 60 words
+/// respectively, which is what the cost of the expansion grows with.
+///
+/// The first Nk words are the key, and words Nk to Nb * (Nr + 1) - 1 are derived from the word
+/// Nk positions before and from the previous word. The length of the schedule is asserted to be
+/// Nb * (Nr + 1) in debug builds.
+pub fn expand_key(key: &[u8]) -> Result<Vec<[u8; 4]>, AesError> {
+    let nk = key.len() / 4;
+    if key.len() != 16 && key.len() != 24 && key.len() != 32 {
+        return Err(AesError::InvalidKeyLength);
+    }
+    let nr = nk + 6;
+    let word_count = Nb * (nr + 1);
+    let mut w = vec![[0u8; 4]; word_count];
+
+    for (word, key_part) in w.iter_mut().zip(key.chunks_exact(4)) {
+        word.copy_from_slice(key_part);
+    }
+
+    // words are [u8; 4] on the stack, w being the only allocation
+    for i in nk..word_count {
+        let mut temp = w[i - 1];
+        if i % nk == 0 {
+            temp = sub_word(&rot_word(&temp));
+            xor::xor_in_place(&mut temp, &Rcon[(i / nk) - 1]);
+        } else if nk > 6 && i % nk == 4 {
+            temp = sub_word(&temp);
+        }
+        xor::xor_in_place(&mut temp, &w[i - nk]);
+        w[i] = temp;
+    }
+    debug_assert_eq!(w.len(), Nb * (nr + 1), "Key schedule must have Nb * (Nr + 1) words.");
+
+    Ok(w)
+}
+
+/// Round constant word Rcon[n] = [x^(n-1), {00}, {00}, {00}] (Sec. 5.2), with x^(n-1) computed by
+/// doubling {01} in GF(2^8). n starts at 1.
+pub fn compute_rcon(n: usize) -> [u8; 4] {
+    assert!(n >= 1, "Round constants start at 1.");
+
+    let mut power = 0x01;
+    for _ in 1..n {
+        power = math::xtime(power);
+    }
+
+    [power, 0x00, 0x00, 0x00]
+}
+
+fn hex_digit_value(digit: u8) -> Result<u8, AesError> {
+    match digit {
+        b'0'..=b'9' => Ok(digit - b'0'),
+        b'a'..=b'f' => Ok(digit - b'a' + 10),
+        b'A'..=b'F' => Ok(digit - b'A' + 10),
+        _ => Err(AesError::InvalidHex),
+    }
+}
+
+/// Overwrites bytes with zeros using volatile writes, so that the wipe isn't optimized away.
+pub(crate) fn wipe(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut() {
+        unsafe { ptr::write_volatile(byte, 0) };
+    }
 }
 
 #[cfg(test)]
@@ -68,15 +283,25 @@ mod tests {
         assert_eq!(key.0, expected_key_value);
     }
 
+    #[test]
+    fn key_can_be_copied() {
+        let key = Key::from_string("SOME KEY ABCDEFG");
+        let copied_key = key;
+
+        assert_eq!(key, copied_key);
+    }
+
+    // as provided in official paper
+    const FIPS_197_KEY: Key = Key([
+        0x2b, 0x7e, 0x15, 0x16,
+        0x28, 0xae, 0xd2, 0xa6,
+        0xab, 0xf7, 0x15, 0x88,
+        0x09, 0xcf, 0x4f, 0x3c
+    ]);
+
     #[test]
     fn do_key_expansion_produces_corresponding_key_schedule() {
-        // as provided in official paper
-        let key = &Key([
-            0x2b, 0x7e, 0x15, 0x16,
-            0x28, 0xae, 0xd2, 0xa6,
-            0xab, 0xf7, 0x15, 0x88,
-            0x09, 0xcf, 0x4f, 0x3c
-        ]);
+        let key = &FIPS_197_KEY;
         // also known as w
         let expected_key_schedule: [[u8; 4]; 44] = [
             // copy of key
@@ -132,4 +357,268 @@ mod tests {
 
         assert_eq!(actual_key_schedule.0.to_vec(), expected_key_schedule.to_vec());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn key_schedule_round_trips_through_bytes() {
+        let key_schedule = FIPS_197_KEY.do_key_expansion();
+
+        let bytes = key_schedule.to_bytes();
+
+        assert_eq!(bytes.len(), 176);
+        assert_eq!(bytes[..16], FIPS_197_KEY.0);
+        assert_eq!(bytes[172..], [0xb6, 0x63, 0x0c, 0xa6]);
+        assert_eq!(KeySchedule::from_bytes(&bytes), Ok(key_schedule));
+    }
+
+    #[test]
+    fn from_bytes_rejects_wrong_length() {
+        let bytes = FIPS_197_KEY.do_key_expansion().to_bytes();
+
+        assert_eq!(KeySchedule::from_bytes(&bytes[1..]), Err(AesError::InvalidKeyScheduleLength));
+        assert_eq!(
+            KeySchedule::from_bytes(&[&bytes[..], &[0x00]].concat()),
+            Err(AesError::InvalidKeyScheduleLength)
+        );
+        assert_eq!(KeySchedule::from_bytes(&[]), Err(AesError::InvalidKeyScheduleLength));
+    }
+
+    #[test]
+    fn from_hex_decodes_key() {
+        let key = Key::from_hex("000102030405060708090a0B0c0D0e0F");
+
+        assert_eq!(key, Ok(Key([
+            0x00, 0x01, 0x02, 0x03,
+            0x04, 0x05, 0x06, 0x07,
+            0x08, 0x09, 0x0a, 0x0b,
+            0x0c, 0x0d, 0x0e, 0x0f
+        ])));
+    }
+
+    #[test]
+    fn from_hex_rejects_invalid_length() {
+        assert_eq!(Key::from_hex("000102"), Err(AesError::InvalidKeyLength));
+        assert_eq!(Key::from_hex(&"00".repeat(17)), Err(AesError::InvalidKeyLength));
+    }
+
+    #[test]
+    fn from_hex_rejects_invalid_digits() {
+        assert_eq!(Key::from_hex("000102030405060708090a0b0c0d0e0g"), Err(AesError::InvalidHex));
+        assert_eq!(Key::from_hex("+00102030405060708090a0b0c0d0e0f"), Err(AesError::InvalidHex));
+    }
+
+    #[test]
+    fn from_env_reads_hex_key() {
+        env::set_var("AES_NDLR_TEST_KEY_VALID", " 2b7e151628aed2a6abf7158809cf4f3c\n");
+
+        let key = Key::from_env("AES_NDLR_TEST_KEY_VALID");
+
+        assert_eq!(key, Ok(FIPS_197_KEY));
+    }
+
+    #[test]
+    fn from_env_errors_on_missing_variable() {
+        let key = Key::from_env("AES_NDLR_TEST_KEY_MISSING");
+
+        assert_eq!(key, Err(AesError::MissingEnvVar("AES_NDLR_TEST_KEY_MISSING".to_string())));
+    }
+
+    #[test]
+    fn from_env_errors_on_bad_hex() {
+        // of the right length, so that it's the hex decoding that fails
+        env::set_var("AES_NDLR_TEST_KEY_BAD_HEX", "zz".repeat(16));
+
+        let key = Key::from_env("AES_NDLR_TEST_KEY_BAD_HEX");
+
+        assert_eq!(key, Err(AesError::InvalidHex));
+    }
+
+    #[test]
+    fn wipe_zeroes_bytes() {
+        let mut bytes = vec![0x42; 32];
+
+        wipe(&mut bytes);
+
+        assert_eq!(bytes, vec![0; 32]);
+    }
+
+    #[test]
+    fn computed_rcon_matches_embedded_rcon() {
+        for (i, rcon) in Rcon.iter().enumerate() {
+            assert_eq!(compute_rcon(i + 1), *rcon);
+        }
+    }
+
+    #[test]
+    fn computes_rcon_past_embedded_rcon() {
+        assert_eq!(compute_rcon(11), [0x6c, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn key_schedule_has_44_words() {
+        assert_eq!(FIPS_197_KEY.do_key_expansion().word_count(), 44);
+    }
+
+    #[test]
+    fn expand_key_expands_128_bit_key() {
+        let w = expand_key(&FIPS_197_KEY.0).unwrap();
+
+        assert_eq!(w[..], FIPS_197_KEY.do_key_expansion().0[..]);
+    }
+
+    #[test]
+    fn expand_key_expands_192_bit_key() {
+        // Appendix A.2
+        let key = [
+            0x8e, 0x73, 0xb0, 0xf7, 0xda, 0x0e, 0x64, 0x52,
+            0xc8, 0x10, 0xf3, 0x2b, 0x80, 0x90, 0x79, 0xe5,
+            0x62, 0xf8, 0xea, 0xd2, 0x52, 0x2c, 0x6b, 0x7b
+        ];
+        let expected: [[u8; 4]; 52] = [
+            [0x8e, 0x73, 0xb0, 0xf7], [0xda, 0x0e, 0x64, 0x52],
+            [0xc8, 0x10, 0xf3, 0x2b], [0x80, 0x90, 0x79, 0xe5],
+            [0x62, 0xf8, 0xea, 0xd2], [0x52, 0x2c, 0x6b, 0x7b],
+            [0xfe, 0x0c, 0x91, 0xf7], [0x24, 0x02, 0xf5, 0xa5],
+            [0xec, 0x12, 0x06, 0x8e], [0x6c, 0x82, 0x7f, 0x6b],
+            [0x0e, 0x7a, 0x95, 0xb9], [0x5c, 0x56, 0xfe, 0xc2],
+            [0x4d, 0xb7, 0xb4, 0xbd], [0x69, 0xb5, 0x41, 0x18],
+            [0x85, 0xa7, 0x47, 0x96], [0xe9, 0x25, 0x38, 0xfd],
+            [0xe7, 0x5f, 0xad, 0x44], [0xbb, 0x09, 0x53, 0x86],
+            [0x48, 0x5a, 0xf0, 0x57], [0x21, 0xef, 0xb1, 0x4f],
+            [0xa4, 0x48, 0xf6, 0xd9], [0x4d, 0x6d, 0xce, 0x24],
+            [0xaa, 0x32, 0x63, 0x60], [0x11, 0x3b, 0x30, 0xe6],
+            [0xa2, 0x5e, 0x7e, 0xd5], [0x83, 0xb1, 0xcf, 0x9a],
+            [0x27, 0xf9, 0x39, 0x43], [0x6a, 0x94, 0xf7, 0x67],
+            [0xc0, 0xa6, 0x94, 0x07], [0xd1, 0x9d, 0xa4, 0xe1],
+            [0xec, 0x17, 0x86, 0xeb], [0x6f, 0xa6, 0x49, 0x71],
+            [0x48, 0x5f, 0x70, 0x32], [0x22, 0xcb, 0x87, 0x55],
+            [0xe2, 0x6d, 0x13, 0x52], [0x33, 0xf0, 0xb7, 0xb3],
+            [0x40, 0xbe, 0xeb, 0x28], [0x2f, 0x18, 0xa2, 0x59],
+            [0x67, 0x47, 0xd2, 0x6b], [0x45, 0x8c, 0x55, 0x3e],
+            [0xa7, 0xe1, 0x46, 0x6c], [0x94, 0x11, 0xf1, 0xdf],
+            [0x82, 0x1f, 0x75, 0x0a], [0xad, 0x07, 0xd7, 0x53],
+            [0xca, 0x40, 0x05, 0x38], [0x8f, 0xcc, 0x50, 0x06],
+            [0x28, 0x2d, 0x16, 0x6a], [0xbc, 0x3c, 0xe7, 0xb5],
+            [0xe9, 0x8b, 0xa0, 0x6f], [0x44, 0x8c, 0x77, 0x3c],
+            [0x8e, 0xcc, 0x72, 0x04], [0x01, 0x00, 0x22, 0x02]
+        ];
+
+        let w = expand_key(&key).unwrap();
+
+        assert_eq!(w[..], expected[..]);
+    }
+
+    #[test]
+    fn expand_key_expands_256_bit_key_with_extra_sub_word() {
+        // Appendix A.3
+        let key = [
+            0x60, 0x3d, 0xeb, 0x10, 0x15, 0xca, 0x71, 0xbe,
+            0x2b, 0x73, 0xae, 0xf0, 0x85, 0x7d, 0x77, 0x81,
+            0x1f, 0x35, 0x2c, 0x07, 0x3b, 0x61, 0x08, 0xd7,
+            0x2d, 0x98, 0x10, 0xa3, 0x09, 0x14, 0xdf, 0xf4
+        ];
+        let expected: [[u8; 4]; 60] = [
+            [0x60, 0x3d, 0xeb, 0x10], [0x15, 0xca, 0x71, 0xbe],
+            [0x2b, 0x73, 0xae, 0xf0], [0x85, 0x7d, 0x77, 0x81],
+            [0x1f, 0x35, 0x2c, 0x07], [0x3b, 0x61, 0x08, 0xd7],
+            [0x2d, 0x98, 0x10, 0xa3], [0x09, 0x14, 0xdf, 0xf4],
+            [0x9b, 0xa3, 0x54, 0x11], [0x8e, 0x69, 0x25, 0xaf],
+            [0xa5, 0x1a, 0x8b, 0x5f], [0x20, 0x67, 0xfc, 0xde],
+            [0xa8, 0xb0, 0x9c, 0x1a], [0x93, 0xd1, 0x94, 0xcd],
+            [0xbe, 0x49, 0x84, 0x6e], [0xb7, 0x5d, 0x5b, 0x9a],
+            [0xd5, 0x9a, 0xec, 0xb8], [0x5b, 0xf3, 0xc9, 0x17],
+            [0xfe, 0xe9, 0x42, 0x48], [0xde, 0x8e, 0xbe, 0x96],
+            [0xb5, 0xa9, 0x32, 0x8a], [0x26, 0x78, 0xa6, 0x47],
+            [0x98, 0x31, 0x22, 0x29], [0x2f, 0x6c, 0x79, 0xb3],
+            [0x81, 0x2c, 0x81, 0xad], [0xda, 0xdf, 0x48, 0xba],
+            [0x24, 0x36, 0x0a, 0xf2], [0xfa, 0xb8, 0xb4, 0x64],
+            [0x98, 0xc5, 0xbf, 0xc9], [0xbe, 0xbd, 0x19, 0x8e],
+            [0x26, 0x8c, 0x3b, 0xa7], [0x09, 0xe0, 0x42, 0x14],
+            [0x68, 0x00, 0x7b, 0xac], [0xb2, 0xdf, 0x33, 0x16],
+            [0x96, 0xe9, 0x39, 0xe4], [0x6c, 0x51, 0x8d, 0x80],
+            [0xc8, 0x14, 0xe2, 0x04], [0x76, 0xa9, 0xfb, 0x8a],
+            [0x50, 0x25, 0xc0, 0x2d], [0x59, 0xc5, 0x82, 0x39],
+            [0xde, 0x13, 0x69, 0x67], [0x6c, 0xcc, 0x5a, 0x71],
+            [0xfa, 0x25, 0x63, 0x95], [0x96, 0x74, 0xee, 0x15],
+            [0x58, 0x86, 0xca, 0x5d], [0x2e, 0x2f, 0x31, 0xd7],
+            [0x7e, 0x0a, 0xf1, 0xfa], [0x27, 0xcf, 0x73, 0xc3],
+            [0x74, 0x9c, 0x47, 0xab], [0x18, 0x50, 0x1d, 0xda],
+            [0xe2, 0x75, 0x7e, 0x4f], [0x74, 0x01, 0x90, 0x5a],
+            [0xca, 0xfa, 0xaa, 0xe3], [0xe4, 0xd5, 0x9b, 0x34],
+            [0x9a, 0xdf, 0x6a, 0xce], [0xbd, 0x10, 0x19, 0x0d],
+            [0xfe, 0x48, 0x90, 0xd1], [0xe6, 0x18, 0x8d, 0x0b],
+            [0x04, 0x6d, 0xf3, 0x44], [0x70, 0x6c, 0x63, 0x1e]
+        ];
+
+        let w = expand_key(&key).unwrap();
+
+        assert_eq!(w[..], expected[..]);
+    }
+
+    #[test]
+    fn schedule_matches_its_key() {
+        let cached = KeySchedule::from_bytes(&FIPS_197_KEY.do_key_expansion().to_bytes()).unwrap();
+
+        assert!(cached.matches_key(&FIPS_197_KEY));
+        assert!(!cached.matches_key(&Key([0x00; 16])));
+    }
+
+    #[test]
+    fn corrupted_schedule_does_not_match_its_key() {
+        for corrupted_word in [0, Nk, Nb * (Nr + 1) - 1].iter() {
+            let mut schedule = FIPS_197_KEY.do_key_expansion();
+            schedule.0[*corrupted_word][2] ^= 0x01;
+
+            assert!(!schedule.matches_key(&FIPS_197_KEY), "word {}", corrupted_word);
+        }
+    }
+
+    #[test]
+    fn expand_key_produces_nb_times_nr_plus_one_words_for_every_key_size() {
+        assert_eq!(expand_key(&[0x42; 16]).unwrap().len(), 44);
+        assert_eq!(expand_key(&[0x42; 24]).unwrap().len(), 52);
+        assert_eq!(expand_key(&[0x42; 32]).unwrap().len(), 60);
+    }
+
+    #[test]
+    fn expand_key_rejects_unsupported_key_lengths() {
+        assert_eq!(expand_key(&[0; 15]), Err(AesError::InvalidKeyLength));
+        assert_eq!(expand_key(&[0; 20]), Err(AesError::InvalidKeyLength));
+        assert_eq!(expand_key(&[]), Err(AesError::InvalidKeyLength));
+    }
+
+    #[test]
+    fn keys_are_equal_only_when_every_byte_is() {
+        let key = Key::from_string("YELLOW SUBMARINE");
+        let mut last_byte_differs = key;
+        last_byte_differs.0[15] ^= 0x01;
+
+        assert_eq!(key, Key::from_string("YELLOW SUBMARINE"));
+        assert_ne!(key, last_byte_differs);
+        assert_ne!(key, Key::from_string("yELLOW SUBMARINE"));
+    }
+
+    #[test]
+    fn for_decryption_transforms_interior_round_keys_only() {
+        let key_schedule = FIPS_197_KEY.do_key_expansion();
+
+        let dw = key_schedule.for_decryption().0;
+
+        assert_eq!(dw[..Nb], key_schedule.0[..Nb]);
+        assert_eq!(dw[Nr * Nb..], key_schedule.0[Nr * Nb..]);
+        for round in 1..Nr {
+            let mut state = State::from_part(&dw[round * Nb..(round + 1) * Nb].concat());
+            state.mix_columns();
+            let mut round_key = [0u8; 4 * Nb];
+            state.write_block(&mut round_key);
+
+            assert_eq!(round_key.to_vec(), key_schedule.0[round * Nb..(round + 1) * Nb].concat());
+        }
+    }
+
+    #[test]
+    fn inverse_key_expansion_is_forward_expansion_for_decryption() {
+        for key in [FIPS_197_KEY, Key([0x00; 16]), Key([0xff; 16])].iter() {
+            assert_eq!(key.do_inv_key_expansion(), key.do_key_expansion().for_decryption());
+        }
+    }
+}
@@ -1,25 +1,64 @@
 /// Resources used:
 /// - FIPS 197, Advanced Encryption Standard (AES):
-///     https://csrc.nist.gov/csrc/media/publications/fips/197/final/documents/fips-197.pdf
-///     Comments in the code reference this paper's sections.
+///   https://csrc.nist.gov/csrc/media/publications/fips/197/final/documents/fips-197.pdf
+///   Comments in the code reference this paper's sections.
 /// - Rijndael MixColumns - Implementation example:
-///     https://en.wikipedia.org/wiki/Rijndael_MixColumns#Implementation_example
+///   https://en.wikipedia.org/wiki/Rijndael_MixColumns#Implementation_example
 /// - Block cipher mode of operation:
-///     https://en.wikipedia.org/wiki/Block_cipher_mode_of_operation
+///   https://en.wikipedia.org/wiki/Block_cipher_mode_of_operation
+#[cfg(feature = "rand")]
+extern crate rand;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+
 use constants::*;
-use key::Key;
-use pad::{Padding, pkcs7_pad};
-use Padding::PKCS7;
-use state::State;
+use error::{AesError, TagMismatch};
+use bitslice::BitslicedAes128;
+use cipher::{Aes128, BlockCipher, ByteOrder, CipherBackend, ScatterGatherAes128};
+use key::{Key, KeySchedule, wipe};
+use pad::{Padding, PaddingScheme};
 
 pub mod pad;
 pub mod key;
+pub mod error;
+pub mod cipher;
+pub mod nonce_guard;
+pub mod gcm;
+pub mod gcm_siv;
+pub mod analysis;
+pub mod cmac;
+pub mod openssl_compat;
+pub mod records;
+pub mod cts;
+pub mod ff1;
+pub mod bitslice;
+pub mod auto;
+pub mod text;
+pub mod compression;
+pub mod kdf;
+pub mod cbc_hmac;
+pub mod xex;
+pub mod secure_cbc;
+pub mod cbc_writer;
+pub mod envelope;
+pub mod tag;
 mod state;
 mod xor;
 mod math;
 mod word;
 mod constants;
+mod sbox;
 mod ctr;
+mod constant_time;
+mod md5;
+mod base64;
+mod sha256;
+
+pub use constants::BLOCK_SIZE;
+pub use state::State;
+pub use ctr::{CtrCipher, CtrState, inc32};
+pub use constant_time::verified_decrypt;
+pub use text::{Ciphertext, Plaintext};
 
 #[derive(PartialEq, Debug)]
 pub struct AESEncryptionOptions<'a> {
@@ -34,6 +73,21 @@ impl<'a> AESEncryptionOptions<'a> {
             padding,
         }
     }
+
+    /// Checks that raw_bytes can be encrypted without padding: with Padding::Prepadded, and with
+    /// Padding::None outside of CTR, it must be a multiple of BLOCK_SIZE.
+    fn check_input_length(&self, raw_bytes: &[u8]) -> Result<(), AesError> {
+        let unpadded = match self.padding {
+            Padding::Prepadded => true,
+            Padding::None => self.block_cipher_mode.counter_start().is_none(),
+            _ => false,
+        };
+        if unpadded && !raw_bytes.len().is_multiple_of(BLOCK_SIZE) {
+            return Err(AesError::InvalidInputLength);
+        }
+
+        Ok(())
+    }
 }
 
 impl Default for AESEncryptionOptions<'_> {
@@ -43,20 +97,115 @@ impl Default for AESEncryptionOptions<'_> {
 }
 
 /// a 4 x Nb matrix
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub struct Block(pub [[u8; 4]; Nb]);
 
 impl Block {
     pub fn empty() -> Self {
+        Block::zero()
+    }
+
+    /// The all-zero block, e.g. the zero Iv that CBC-MAC based constructions start from. CBC with
+    /// a zero Iv encrypts the first block as is, as ECB does.
+    pub const fn zero() -> Self {
         Block([[0; 4]; Nb])
     }
+
+    /// Builds a block from flat bytes, in the same byte order as the cipher's input (Sec. 3.4),
+    /// so that e.g. an Iv given as 16 bytes doesn't have to be nested by hand.
+    pub fn from_bytes(bytes: &[u8; BLOCK_SIZE]) -> Self {
+        let mut block = Block::empty();
+        for (word, bytes_part) in block.0.iter_mut().zip(bytes.chunks_exact(4)) {
+            word.copy_from_slice(bytes_part);
+        }
+
+        block
+    }
+
+    /// Flattens the block to bytes, reversing from_bytes.
+    pub fn to_bytes(&self) -> [u8; BLOCK_SIZE] {
+        let mut bytes = [0u8; BLOCK_SIZE];
+        for (bytes_part, word) in bytes.chunks_exact_mut(4).zip(self.0.iter()) {
+            bytes_part.copy_from_slice(word);
+        }
+
+        bytes
+    }
+
+    /// Adds one to the block, read as a big-endian 128-bit counter. All 0xff wraps around to zero.
+    pub fn increment(&mut self) {
+        self.increment_by(1);
+    }
+
+    /// Adds n to the block, read as a big-endian 128-bit counter, wrapping around on overflow.
+    pub fn increment_by(&mut self, n: u64) {
+        let counter = u128::from_be_bytes(self.to_bytes()).wrapping_add(n as u128);
+
+        *self = Block::from_bytes(&counter.to_be_bytes());
+    }
+
+    /// Generates a block of random bytes from the operating system's RNG, e.g. for use as an Iv.
+    #[cfg(feature = "rand")]
+    pub fn random() -> Self {
+        Block::random_from(&mut rand::rngs::OsRng)
+    }
+
+    /// Generates a block of random bytes from rng, e.g. a seeded RNG to generate deterministic
+    /// IVs in tests.
+    #[cfg(feature = "rand")]
+    pub fn random_from(rng: &mut impl rand::RngCore) -> Self {
+        let mut bytes = [0u8; BLOCK_SIZE];
+        rng.fill_bytes(&mut bytes);
+
+        Block::from_bytes(&bytes)
+    }
 }
 
 #[derive(PartialEq, Debug)]
 pub enum BlockCipherMode<'a> {
     ECB,
     CBC(&'a Iv),
+    /// Counter mode using Nonce + {C} counter blocks, where C is an 8 byte big-endian counter
+    /// starting at 1. This is compatible with OpenSSL's aes-128-ctr given the IV
+    /// Nonce + 0x0000000000000001.
+    ///
+    /// SP 800-38A leaves the choice of the initial counter block to the application: starting at
+    /// 1 is a convention of this crate, not an off-by-one. To match a stream starting at another
+    /// counter block, such as the SP 800-38A vectors or a stream starting at 0, use
+    /// CTRWithCounter.
     CTR(&'a Nonce),
+    /// Counter mode like CTR, but where the first block uses the given counter value, e.g. to
+    /// match the stream of another system. CTRWithCounter(nonce, 1) is CTR(nonce).
+    CTRWithCounter(&'a Nonce, u64),
+}
+
+impl BlockCipherMode<'_> {
+    /// Short name of the block cipher mode, e.g. "CBC".
+    pub fn name(&self) -> &'static str {
+        match self {
+            BlockCipherMode::ECB => "ECB",
+            BlockCipherMode::CBC(_) => "CBC",
+            BlockCipherMode::CTR(_) | BlockCipherMode::CTRWithCounter(_, _) => "CTR",
+        }
+    }
+
+    /// Nonce and counter of the first counter block, for the counter modes.
+    fn counter_start(&self) -> Option<(&Nonce, u64)> {
+        match self {
+            BlockCipherMode::CTR(nonce) => Some((nonce, ctr::DEFAULT_INITIAL_COUNTER)),
+            BlockCipherMode::CTRWithCounter(nonce, initial_counter) => Some((nonce, *initial_counter)),
+            _ => None,
+        }
+    }
+}
+
+/// Ciphertext along with a description of how it was produced, for logging and auditing.
+#[derive(PartialEq, Debug)]
+pub struct EncryptionResult {
+    pub ciphertext: Vec<u8>,
+    pub mode: String,
+    pub iv_used: Option<Iv>,
+    pub padding_added: usize,
 }
 
 pub type Iv = Block;
@@ -68,108 +217,815 @@ pub type Nonce = [u8; 8];
 /// described in Sec. 3.4. After an initial Round Key addition, the State array is transformed by
 /// implementing a round function Nr times, with the final round differing slightly from the first
 /// Nr -1 rounds. The final State is then copied to the output as described in Sec. 3.4.
+///
+/// Panics if the input is rejected by try_encrypt_aes_128.
 pub fn encrypt_aes_128(raw_bytes: &[u8], key: &Key, options: &AESEncryptionOptions) -> Vec<u8> {
-    let block_size = 16;
+    match try_encrypt_aes_128(raw_bytes, key, options) {
+        Ok(cipher) => cipher,
+        Err(error) => panic!("{}", error),
+    }
+}
 
-    let w = &key.do_key_expansion().0;
-    let bytes = &if options.padding == &PKCS7 {
-        pkcs7_pad(raw_bytes, block_size)
+/// Encrypts in aes-128 like try_encrypt_aes_128, returning the ciphertext as blocks rather than
+/// as flat bytes.
+///
+/// Fails like try_encrypt_aes_128, and with InvalidInputLength in CTR mode if the input isn't a
+/// multiple of BLOCK_SIZE, as the last block of the ciphertext would be incomplete.
+pub fn encrypt_blocks_vec(
+    raw_bytes: &[u8],
+    key: &Key,
+    options: &AESEncryptionOptions,
+) -> Result<Vec<[u8; BLOCK_SIZE]>, AesError> {
+    let cipher = try_encrypt_aes_128(raw_bytes, key, options)?;
+    if !cipher.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(AesError::InvalidInputLength);
+    }
+
+    Ok(cipher
+        .chunks_exact(BLOCK_SIZE)
+        .map(|block| {
+            let mut block_arr = [0u8; BLOCK_SIZE];
+            block_arr.copy_from_slice(block);
+            block_arr
+        })
+        .collect())
+}
+
+/// Encrypts states in place in aes-128 with the expanded key schedule, for pipelines that work on
+/// State values rather than on bytes. Each State is a block, chained according to mode: ECB and
+/// CTR encrypt the states independently, CTR XORing each with the encryption of its counter
+/// block, and CBC chains each to the previous one.
+pub fn encrypt_states(states: &mut [State], schedule: &KeySchedule, mode: &BlockCipherMode) {
+    let w = &schedule.0;
+
+    if let Some((nonce, initial_counter)) = mode.counter_start() {
+        let mut counter_block = [0u8; BLOCK_SIZE];
+        counter_block[..nonce.len()].copy_from_slice(nonce);
+        for (i, state) in states.iter_mut().enumerate() {
+            let counter = initial_counter.wrapping_add(i as u64);
+            counter_block[nonce.len()..].copy_from_slice(&counter.to_be_bytes());
+            let mut keystream = State::from_part(&counter_block);
+            cipher::cipher(&mut keystream, w);
+            state.xor_with_state(&keystream);
+        }
+    } else if let BlockCipherMode::CBC(iv) = mode {
+        let mut previous_state = State::from_part(&iv.to_bytes());
+        for state in states.iter_mut() {
+            state.xor_with_state(&previous_state);
+            cipher::cipher(state, w);
+            previous_state = state.clone();
+        }
     } else {
-        if let BlockCipherMode::CTR(nonce) = &options.block_cipher_mode {
-            ctr::generate_ctr_byte_stream_for_length(raw_bytes.len(), &nonce)
-        } else {
-            raw_bytes.to_vec()
+        cipher::warn_on_ecb(mode);
+        for state in states.iter_mut() {
+            cipher::cipher(state, w);
         }
-    };
-    let parts = bytes_to_parts(bytes);
+    }
+}
 
-    let mut cipher: Vec<u8> = Vec::with_capacity(raw_bytes.len());
-    let mut previous_state: State = State::empty();
+/// Encrypts each block in aes-128 ECB, emitting them in a custom order: block i of the output is
+/// the encryption of block order[i] of the input, e.g. order = [1, 0] swaps two blocks. ECB
+/// encrypts blocks independently, so the order doesn't change what each block encrypts to.
+///
+/// Fails with InvalidInputLength if raw_bytes isn't a multiple of BLOCK_SIZE, and with
+/// InvalidPermutation if order doesn't hold every block index exactly once.
+pub fn encrypt_blocks_custom_order(
+    raw_bytes: &[u8],
+    key: &Key,
+    order: &[usize],
+) -> Result<Vec<u8>, AesError> {
+    if !raw_bytes.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(AesError::InvalidInputLength);
+    }
 
-    for (i, part) in parts.iter().enumerate() {
-        let mut state = State::from_part(part);
-        if let BlockCipherMode::CBC(iv) = &options.block_cipher_mode {
-            if i == 0 {
-                state.xor_with_iv(&iv);
-            } else {
-                state.xor_with_state(&previous_state);
-            };
+    let block_count = raw_bytes.len() / BLOCK_SIZE;
+    if order.len() != block_count {
+        return Err(AesError::InvalidPermutation);
+    }
+    let mut seen = vec![false; block_count];
+    for &index in order {
+        if index >= block_count || seen[index] {
+            return Err(AesError::InvalidPermutation);
+        }
+        seen[index] = true;
+    }
+
+    cipher::warn_on_ecb(&BlockCipherMode::ECB);
+    let aes = Aes128::new(key);
+    let mut cipher = Vec::with_capacity(raw_bytes.len());
+    for &index in order {
+        let mut block = [0u8; BLOCK_SIZE];
+        block.copy_from_slice(&raw_bytes[index * BLOCK_SIZE..(index + 1) * BLOCK_SIZE]);
+        aes.encrypt_block(&mut block);
+        cipher.extend_from_slice(&block);
+    }
+
+    Ok(cipher)
+}
+
+/// Encrypts in aes-128, returning an error instead of panicking when the input is rejected.
+///
+/// With Padding::Prepadded, and with Padding::None outside of CTR, the input must already be a
+/// multiple of BLOCK_SIZE, or InvalidInputLength is returned. Empty input
+/// encrypts to an empty cipher, except with PKCS7 and ISO 10126 padding, where it encrypts to a
+/// single block of padding.
+pub fn try_encrypt_aes_128(
+    raw_bytes: &[u8],
+    key: &Key,
+    options: &AESEncryptionOptions,
+) -> Result<Vec<u8>, AesError> {
+    Aes128::new(key).encrypt_bytes(raw_bytes, options)
+}
+
+/// Encrypts in aes-128 like try_encrypt_aes_128, padding with any PaddingScheme, e.g. one defined
+/// outside of this crate. CTR ignores the padding, as with Padding.
+///
+/// Fails with InvalidInputLength if the padded input isn't a multiple of BLOCK_SIZE outside of CTR.
+pub fn encrypt_with_padding_scheme(
+    raw_bytes: &[u8],
+    key: &Key,
+    mode: &BlockCipherMode,
+    padding: &dyn PaddingScheme,
+) -> Result<Vec<u8>, AesError> {
+    let options = AESEncryptionOptions::new(mode, &Padding::None);
+    if mode.counter_start().is_some() {
+        return try_encrypt_aes_128(raw_bytes, key, &options);
+    }
+
+    try_encrypt_aes_128(&padding.pad(raw_bytes, BLOCK_SIZE), key, &options)
+}
+
+/// Decrypts the output of encrypt_with_padding_scheme and removes its padding with padding.
+pub fn decrypt_with_padding_scheme(
+    cipher: &[u8],
+    key: &Key,
+    mode: &BlockCipherMode,
+    padding: &dyn PaddingScheme,
+) -> Result<Vec<u8>, AesError> {
+    let mut padded_raw = vec![0u8; cipher.len()];
+    decrypt_into(cipher, key, mode, &Padding::None, &mut padded_raw)?;
+    if mode.counter_start().is_some() {
+        return Ok(padded_raw);
+    }
+
+    padding.unpad(&padded_raw, BLOCK_SIZE)
+}
+
+/// Encrypts in aes-128 like try_encrypt_aes_128, with the given implementation of the cipher. All
+/// backends produce the same ciphertext: CipherBackend::ConstantTime trades speed for timing that
+/// doesn't depend on the key nor on the data, and CipherBackend::ScatterGather trades less speed
+/// for S-box lookups whose memory accesses don't.
+pub fn encrypt_with_backend(
+    raw_bytes: &[u8],
+    key: &Key,
+    options: &AESEncryptionOptions,
+    backend: CipherBackend,
+) -> Result<Vec<u8>, AesError> {
+    match backend {
+        CipherBackend::Table => try_encrypt_aes_128(raw_bytes, key, options),
+        CipherBackend::ConstantTime => BitslicedAes128::new(key).encrypt_bytes(raw_bytes, options),
+        CipherBackend::ScatterGather => {
+            encrypt_bytes_with(&ScatterGatherAes128::new(key), raw_bytes, options)
         }
+    }
+}
+
+/// Encrypts in aes-128 like try_encrypt_aes_128, with the given mapping between the bytes of the
+/// blocks and the State. Standard AES is ByteOrder::ColumnMajor. ByteOrder::RowMajor transposes
+/// every block on its way into and out of the cipher, which is a change at the boundary and not to
+/// the cipher itself, e.g. to interoperate with an implementation that loads the State row by row.
+/// The IV, nonce and counter blocks are bytes in the same order as the data.
+pub fn encrypt_with_byte_order(
+    raw_bytes: &[u8],
+    key: &Key,
+    options: &AESEncryptionOptions,
+    byte_order: ByteOrder,
+) -> Result<Vec<u8>, AesError> {
+    if byte_order == ByteOrder::ColumnMajor {
+        return try_encrypt_aes_128(raw_bytes, key, options);
+    }
+
+    encrypt_bytes_with(&RowMajorAes128(Aes128::new(key)), raw_bytes, options)
+}
 
-        state.add_round_key(&w[0..Nb]);
+/// Encrypts raw_bytes with the given mode and padding, one block at a time with block_cipher.
+fn encrypt_bytes_with<C: BlockCipher>(
+    block_cipher: &C,
+    raw_bytes: &[u8],
+    options: &AESEncryptionOptions,
+) -> Result<Vec<u8>, AesError> {
+    options.check_input_length(raw_bytes)?;
 
-        for round in 1..Nr {
-            state.sub_bytes();
-            state.shift_rows();
-            state.mix_columns();
-            state.add_round_key(&w[round * Nb..(round + 1) * Nb]);
+    if let Some((nonce, initial_counter)) = options.block_cipher_mode.counter_start() {
+        let mut keystream =
+            ctr::generate_ctr_byte_stream_for_length(raw_bytes.len(), nonce, initial_counter);
+        for block in keystream.chunks_exact_mut(BLOCK_SIZE) {
+            block_cipher.encrypt_block(block);
         }
 
-        state.sub_bytes();
-        state.shift_rows();
-        state.add_round_key(&w[Nr * Nb..(Nr + 1) * Nb]);
+        return Ok(xor::xor_bytes(raw_bytes, &keystream[..raw_bytes.len()]));
+    }
+
+    let mut cipher = options.padding.pad(raw_bytes, BLOCK_SIZE);
+    encrypt_blocks(block_cipher, &mut cipher, options.block_cipher_mode);
 
-        if let BlockCipherMode::CBC(_iv) = &options.block_cipher_mode {
-            previous_state = state.clone();
+    Ok(cipher)
+}
+
+/// Decrypts aes-128 ciphers with the given mapping between the bytes of the blocks and the State,
+/// reversing encrypt_with_byte_order in every mode. Like decrypt_aes_128, the padding isn't
+/// removed.
+///
+/// Fails with InvalidInputLength if cipher isn't a multiple of BLOCK_SIZE outside of CTR.
+pub fn decrypt_with_byte_order(
+    cipher: &[u8],
+    key: &Key,
+    mode: &BlockCipherMode,
+    byte_order: ByteOrder,
+) -> Result<Vec<u8>, AesError> {
+    if mode.counter_start().is_some() {
+        // CTR decryption is encryption, and ignores padding
+        let options = AESEncryptionOptions::new(mode, &Padding::None);
+        return encrypt_with_byte_order(cipher, key, &options, byte_order);
+    }
+    if !cipher.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(AesError::InvalidInputLength);
+    }
+
+    let mut deciphered = cipher.to_vec();
+    match byte_order {
+        ByteOrder::ColumnMajor => decrypt_blocks(&Aes128::new(key), &mut deciphered, mode),
+        ByteOrder::RowMajor => decrypt_blocks(&RowMajorAes128(Aes128::new(key)), &mut deciphered, mode),
+    }
+
+    Ok(deciphered)
+}
+
+/// aes-128 over blocks whose bytes fill the State row by row, see ByteOrder::RowMajor.
+struct RowMajorAes128(Aes128);
+
+/// Transposes the block, encrypts or decrypts it as standard AES, then transposes it back.
+impl BlockCipher for RowMajorAes128 {
+    const BLOCK_SIZE: usize = BLOCK_SIZE;
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        transpose_block(block);
+        self.0.encrypt_block(block);
+        transpose_block(block);
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        transpose_block(block);
+        self.0.decrypt_block(block);
+        transpose_block(block);
+    }
+}
+
+/// Swaps bytes r + 4c and c + 4r of a block, switching between column-major and row-major order.
+fn transpose_block(block: &mut [u8]) {
+    for r in 0..4 {
+        for c in r + 1..Nb {
+            block.swap(r + 4 * c, c + 4 * r);
         }
+    }
+}
+
+/// Encrypts in aes-128 like try_encrypt_aes_128, calling progress with the total number of bytes
+/// encrypted so far every cipher::PROGRESS_INTERVAL bytes, the last call being at completion with
+/// the length of the cipher, e.g. to show a progress bar for large inputs.
+pub fn encrypt_with_progress<F: FnMut(usize)>(
+    raw_bytes: &[u8],
+    key: &Key,
+    options: &AESEncryptionOptions,
+    progress: F,
+) -> Result<Vec<u8>, AesError> {
+    Aes128::new(key).encrypt_bytes_with_progress(raw_bytes, options, progress)
+}
+
+/// Encrypts a single block in aes-128, on the stack and without any heap allocation.
+pub fn encrypt_block_arr(block: [u8; BLOCK_SIZE], key: &Key) -> [u8; BLOCK_SIZE] {
+    let w = &key.do_key_expansion().0;
+    let mut state = State::empty();
+    let mut out = [0u8; BLOCK_SIZE];
+
+    state.load_part(&block);
+    cipher::cipher(&mut state, w);
+    state.write_block(&mut out);
+
+    out
+}
+
+/// Encrypts the same block under each of keys in turn, e.g. to test candidate keys against a
+/// known plaintext and ciphertext pair. Each key is expanded and used on the stack, without any
+/// heap allocation, which makes it cheaper per key than encrypt_aes_128.
+pub fn encrypt_block_under_keys<I: Iterator<Item = Key>>(
+    block: &[u8; BLOCK_SIZE],
+    keys: I,
+) -> impl Iterator<Item = [u8; BLOCK_SIZE]> {
+    let block = *block;
+
+    keys.map(move |key| encrypt_block_arr(block, &key))
+}
+
+/// Encrypts the same block under each of keys like encrypt_block_under_keys, spreading the keys
+/// over threads.
+#[cfg(feature = "rayon")]
+pub fn encrypt_block_under_keys_parallel(block: &[u8; BLOCK_SIZE], keys: &[Key]) -> Vec<[u8; BLOCK_SIZE]> {
+    use rayon::prelude::*;
+
+    keys.par_iter().map(|key| encrypt_block_arr(*block, key)).collect()
+}
+
+/// Generates length bytes of CTR keystream, i.e. the encrypted counter blocks of
+/// BlockCipherMode::CTR, without XORing them with any data.
+pub fn ctr_keystream(key: &Key, nonce: &Nonce, length: usize) -> Vec<u8> {
+    Aes128::new(key).ctr_keystream(nonce, length)
+}
+
+/// Encrypts in aes-128 CTR mode with counter blocks computed by counter_fn, for protocols whose
+/// counter blocks aren't a nonce followed by a counter, e.g. a hash of a record number. The i-th
+/// block, starting at 0, is XORed with the encryption of counter_fn(i). As with CTR, counter
+/// blocks must never repeat under the same key.
+pub fn encrypt_ctr_with<F: Fn(u64) -> [u8; BLOCK_SIZE]>(
+    raw_bytes: &[u8],
+    key: &Key,
+    counter_fn: F,
+) -> Vec<u8> {
+    let aes = Aes128::new(key);
+    let mut cipher = raw_bytes.to_vec();
+
+    for (i, chunk) in cipher.chunks_mut(BLOCK_SIZE).enumerate() {
+        let mut keystream_block = counter_fn(i as u64);
+        aes.encrypt_block(&mut keystream_block);
+        xor::xor_in_place(chunk, &keystream_block);
+    }
+
+    cipher
+}
+
+/// Decrypts the output of encrypt_ctr_with, which is encrypting it again with the same counter_fn.
+pub fn decrypt_ctr_with<F: Fn(u64) -> [u8; BLOCK_SIZE]>(
+    cipher: &[u8],
+    key: &Key,
+    counter_fn: F,
+) -> Vec<u8> {
+    encrypt_ctr_with(cipher, key, counter_fn)
+}
+
+/// Encrypts in aes-128 CTR mode with a 32-bit counter, as GCM's GCTR does: the first block uses
+/// initial_counter_block as is, and each following block increments only its low 32 bits with
+/// inc32, wrapping around at 2^32 and leaving the upper 96 bits fixed. Past 2^32 blocks the
+/// keystream repeats. Decryption is encryption.
+pub fn encrypt_ctr32(
+    raw_bytes: &[u8],
+    key: &Key,
+    initial_counter_block: &[u8; BLOCK_SIZE],
+) -> Vec<u8> {
+    let aes = Aes128::new(key);
+    let mut counter_block = *initial_counter_block;
+    let mut cipher = raw_bytes.to_vec();
 
-        cipher.append(state.to_block().as_mut());
+    for chunk in cipher.chunks_mut(BLOCK_SIZE) {
+        let mut keystream_block = counter_block;
+        aes.encrypt_block(&mut keystream_block);
+        xor::xor_in_place(chunk, &keystream_block);
+        inc32(&mut counter_block);
     }
 
-    if let BlockCipherMode::CTR(_nonce) = &options.block_cipher_mode {
-        xor::fixed_key_xor(&raw_bytes, &cipher)
+    cipher
+}
+
+/// XORs data in place with the first data.len() bytes of an externally generated keystream, e.g.
+/// from ctr_keystream, which encrypts or decrypts it. Fails with KeystreamTooShort, leaving data
+/// unchanged, if the keystream is shorter than data.
+pub fn apply_keystream(data: &mut [u8], keystream: &[u8]) -> Result<(), AesError> {
+    if keystream.len() < data.len() {
+        return Err(AesError::KeystreamTooShort);
+    }
+
+    xor::xor_in_place(data, &keystream[..data.len()]);
+
+    Ok(())
+}
+
+/// Encrypts in aes-128 like try_encrypt_aes_128, also reporting the mode, the IV and the number
+/// of padding bytes that were used.
+pub fn encrypt_with_mode(
+    raw_bytes: &[u8],
+    key: &Key,
+    options: &AESEncryptionOptions,
+) -> Result<EncryptionResult, AesError> {
+    let ciphertext = try_encrypt_aes_128(raw_bytes, key, options)?;
+    let iv_used = if let BlockCipherMode::CBC(iv) = options.block_cipher_mode {
+        Some(**iv)
     } else {
-        cipher
+        None
+    };
+    let padding_added = ciphertext.len().saturating_sub(raw_bytes.len());
+
+    Ok(EncryptionResult {
+        ciphertext,
+        mode: options.block_cipher_mode.name().to_string(),
+        iv_used,
+        padding_added,
+    })
+}
+
+/// Encrypts in aes-128 CBC mode using a freshly generated random IV, which is reported in the
+/// result so that it can be stored alongside the ciphertext.
+#[cfg(feature = "rand")]
+pub fn encrypt_cbc_with_random_iv(
+    raw_bytes: &[u8],
+    key: &Key,
+    padding: &Padding,
+) -> Result<EncryptionResult, AesError> {
+    let iv = Iv::random();
+
+    encrypt_with_mode(
+        raw_bytes,
+        key,
+        &AESEncryptionOptions::new(&BlockCipherMode::CBC(&iv), padding),
+    )
+}
+
+/// Encrypts in aes-128 CBC mode with PKCS7 padding under a random IV, and returns the IV followed
+/// by the ciphertext, to be decrypted with decrypt_cbc. The IV and the padding are managed here.
+///
+/// The output isn't authenticated: prefer secure_cbc, which also takes a MAC key, whenever the
+/// ciphertext can be tampered with.
+#[cfg(feature = "rand")]
+pub fn encrypt_cbc(raw_bytes: &[u8], key: &Key) -> Vec<u8> {
+    let iv = Iv::random();
+    let cipher = encrypt_aes_128(
+        raw_bytes,
+        key,
+        &AESEncryptionOptions::new(&BlockCipherMode::CBC(&iv), &Padding::PKCS7),
+    );
+
+    [&iv.to_bytes()[..], &cipher[..]].concat()
+}
+
+/// Decrypts the output of encrypt_cbc and removes its padding.
+///
+/// Fails with InvalidInputLength if iv_and_cipher isn't an IV followed by at least one block, and
+/// with InvalidPadding on malformed padding, which must not be reported to whoever supplied the
+/// ciphertext, as it would make a padding oracle.
+pub fn decrypt_cbc(iv_and_cipher: &[u8], key: &Key) -> Result<Vec<u8>, AesError> {
+    if iv_and_cipher.len() < 2 * BLOCK_SIZE || !iv_and_cipher.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(AesError::InvalidInputLength);
+    }
+    let iv = extract_iv(iv_and_cipher)?;
+
+    let padded_raw = decrypt_aes_128(&iv_and_cipher[BLOCK_SIZE..], key, &BlockCipherMode::CBC(&iv));
+
+    pad::unpad_aes(&padded_raw, &Padding::PKCS7)
+}
+
+/// Reads the IV at the start of an IV || ciphertext blob, such as the output of encrypt_cbc,
+/// without decrypting it, e.g. for logging or to detect duplicates. Fails with InvalidInputLength
+/// if blob is shorter than an IV.
+pub fn extract_iv(blob: &[u8]) -> Result<Iv, AesError> {
+    if blob.len() < BLOCK_SIZE {
+        return Err(AesError::InvalidInputLength);
+    }
+    let mut iv = [0u8; BLOCK_SIZE];
+    iv.copy_from_slice(&blob[..BLOCK_SIZE]);
+
+    Ok(Iv::from_bytes(&iv))
+}
+
+/// Derives a CBC IV from a message counter by encrypting the counter block 0^64 || counter
+/// (big-endian) under key, which may be the key the message is then encrypted with (SP 800-38A,
+/// Appendix C). The IV is unpredictable to whoever doesn't hold key, unlike the counter itself.
+///
+/// counter must never repeat under the same key: the same counter always gives the same IV, and
+/// so the same ciphertext for the same message.
+pub fn cbc_iv_from_counter(key: &Key, counter: u64) -> Iv {
+    let mut counter_block = [0u8; BLOCK_SIZE];
+    counter_block[BLOCK_SIZE - 8..].copy_from_slice(&counter.to_be_bytes());
+    Aes128::new(key).encrypt_block(&mut counter_block);
+
+    Iv::from_bytes(&counter_block)
+}
+
+/// Encrypts in aes-128 CBC mode with PKCS7 padding, using IV = CMAC(iv_key, raw_bytes), and
+/// returns the IV followed by the ciphertext.
+///
+/// This is deterministic: the same plaintext under the same keys always gives the same output,
+/// which allows deduplicating encrypted data, but also reveals to an observer which messages are
+/// equal. Different plaintexts get unrelated IVs, which can't be predicted without iv_key. The IV
+/// also authenticates the plaintext, which decrypt_cbc_deterministic checks. enc_key and iv_key
+/// must be independent keys.
+pub fn encrypt_cbc_deterministic(raw_bytes: &[u8], enc_key: &Key, iv_key: &Key) -> Vec<u8> {
+    let iv = Iv::from_bytes(cmac::cmac(iv_key, raw_bytes).as_bytes());
+    let cipher = encrypt_aes_128(
+        raw_bytes,
+        enc_key,
+        &AESEncryptionOptions::new(&BlockCipherMode::CBC(&iv), &Padding::PKCS7),
+    );
+
+    [&iv.to_bytes()[..], &cipher[..]].concat()
+}
+
+/// Decrypts the output of encrypt_cbc_deterministic, checking that the IV is the CMAC of the
+/// decrypted plaintext.
+pub fn decrypt_cbc_deterministic(
+    iv_and_cipher: &[u8],
+    enc_key: &Key,
+    iv_key: &Key,
+) -> Result<Vec<u8>, AesError> {
+    if iv_and_cipher.len() < 2 * BLOCK_SIZE || !iv_and_cipher.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(AesError::InvalidInputLength);
     }
+    let (iv_bytes, cipher) = iv_and_cipher.split_at(BLOCK_SIZE);
+    let mut iv = [0u8; BLOCK_SIZE];
+    iv.copy_from_slice(iv_bytes);
+
+    let padded_raw = decrypt_aes_128(cipher, enc_key, &BlockCipherMode::CBC(&Iv::from_bytes(&iv)));
+    let raw = pad::unpad_aes(&padded_raw, &Padding::PKCS7)
+        .map_err(|_| AesError::AuthenticationFailed(TagMismatch::new(None)))?;
+
+    let computed_iv = cmac::cmac(iv_key, &raw).to_bytes();
+    if !constant_time::verify_eq(&computed_iv, &iv) {
+        return Err(AesError::AuthenticationFailed(TagMismatch::new(Some(computed_iv))));
+    }
+
+    Ok(raw)
 }
 
+/// Number of blocks per chunk of parallel CBC decryption, as a single block is too little work to
+/// hand to a thread.
+#[cfg(feature = "rayon")]
+pub const PARALLEL_CBC_CHUNK_BLOCKS: usize = 256;
+
 /// Decrypts aes-128 ciphers.
+///
+/// With the rayon feature, CBC ciphers of at least two chunks of PARALLEL_CBC_CHUNK_BLOCKS blocks
+/// are decrypted in parallel, like decrypt_cbc_parallel.
 pub fn decrypt_aes_128(cipher: &[u8], key: &Key, mode: &BlockCipherMode) -> Vec<u8> {
-    if let BlockCipherMode::CTR(_nonce) = mode {
-        panic!("Cannot decrypt using CTR block cipher mode. Use encryption instead.");
+    #[cfg(feature = "rayon")]
+    {
+        if let BlockCipherMode::CBC(iv) = mode {
+            if cipher.len() >= 2 * PARALLEL_CBC_CHUNK_BLOCKS * BLOCK_SIZE {
+                return decrypt_cbc_parallel(cipher, key, iv);
+            }
+        }
     }
 
-    let w = &key.do_key_expansion().0;
-    let parts = bytes_to_parts(cipher);
-    let mut deciphered: Vec<u8> = Vec::with_capacity(cipher.len());
-    let mut previous_state = State::empty();
+    Aes128::new(key).decrypt_bytes(cipher, mode)
+}
+
+/// Decrypts an aes-128 CBC cipher without removing its padding, returning the decrypted bytes and
+/// whether they end with valid PKCS7 padding. The padding is checked in constant time and the
+/// bytes are returned whether it is valid or not.
+///
+/// DANGER: this is the primitive of the padding oracle attack. Anyone who learns the boolean for
+/// ciphertexts of their choosing, even indirectly through an error message or a response time,
+/// can decrypt any ciphertext under key, one byte at a time. It exists to demonstrate the attack;
+/// never expose the result to an attacker, and prefer an authenticated mode such as
+/// secure_cbc, which never looks at the padding of unauthenticated data.
+///
+/// An incomplete trailing block is ignored, and an empty cipher has invalid padding.
+pub fn decrypt_cbc_check_padding(cipher: &[u8], key: &Key, iv: &Iv) -> (Vec<u8>, bool) {
+    let deciphered = decrypt_aes_128(cipher, key, &BlockCipherMode::CBC(iv));
+    if deciphered.is_empty() {
+        return (deciphered, false);
+    }
 
-    for (i, part) in parts.iter().enumerate() {
-        let mut state = State::from_part(part);
+    let mut last_block = [0u8; BLOCK_SIZE];
+    last_block.copy_from_slice(&deciphered[deciphered.len() - BLOCK_SIZE..]);
+    let is_valid = constant_time::pkcs7_padding_is_valid(&last_block);
 
-        state.add_round_key(&w[Nr * Nb..(Nr + 1) * Nb]);
+    (deciphered, is_valid)
+}
 
-        for round in (1..Nr).rev() {
-            state.inv_shift_rows();
-            state.inv_sub_bytes();
-            state.add_round_key(&w[round * Nb..(round + 1) * Nb]);
-            state.inv_mix_columns();
-        }
+/// Decrypts aes-128 CBC ciphers like decrypt_aes_128, decrypting chunks of blocks in parallel.
+/// Unlike encryption, CBC decryption of a block only depends on its ciphertext block and on the
+/// previous one, which are both known upfront: each chunk is decrypted sequentially, chaining from
+/// the ciphertext block immediately preceding it.
+#[cfg(feature = "rayon")]
+pub fn decrypt_cbc_parallel(cipher: &[u8], key: &Key, iv: &Iv) -> Vec<u8> {
+    decrypt_cbc_in_chunks(cipher, &Aes128::new(key), iv, PARALLEL_CBC_CHUNK_BLOCKS)
+}
 
-        state.inv_shift_rows();
-        state.inv_sub_bytes();
-        state.add_round_key(&w[0..Nb]);
+#[cfg(feature = "rayon")]
+fn decrypt_cbc_in_chunks(cipher: &[u8], aes: &Aes128, iv: &Iv, blocks_per_chunk: usize) -> Vec<u8> {
+    use rayon::prelude::*;
 
-        if let BlockCipherMode::CBC(iv) = mode {
-            if i == 0 {
-                state.xor_with_iv(iv);
+    let chunk_size = blocks_per_chunk * BLOCK_SIZE;
+    let mut deciphered = cipher[..cipher.len() / BLOCK_SIZE * BLOCK_SIZE].to_vec();
+
+    deciphered.par_chunks_mut(chunk_size)
+        .enumerate()
+        .for_each(|(i, chunk)| {
+            let start = i * chunk_size;
+            let previous_block = if start == 0 {
+                *iv
             } else {
-                state.xor_with_state(&previous_state);
+                let mut previous_block = [0u8; BLOCK_SIZE];
+                previous_block.copy_from_slice(&cipher[start - BLOCK_SIZE..start]);
+                Iv::from_bytes(&previous_block)
             };
-            previous_state = State::from_part(part);
+
+            decrypt_blocks(aes, chunk, &BlockCipherMode::CBC(&previous_block));
+        });
+
+    deciphered
+}
+
+/// Decrypts in aes-128 into out, removing the given padding, and returns the number of plaintext
+/// bytes, so that the plaintext is &out[..n]. out must be at least as long as cipher, as the
+/// padding is only known once decrypted.
+pub fn decrypt_into(
+    cipher: &[u8],
+    key: &Key,
+    mode: &BlockCipherMode,
+    padding: &Padding,
+    out: &mut [u8],
+) -> Result<usize, AesError> {
+    if out.len() < cipher.len() {
+        return Err(AesError::BufferTooSmall);
+    }
+    let out = &mut out[..cipher.len()];
+
+    if mode.counter_start().is_some() {
+        // CTR decryption is encryption, and ignores padding
+        let options = AESEncryptionOptions::new(mode, &Padding::None);
+        out.copy_from_slice(&try_encrypt_aes_128(cipher, key, &options)?);
+
+        return Ok(cipher.len());
+    }
+    if !cipher.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(AesError::InvalidInputLength);
+    }
+
+    out.copy_from_slice(cipher);
+    decrypt_blocks(&Aes128::new(key), out, mode);
+
+    pad::unpadded_length_aes(out, padding)
+}
+
+/// Decrypts in aes-128 from a borrowed cipher, e.g. a memory-mapped file, into out, without
+/// removing any padding, and returns the number of bytes written, which is the length of cipher.
+/// Nothing the size of cipher is allocated: it is copied once, into out, and decrypted there, and
+/// the CTR keystream is generated one block at a time.
+///
+/// Fails with BufferTooSmall if out is shorter than cipher, and with InvalidInputLength if cipher
+/// isn't a multiple of BLOCK_SIZE outside of CTR.
+pub fn decrypt_slice_into(
+    cipher: &[u8],
+    key: &Key,
+    mode: &BlockCipherMode,
+    out: &mut [u8],
+) -> Result<usize, AesError> {
+    if out.len() < cipher.len() {
+        return Err(AesError::BufferTooSmall);
+    }
+    if mode.counter_start().is_none() && !cipher.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(AesError::InvalidInputLength);
+    }
+    let out = &mut out[..cipher.len()];
+    out.copy_from_slice(cipher);
+    decrypt_in_place(&Aes128::new(key), out, mode);
+
+    Ok(cipher.len())
+}
+
+/// Decrypts data in place, generating the CTR keystream one block at a time. Outside of CTR, data
+/// must be a multiple of BLOCK_SIZE.
+fn decrypt_in_place(aes: &Aes128, data: &mut [u8], mode: &BlockCipherMode) {
+    match mode.counter_start() {
+        Some((nonce, initial_counter)) => {
+            let mut counter = initial_counter;
+            for chunk in data.chunks_mut(BLOCK_SIZE) {
+                let mut keystream = [0u8; BLOCK_SIZE];
+                keystream[..nonce.len()].copy_from_slice(nonce);
+                keystream[nonce.len()..].copy_from_slice(&counter.to_be_bytes());
+                aes.encrypt_block(&mut keystream);
+                xor::xor_in_place(chunk, &keystream);
+                counter = counter.wrapping_add(1);
+            }
         }
+        None => decrypt_blocks(aes, data, mode),
+    }
+}
+
+/// Re-encrypts cipher, e.g. to rotate the key of stored data: decrypts it under old_key with the
+/// mode and padding of old_options, and encrypts the plaintext again under new_key with
+/// new_options, which must carry a fresh IV or nonce, as for any new encryption.
+///
+/// The plaintext is transiently in memory in between. Its buffer is overwritten with zeros before
+/// returning, but copies may remain, e.g. in registers or where the allocator moved it.
+pub fn reencrypt(
+    cipher: &[u8],
+    old_key: &Key,
+    old_options: &AESEncryptionOptions,
+    new_key: &Key,
+    new_options: &AESEncryptionOptions,
+) -> Result<Vec<u8>, AesError> {
+    let mut raw_bytes = vec![0u8; cipher.len()];
+    let result = decrypt_into(
+        cipher,
+        old_key,
+        old_options.block_cipher_mode,
+        old_options.padding,
+        &mut raw_bytes,
+    )
+    .and_then(|length| try_encrypt_aes_128(&raw_bytes[..length], new_key, new_options));
+
+    wipe(&mut raw_bytes);
 
-        deciphered.append(state.to_block().as_mut());
+    result
+}
+
+/// Re-encrypts the output of encrypt_cbc under new_key, with a newly generated random IV, like
+/// reencrypt. Fails like decrypt_cbc.
+#[cfg(feature = "rand")]
+pub fn reencrypt_cbc(iv_and_cipher: &[u8], old_key: &Key, new_key: &Key) -> Result<Vec<u8>, AesError> {
+    let mut raw_bytes = decrypt_cbc(iv_and_cipher, old_key)?;
+    let iv_and_cipher = encrypt_cbc(&raw_bytes, new_key);
+
+    wipe(&mut raw_bytes);
+
+    Ok(iv_and_cipher)
+}
+
+/// Encrypts in aes-128 like try_encrypt_aes_128, and encodes the ciphertext in base 64, with the
+/// standard alphabet and padding. Fails like try_encrypt_aes_128.
+pub fn encrypt_to_base64(
+    raw_bytes: &[u8],
+    key: &Key,
+    options: &AESEncryptionOptions,
+) -> Result<String, AesError> {
+    Ok(base64::encode(&try_encrypt_aes_128(raw_bytes, key, options)?))
+}
+
+/// Decodes a base 64 ciphertext and decrypts it in aes-128, in the decoded buffer. Like
+/// decrypt_aes_128, the padding isn't removed.
+pub fn decrypt_from_base64(
+    encoded: &str,
+    key: &Key,
+    mode: &BlockCipherMode,
+) -> Result<Vec<u8>, AesError> {
+    let mut deciphered = base64::decode(encoded)?;
+    if mode.counter_start().is_none() && !deciphered.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(AesError::InvalidInputLength);
     }
 
-    deciphered
+    decrypt_in_place(&Aes128::new(key), &mut deciphered, mode);
+
+    Ok(deciphered)
 }
 
-/// chunks a slice of bytes to chunks of block_size length
-pub fn bytes_to_parts(bytes: &[u8]) -> Vec<&[u8]> {
-    let block_size = 16usize;
+/// Encrypts complete blocks of data in place in ECB or CBC mode.
+fn encrypt_blocks<C: BlockCipher>(block_cipher: &C, data: &mut [u8], mode: &BlockCipherMode) {
+    cipher::warn_on_ecb(mode);
+
+    // previous ciphertext block, which for CBC starts out as the IV
+    let mut previous_block = match mode {
+        BlockCipherMode::CBC(iv) => iv.to_bytes().to_vec(),
+        _ => vec![],
+    };
+
+    for block in data.chunks_exact_mut(C::BLOCK_SIZE) {
+        if let BlockCipherMode::CBC(_iv) = mode {
+            xor::xor_in_place(block, &previous_block);
+        }
+
+        block_cipher.encrypt_block(block);
 
-    bytes.chunks_exact(block_size).collect()
+        if let BlockCipherMode::CBC(_iv) = mode {
+            previous_block.copy_from_slice(block);
+        }
+    }
+}
+
+/// Decrypts complete blocks of data in place in ECB or CBC mode.
+fn decrypt_blocks<C: BlockCipher>(block_cipher: &C, data: &mut [u8], mode: &BlockCipherMode) {
+    // previous ciphertext block, which for CBC starts out as the IV
+    let mut previous_block = match mode {
+        BlockCipherMode::CBC(iv) => iv.to_bytes().to_vec(),
+        _ => vec![],
+    };
+    let mut cipher_block = previous_block.clone();
+
+    for block in data.chunks_exact_mut(C::BLOCK_SIZE) {
+        if let BlockCipherMode::CBC(_iv) = mode {
+            cipher_block.copy_from_slice(block);
+        }
+
+        block_cipher.decrypt_block(block);
+
+        if let BlockCipherMode::CBC(_iv) = mode {
+            xor::xor_in_place(block, &previous_block);
+            std::mem::swap(&mut previous_block, &mut cipher_block);
+        }
+    }
+}
+
+/// chunks a slice of bytes to chunks of BLOCK_SIZE length
+pub fn bytes_to_parts(bytes: &[u8]) -> Vec<&[u8]> {
+    bytes.chunks_exact(BLOCK_SIZE).collect()
 }
 
 /// Some encryption/decryption test cases are taken from:
@@ -253,104 +1109,1205 @@ mod tests {
     }
 
     #[test]
-    fn empty_produces_empty_block() {
-        let block = Block::empty();
-        let expected_block = [
-            [0, 0, 0, 0],
-            [0, 0, 0, 0],
-            [0, 0, 0, 0],
-            [0, 0, 0, 0],
-        ];
+    fn encrypts_block_under_each_key_like_single_calls() {
+        let keys = [ECB_KEY, CBC_KEY, CTR_KEY, Key([0x00; 16])];
+        let block = [0x42; BLOCK_SIZE];
 
-        assert_eq!(block.0, expected_block);
+        let ciphers: Vec<[u8; BLOCK_SIZE]> = encrypt_block_under_keys(&block, keys.iter().cloned()).collect();
+
+        assert_eq!(ciphers.len(), keys.len());
+        for (cipher, key) in ciphers.iter().zip(keys.iter()) {
+            assert_eq!(cipher[..], encrypt_aes_128(&block, key, &AESEncryptionOptions::default())[..]);
+        }
     }
 
     #[test]
-    fn encrypts_in_ecb_mode() {
-        let actual_cipher = encrypt_aes_128(
-            &RAW_ECB,
-            &ECB_KEY,
-            &AESEncryptionOptions::new(
-                &BlockCipherMode::ECB,
-                &Padding::None,
+    #[cfg(feature = "rayon")]
+    fn encrypts_block_under_keys_in_parallel_like_sequentially() {
+        let keys: Vec<Key> = (0..100u8).map(|i| Key([i; 16])).collect();
+        let block = [0x42; BLOCK_SIZE];
+
+        let expected: Vec<[u8; BLOCK_SIZE]> = encrypt_block_under_keys(&block, keys.iter().cloned()).collect();
+
+        assert_eq!(encrypt_block_under_keys_parallel(&block, &keys), expected);
+    }
+
+    #[test]
+    fn zero_iv_is_all_zero_bytes() {
+        assert_eq!(Iv::zero().to_bytes(), [0u8; BLOCK_SIZE]);
+        assert_eq!(Iv::zero(), Block::empty());
+    }
+
+    #[test]
+    fn cbc_with_zero_iv_encrypts_first_block_like_ecb() {
+        let raw: Vec<u8> = (0..3 * BLOCK_SIZE as u8).collect();
+        let iv = Iv::zero();
+        let mode = BlockCipherMode::CBC(&iv);
+        let cbc_options = AESEncryptionOptions::new(&mode, &Padding::None);
+
+        let cbc_cipher = encrypt_aes_128(&raw, &CBC_KEY, &cbc_options);
+        let ecb_cipher = encrypt_aes_128(&raw[..BLOCK_SIZE], &CBC_KEY, &AESEncryptionOptions::default());
+
+        assert_eq!(cbc_cipher[..BLOCK_SIZE], ecb_cipher[..]);
+    }
+
+    #[test]
+    fn empty_produces_empty_block() {
+        let block = Block::empty();
+        let expected_block = [
+            [0, 0, 0, 0],
+            [0, 0, 0, 0],
+            [0, 0, 0, 0],
+            [0, 0, 0, 0],
+        ];
+
+        assert_eq!(block.0, expected_block);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn random_from_generates_block_from_given_rng() {
+        let mut rng = rand::rngs::mock::StepRng::new(0x0706050403020100, 0x0808080808080808);
+
+        let iv = Iv::random_from(&mut rng);
+
+        assert_eq!(iv.to_bytes(), [
+            0x00, 0x01, 0x02, 0x03,
+            0x04, 0x05, 0x06, 0x07,
+            0x08, 0x09, 0x0a, 0x0b,
+            0x0c, 0x0d, 0x0e, 0x0f
+        ]);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn random_generates_different_blocks() {
+        assert_ne!(Iv::random(), Iv::random());
+    }
+
+    #[test]
+    fn increments_block() {
+        let mut block = Block::empty();
+
+        block.increment();
+
+        assert_eq!(block, Block([[0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 1]]));
+    }
+
+    #[test]
+    fn increments_block_with_carry_across_byte_boundaries() {
+        let mut block = Block([[0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0xff, 0xff]]);
+
+        block.increment();
+
+        assert_eq!(block, Block([[0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0], [0, 1, 0, 0]]));
+    }
+
+    #[test]
+    fn increments_block_with_carry_across_words() {
+        let mut block = Block([[0, 0, 0, 0], [0, 0, 0, 0xff], [0xff, 0xff, 0xff, 0xff], [0xff, 0xff, 0xff, 0xff]]);
+
+        block.increment();
+
+        assert_eq!(block, Block([[0, 0, 0, 0], [0, 0, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]]));
+    }
+
+    #[test]
+    fn increment_wraps_around_to_zero() {
+        let mut block = Block::from_bytes(&[0xff; BLOCK_SIZE]);
+
+        block.increment();
+
+        assert_eq!(block, Block::empty());
+    }
+
+    #[test]
+    fn increments_block_by_n() {
+        let mut block = Block([[0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0xff, 0xfe]]);
+
+        block.increment_by(0x0103);
+
+        assert_eq!(block, Block([[0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0], [0, 1, 1, 1]]));
+    }
+
+    #[test]
+    fn increment_by_wraps_around() {
+        let mut block = Block::from_bytes(&[0xff; BLOCK_SIZE]);
+
+        block.increment_by(3);
+
+        assert_eq!(block, Block([[0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 2]]));
+    }
+
+    #[test]
+    fn from_bytes_builds_block_in_cipher_byte_order() {
+        let bytes = [
+            0x00, 0x01, 0x02, 0x03,
+            0x04, 0x05, 0x06, 0x07,
+            0x08, 0x09, 0x0a, 0x0b,
+            0x0c, 0x0d, 0x0e, 0x0f
+        ];
+
+        let block = Block::from_bytes(&bytes);
+
+        assert_eq!(block, CBC_IV);
+        assert_eq!(block.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn encrypts_in_cbc_mode_with_iv_from_bytes() {
+        let iv = Iv::from_bytes(&[
+            0x00, 0x01, 0x02, 0x03,
+            0x04, 0x05, 0x06, 0x07,
+            0x08, 0x09, 0x0a, 0x0b,
+            0x0c, 0x0d, 0x0e, 0x0f
+        ]);
+
+        let actual_cipher = encrypt_aes_128(
+            &RAW_CBC,
+            &CBC_KEY,
+            &AESEncryptionOptions::new(
+                &BlockCipherMode::CBC(&iv),
+                &Padding::None,
+            ),
+        );
+
+        assert_eq!(actual_cipher, CIPHERED_CBC);
+    }
+
+    #[test]
+    fn encrypts_in_ecb_mode() {
+        let actual_cipher = encrypt_aes_128(
+            &RAW_ECB,
+            &ECB_KEY,
+            &AESEncryptionOptions::new(
+                &BlockCipherMode::ECB,
+                &Padding::None,
+            ),
+        );
+
+        assert_eq!(actual_cipher, CIPHERED_ECB);
+    }
+
+    #[test]
+    fn encrypts_prepadded_input() {
+        let actual_cipher = try_encrypt_aes_128(
+            &RAW_ECB,
+            &ECB_KEY,
+            &AESEncryptionOptions::new(
+                &BlockCipherMode::ECB,
+                &Padding::Prepadded,
+            ),
+        );
+
+        assert_eq!(actual_cipher, Ok(CIPHERED_ECB.to_vec()));
+    }
+
+    #[test]
+    fn encrypts_states_like_bytes() {
+        let raw: Vec<u8> = (0..4 * BLOCK_SIZE).map(|i| i as u8).collect();
+        let schedule = CBC_KEY.do_key_expansion();
+        let modes = [
+            BlockCipherMode::ECB,
+            BlockCipherMode::CBC(&CBC_IV),
+            BlockCipherMode::CTR(&CTR_NONCE),
+            BlockCipherMode::CTRWithCounter(&CTR_NONCE, u64::MAX),
+        ];
+
+        for mode in modes.iter() {
+            let mut states: Vec<State> = raw.chunks_exact(BLOCK_SIZE).map(State::from_part).collect();
+
+            encrypt_states(&mut states, &schedule, mode);
+
+            let cipher: Vec<u8> = states.iter().flat_map(|state| state.to_block()).collect();
+            assert_eq!(cipher, encrypt_aes_128(&raw, &CBC_KEY, &AESEncryptionOptions::new(mode, &Padding::None)));
+        }
+    }
+
+    #[test]
+    fn rejects_unpadded_input_that_is_not_block_aligned() {
+        let cbc = BlockCipherMode::CBC(&CBC_IV);
+        let raw = [0x42; 20];
+
+        for mode in [&cbc, &BlockCipherMode::ECB].iter() {
+            let options = AESEncryptionOptions::new(mode, &Padding::None);
+
+            assert_eq!(try_encrypt_aes_128(&raw, &CBC_KEY, &options), Err(AesError::InvalidInputLength));
+            assert_eq!(
+                encrypt_with_byte_order(&raw, &CBC_KEY, &options, ByteOrder::RowMajor),
+                Err(AesError::InvalidInputLength)
+            );
+            assert_eq!(
+                encrypt_with_backend(&raw, &CBC_KEY, &options, CipherBackend::ConstantTime),
+                Err(AesError::InvalidInputLength)
+            );
+            assert_eq!(try_encrypt_aes_128(&raw[..16], &CBC_KEY, &options).map(|cipher| cipher.len()), Ok(16));
+        }
+    }
+
+    #[test]
+    fn encrypts_unpadded_input_of_any_length_in_ctr() {
+        let options = AESEncryptionOptions::new(&BlockCipherMode::CTR(&CTR_NONCE), &Padding::None);
+
+        assert_eq!(try_encrypt_aes_128(&[0x42; 20], &CTR_KEY, &options).map(|cipher| cipher.len()), Ok(20));
+    }
+
+    #[test]
+    fn rejects_prepadded_input_that_is_not_block_aligned() {
+        let actual_cipher = try_encrypt_aes_128(
+            &RAW_ECB[..15],
+            &ECB_KEY,
+            &AESEncryptionOptions::new(
+                &BlockCipherMode::ECB,
+                &Padding::Prepadded,
+            ),
+        );
+
+        assert_eq!(actual_cipher, Err(AesError::InvalidInputLength));
+    }
+
+    #[test]
+    #[should_panic(expected = "Input length must be a multiple of the block size (16 bytes).")]
+    fn encryption_of_misaligned_prepadded_input_should_panic() {
+        encrypt_aes_128(
+            &RAW_ECB[..15],
+            &ECB_KEY,
+            &AESEncryptionOptions::new(
+                &BlockCipherMode::ECB,
+                &Padding::Prepadded,
+            ),
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn encrypts_and_decrypts_with_iso10126_padding() {
+        let raw = b"Some bytes that aren't block aligned";
+
+        let cipher = encrypt_aes_128(
+            raw,
+            &CBC_KEY,
+            &AESEncryptionOptions::new(
+                &BlockCipherMode::CBC(&CBC_IV),
+                &Padding::Iso10126,
+            ),
+        );
+        let padded_raw = decrypt_aes_128(&cipher, &CBC_KEY, &BlockCipherMode::CBC(&CBC_IV));
+
+        assert_eq!(cipher.len(), 48);
+        assert_eq!(
+            pad::unpad_aes(&padded_raw, &Padding::Iso10126),
+            Ok(raw.to_vec())
+        );
+    }
+
+    #[test]
+    fn encrypts_block_arr() {
+        assert_eq!(encrypt_block_arr(RAW_ECB, &ECB_KEY), CIPHERED_ECB);
+    }
+
+    #[test]
+    fn ctr_keystream_xored_with_raw_is_ctr_cipher() {
+        let raw = b"Some plaintext that spans a few blocks and a partial one";
+
+        let keystream = ctr_keystream(&CTR_KEY, &CTR_NONCE, raw.len());
+        let cipher = encrypt_aes_128(
+            raw,
+            &CTR_KEY,
+            &AESEncryptionOptions::new(&BlockCipherMode::CTR(&CTR_NONCE), &Padding::None),
+        );
+
+        assert_eq!(keystream.len(), raw.len());
+        assert_eq!(xor::fixed_key_xor(raw, &keystream), cipher);
+    }
+
+    #[test]
+    fn ctr_keystream_is_first_counter_block_encrypted() {
+        let keystream = ctr_keystream(&CTR_KEY, &CTR_NONCE, BLOCK_SIZE);
+
+        // E(K, 0xffffffffffffffff0000000000000001)
+        assert_eq!(keystream, vec![
+            0x17, 0x94, 0x2b, 0xb2,
+            0x70, 0x0f, 0x1d, 0x82,
+            0xca, 0x97, 0x15, 0x79,
+            0xbb, 0xc8, 0xe0, 0xca,
+        ]);
+    }
+
+    #[test]
+    fn ctr_keystream_is_encrypted_counter_block() {
+        let keystream = ctr_keystream(&CTR_KEY, &CTR_NONCE, BLOCK_SIZE);
+
+        assert_eq!(xor::fixed_key_xor(&RAW_CTR, &keystream), CIPHERED_CTR);
+    }
+
+    #[test]
+    fn encrypts_blocks_in_reverse_order_restored_by_inverse_permutation() {
+        let raw = [[0x01; BLOCK_SIZE], [0x02; BLOCK_SIZE]].concat();
+
+        let cipher = encrypt_blocks_custom_order(&raw, &ECB_KEY, &[1, 0]).unwrap();
+        let deciphered = decrypt_aes_128(&cipher, &ECB_KEY, &BlockCipherMode::ECB);
+
+        // reversing is its own inverse
+        let restored = [&deciphered[BLOCK_SIZE..], &deciphered[..BLOCK_SIZE]].concat();
+        assert_eq!(restored, raw);
+        assert_eq!(
+            encrypt_blocks_custom_order(&raw, &ECB_KEY, &[0, 1]),
+            Ok(encrypt_aes_128(&raw, &ECB_KEY, &AESEncryptionOptions::default()))
+        );
+    }
+
+    #[test]
+    fn encrypt_blocks_custom_order_rejects_invalid_permutations() {
+        let raw = [0x42; 3 * BLOCK_SIZE];
+
+        for order in [&[0, 1][..], &[0, 1, 1], &[0, 1, 3], &[0, 1, 2, 3]].iter() {
+            assert_eq!(encrypt_blocks_custom_order(&raw, &ECB_KEY, order), Err(AesError::InvalidPermutation));
+        }
+        assert_eq!(encrypt_blocks_custom_order(&raw[1..], &ECB_KEY, &[0, 1]), Err(AesError::InvalidInputLength));
+    }
+
+    #[test]
+    fn encrypts_to_blocks_that_flatten_to_flat_cipher() {
+        let raw: Vec<u8> = (0..40).collect();
+        let modes = [BlockCipherMode::ECB, BlockCipherMode::CBC(&CBC_IV)];
+
+        for mode in modes.iter() {
+            let options = AESEncryptionOptions::new(mode, &Padding::PKCS7);
+
+            let blocks = encrypt_blocks_vec(&raw, &CBC_KEY, &options).unwrap();
+
+            assert_eq!(blocks.len(), 3);
+            assert_eq!(blocks.concat(), encrypt_aes_128(&raw, &CBC_KEY, &options));
+        }
+    }
+
+    #[test]
+    fn encrypt_blocks_vec_refuses_incomplete_ctr_block() {
+        let mode = BlockCipherMode::CTR(&CTR_NONCE);
+        let options = AESEncryptionOptions::new(&mode, &Padding::None);
+
+        assert_eq!(encrypt_blocks_vec(&[0x42; 20], &CTR_KEY, &options), Err(AesError::InvalidInputLength));
+        assert_eq!(encrypt_blocks_vec(&[0x42; 32], &CTR_KEY, &options).map(|blocks| blocks.len()), Ok(2));
+    }
+
+    #[test]
+    fn round_trips_ctr_with_custom_counter_function() {
+        let raw: Vec<u8> = (0..50).collect();
+        // the record number little-endian in the first 8 bytes, a fixed label in the last 8
+        let counter_fn = |i: u64| {
+            let mut counter_block = [0x5a; BLOCK_SIZE];
+            counter_block[..8].copy_from_slice(&(i * 0x0101).to_le_bytes());
+            counter_block
+        };
+
+        let cipher = encrypt_ctr_with(&raw, &CTR_KEY, counter_fn);
+
+        assert_eq!(cipher.len(), raw.len());
+        assert_ne!(cipher, raw);
+        assert_eq!(decrypt_ctr_with(&cipher, &CTR_KEY, counter_fn), raw);
+    }
+
+    #[test]
+    fn ctr_with_nonce_and_counter_function_is_ctr() {
+        let counter_fn = |i: u64| {
+            let mut counter_block = [0u8; BLOCK_SIZE];
+            counter_block[..8].copy_from_slice(&CTR_NONCE);
+            counter_block[8..].copy_from_slice(&(i + ctr::DEFAULT_INITIAL_COUNTER).to_be_bytes());
+            counter_block
+        };
+
+        assert_eq!(encrypt_ctr_with(&RAW_CTR, &CTR_KEY, counter_fn), CIPHERED_CTR.to_vec());
+    }
+
+    #[test]
+    fn ctr32_wraps_low_32_bits_keeping_upper_bytes_fixed() {
+        let mut initial_counter_block = [0xab; BLOCK_SIZE];
+        initial_counter_block[12..].copy_from_slice(&[0xff; 4]);
+        let mut wrapped_counter_block = [0xab; BLOCK_SIZE];
+        wrapped_counter_block[12..].copy_from_slice(&[0x00; 4]);
+
+        let keystream = encrypt_ctr32(&[0x00; 2 * BLOCK_SIZE], &CTR_KEY, &initial_counter_block);
+
+        assert_eq!(keystream[..BLOCK_SIZE], encrypt_block_arr(initial_counter_block, &CTR_KEY)[..]);
+        assert_eq!(keystream[BLOCK_SIZE..], encrypt_block_arr(wrapped_counter_block, &CTR_KEY)[..]);
+    }
+
+    #[test]
+    fn ctr32_from_incremented_pre_counter_block_is_gcm_encryption() {
+        let nonce = [0x42; 12];
+        let raw = [0x61; 40];
+        // inc32(J0), where J0 = nonce || 0x00000001
+        let mut initial_counter_block = [0u8; BLOCK_SIZE];
+        initial_counter_block[..12].copy_from_slice(&nonce);
+        initial_counter_block[BLOCK_SIZE - 1] = 2;
+
+        let (gcm_cipher, _) = gcm::encrypt_gcm_detached(&raw, &CTR_KEY, &nonce, &[]).unwrap();
+
+        assert_eq!(encrypt_ctr32(&raw, &CTR_KEY, &initial_counter_block), gcm_cipher);
+    }
+
+    #[test]
+    fn apply_keystream_of_exact_length_is_ctr() {
+        let mut data = RAW_CTR;
+        let keystream = ctr_keystream(&CTR_KEY, &CTR_NONCE, data.len());
+
+        let result = apply_keystream(&mut data, &keystream);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(data, CIPHERED_CTR);
+    }
+
+    #[test]
+    fn apply_keystream_uses_start_of_longer_keystream() {
+        let mut data = [0x42; 3];
+
+        assert_eq!(apply_keystream(&mut data, &[0x01, 0x02, 0x03, 0x04]), Ok(()));
+        assert_eq!(data, [0x43, 0x40, 0x41]);
+    }
+
+    #[test]
+    fn apply_keystream_rejects_shorter_keystream() {
+        let mut data = [0x42; 3];
+
+        assert_eq!(apply_keystream(&mut data, &[0x01, 0x02]), Err(AesError::KeystreamTooShort));
+        assert_eq!(data, [0x42; 3]);
+    }
+
+    #[test]
+    fn ctr_first_block_uses_counter_1() {
+        let counter_block = [&CTR_NONCE[..], &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01]].concat();
+        let mut expected = [0u8; BLOCK_SIZE];
+        expected.copy_from_slice(&counter_block);
+
+        let keystream = ctr_keystream(&CTR_KEY, &CTR_NONCE, BLOCK_SIZE);
+
+        assert_eq!(keystream, encrypt_block_arr(expected, &CTR_KEY));
+    }
+
+    #[test]
+    fn ctr_with_counter_first_block_uses_given_counter() {
+        let raw = [0x42; 3 * BLOCK_SIZE];
+        let encrypt_from = |initial_counter| {
+            let mode = BlockCipherMode::CTRWithCounter(&CTR_NONCE, initial_counter);
+            encrypt_aes_128(&raw, &CTR_KEY, &AESEncryptionOptions::new(&mode, &Padding::None))
+        };
+        let ctr = encrypt_aes_128(
+            &raw,
+            &CTR_KEY,
+            &AESEncryptionOptions::new(&BlockCipherMode::CTR(&CTR_NONCE), &Padding::None),
+        );
+
+        assert_eq!(encrypt_from(1), ctr);
+        // starting at 0 adds a block before the CTR stream
+        assert_eq!(encrypt_from(0)[BLOCK_SIZE..], ctr[..2 * BLOCK_SIZE]);
+        assert_eq!(encrypt_from(2)[..2 * BLOCK_SIZE], ctr[BLOCK_SIZE..]);
+    }
+
+    #[test]
+    fn decrypts_in_ctr_with_counter_mode() {
+        let mode = BlockCipherMode::CTRWithCounter(&CTR_NONCE, 7);
+        let cipher = encrypt_aes_128(&RAW_CTR, &CTR_KEY, &AESEncryptionOptions::new(&mode, &Padding::None));
+        let mut out = [0u8; BLOCK_SIZE];
+
+        let n = decrypt_into(&cipher, &CTR_KEY, &mode, &Padding::None, &mut out);
+
+        assert_eq!(n, Ok(BLOCK_SIZE));
+        assert_eq!(out, RAW_CTR);
+        assert_eq!(mode.name(), "CTR");
+    }
+
+    #[test]
+    fn ctr_uses_first_keystream_byte_of_last_partial_block() {
+        let raw = [0x42; BLOCK_SIZE + 1];
+        let keystream = ctr_keystream(&CTR_KEY, &CTR_NONCE, 2 * BLOCK_SIZE);
+
+        let cipher = encrypt_aes_128(
+            &raw,
+            &CTR_KEY,
+            &AESEncryptionOptions::new(&BlockCipherMode::CTR(&CTR_NONCE), &Padding::None),
+        );
+
+        assert_eq!(cipher.len(), BLOCK_SIZE + 1);
+        assert_eq!(cipher[BLOCK_SIZE], 0x42 ^ keystream[BLOCK_SIZE]);
+        assert_eq!(cipher, xor::xor_bytes(&raw, &keystream[..BLOCK_SIZE + 1]));
+    }
+
+    #[test]
+    fn encrypt_cbc_deterministic_is_deterministic() {
+        let raw = b"Some plaintext to deduplicate";
+
+        let first = encrypt_cbc_deterministic(raw, &CBC_KEY, &ECB_KEY);
+        let second = encrypt_cbc_deterministic(raw, &CBC_KEY, &ECB_KEY);
+        let other = encrypt_cbc_deterministic(b"Some plaintext to deduplicatf", &CBC_KEY, &ECB_KEY);
+
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        assert_ne!(first[..BLOCK_SIZE], other[..BLOCK_SIZE]);
+    }
+
+    #[test]
+    fn encrypt_cbc_deterministic_prepends_cmac_iv() {
+        let raw = b"Some plaintext to deduplicate";
+
+        let iv_and_cipher = encrypt_cbc_deterministic(raw, &CBC_KEY, &ECB_KEY);
+
+        assert_eq!(iv_and_cipher.len(), 3 * BLOCK_SIZE);
+        assert_eq!(iv_and_cipher[..BLOCK_SIZE], cmac::cmac(&ECB_KEY, raw).to_bytes());
+    }
+
+    #[test]
+    fn decrypts_cbc_deterministic() {
+        let raw = b"Some plaintext to deduplicate";
+        let iv_and_cipher = encrypt_cbc_deterministic(raw, &CBC_KEY, &ECB_KEY);
+
+        let actual_raw = decrypt_cbc_deterministic(&iv_and_cipher, &CBC_KEY, &ECB_KEY);
+
+        assert_eq!(actual_raw, Ok(raw.to_vec()));
+    }
+
+    #[test]
+    fn decrypt_cbc_deterministic_rejects_tampered_cipher() {
+        let mut iv_and_cipher = encrypt_cbc_deterministic(&[0x42; 40], &CBC_KEY, &ECB_KEY);
+        iv_and_cipher[BLOCK_SIZE] ^= 1;
+
+        let actual_raw = decrypt_cbc_deterministic(&iv_and_cipher, &CBC_KEY, &ECB_KEY);
+
+        assert!(matches!(actual_raw, Err(AesError::AuthenticationFailed(_))));
+    }
+
+    #[test]
+    fn decrypts_in_ecb_mode() {
+        let actual_raw = decrypt_aes_128(
+            &CIPHERED_ECB,
+            &ECB_KEY,
+            &BlockCipherMode::ECB,
+        );
+
+        assert_eq!(actual_raw, RAW_ECB);
+    }
+
+    #[test]
+    fn encrypts_in_cbc_mode() {
+        let actual_cipher = encrypt_aes_128(
+            &RAW_CBC,
+            &CBC_KEY,
+            &AESEncryptionOptions::new(
+                &BlockCipherMode::CBC(&CBC_IV),
+                &Padding::None,
+            ),
+        );
+
+        assert_eq!(actual_cipher, CIPHERED_CBC);
+    }
+
+    #[test]
+    fn decrypts_in_cbc_mode() {
+        let actual_raw = decrypt_aes_128(
+            &CIPHERED_CBC,
+            &CBC_KEY,
+            &BlockCipherMode::CBC(&CBC_IV),
+        );
+
+        assert_eq!(actual_raw, RAW_CBC);
+    }
+
+    #[test]
+    fn encrypts_in_ctr_mode() {
+        let actual_cipher = encrypt_aes_128(
+            &RAW_CTR,
+            &CTR_KEY,
+            &AESEncryptionOptions::new(
+                &BlockCipherMode::CTR(&CTR_NONCE),
+                &Padding::None,
+            ),
+        );
+
+        assert_eq!(actual_cipher, CIPHERED_CTR);
+    }
+
+    #[cfg(feature = "rayon")]
+    mod parallel_cbc {
+        use super::*;
+
+        /// SP 800-38A F.2.1/F.2.2, where each block chains on the previous ciphertext block
+        const RAW_CBC_MULTI_BLOCK: [u8; 64] = [
+            0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96,
+            0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93, 0x17, 0x2a,
+            0xae, 0x2d, 0x8a, 0x57, 0x1e, 0x03, 0xac, 0x9c,
+            0x9e, 0xb7, 0x6f, 0xac, 0x45, 0xaf, 0x8e, 0x51,
+            0x30, 0xc8, 0x1c, 0x46, 0xa3, 0x5c, 0xe4, 0x11,
+            0xe5, 0xfb, 0xc1, 0x19, 0x1a, 0x0a, 0x52, 0xef,
+            0xf6, 0x9f, 0x24, 0x45, 0xdf, 0x4f, 0x9b, 0x17,
+            0xad, 0x2b, 0x41, 0x7b, 0xe6, 0x6c, 0x37, 0x10
+        ];
+        const CIPHERED_CBC_MULTI_BLOCK: [u8; 64] = [
+            0x76, 0x49, 0xab, 0xac, 0x81, 0x19, 0xb2, 0x46,
+            0xce, 0xe9, 0x8e, 0x9b, 0x12, 0xe9, 0x19, 0x7d,
+            0x50, 0x86, 0xcb, 0x9b, 0x50, 0x72, 0x19, 0xee,
+            0x95, 0xdb, 0x11, 0x3a, 0x91, 0x76, 0x78, 0xb2,
+            0x73, 0xbe, 0xd6, 0xb8, 0xe3, 0xc1, 0x74, 0x3b,
+            0x71, 0x16, 0xe6, 0x9e, 0x22, 0x22, 0x95, 0x16,
+            0x3f, 0xf1, 0xca, 0xa1, 0x68, 0x1f, 0xac, 0x09,
+            0x12, 0x0e, 0xca, 0x30, 0x75, 0x86, 0xe1, 0xa7
+        ];
+
+        #[test]
+        fn decrypts_cbc_in_parallel() {
+            let actual_raw = decrypt_cbc_parallel(&CIPHERED_CBC_MULTI_BLOCK, &CBC_KEY, &CBC_IV);
+
+            assert_eq!(actual_raw[..], RAW_CBC_MULTI_BLOCK[..]);
+        }
+
+        #[test]
+        fn parallel_cbc_decryption_matches_sequential_decryption() {
+            let raw: Vec<u8> = (0..10_000).map(|i| (i * 7 % 251) as u8).collect();
+            let cipher = encrypt_aes_128(
+                &raw,
+                &CBC_KEY,
+                &AESEncryptionOptions::new(&BlockCipherMode::CBC(&CBC_IV), &Padding::PKCS7),
+            );
+
+            let parallel = decrypt_cbc_parallel(&cipher, &CBC_KEY, &CBC_IV);
+            let sequential = Aes128::new(&CBC_KEY).decrypt_bytes(&cipher, &BlockCipherMode::CBC(&CBC_IV));
+
+            assert_eq!(parallel, sequential);
+            // long enough to be decrypted in parallel
+            assert_eq!(decrypt_aes_128(&cipher, &CBC_KEY, &BlockCipherMode::CBC(&CBC_IV)), sequential);
+        }
+
+        #[test]
+        fn chunks_of_parallel_cbc_decryption_chain_from_preceding_cipher_block() {
+            let aes = Aes128::new(&CBC_KEY);
+
+            // chunks of 1 and 3 blocks of the 4 blocks, the last chunk being incomplete
+            for blocks_per_chunk in [1, 3].iter() {
+                let actual_raw =
+                    decrypt_cbc_in_chunks(&CIPHERED_CBC_MULTI_BLOCK, &aes, &CBC_IV, *blocks_per_chunk);
+
+                assert_eq!(actual_raw[..], RAW_CBC_MULTI_BLOCK[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn decrypts_in_ctr_mode() {
+        // CTR decryption uses the dencryption process
+        let actual_raw = encrypt_aes_128(
+            &CIPHERED_CTR,
+            &CTR_KEY,
+            &AESEncryptionOptions::new(
+                &BlockCipherMode::CTR(&CTR_NONCE),
+                &Padding::None,
+            ),
+        );
+
+        assert_eq!(actual_raw, RAW_CTR);
+    }
+
+    /// Reproduce with:
+    /// openssl enc -aes-128-ctr -K 2b7e151628aed2a6abf7158809cf4f3c \
+    ///     -iv ffffffffffffffff0000000000000001 -in plaintext.bin
+    #[test]
+    fn encrypts_in_ctr_mode_like_openssl() {
+        let raw: [u8; 48] = [
+            0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96,
+            0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93, 0x17, 0x2a,
+            0xae, 0x2d, 0x8a, 0x57, 0x1e, 0x03, 0xac, 0x9c,
+            0x9e, 0xb7, 0x6f, 0xac, 0x45, 0xaf, 0x8e, 0x51,
+            0x30, 0xc8, 0x1c, 0x46, 0xa3, 0x5c, 0xe4, 0x11,
+            0xe5, 0xfb, 0xc1, 0x19, 0x1a, 0x0a, 0x52, 0xef
+        ];
+        let openssl_cipher: [u8; 48] = [
+            0x7c, 0x55, 0x95, 0x50, 0x5e, 0x4f, 0x82, 0x14,
+            0x23, 0xaa, 0x6b, 0x68, 0xc8, 0x5b, 0xf7, 0xe0,
+            0xea, 0x19, 0x2c, 0x47, 0x6e, 0x36, 0xc7, 0xef,
+            0x7b, 0x92, 0xf3, 0x72, 0x0e, 0xe3, 0x82, 0xbb,
+            0xfc, 0x47, 0x10, 0x56, 0x02, 0x69, 0xcd, 0x02,
+            0xf6, 0xa3, 0xfb, 0xc1, 0x8e, 0xd2, 0xed, 0x8f
+        ];
+
+        let actual_cipher = encrypt_aes_128(
+            &raw,
+            &CTR_KEY,
+            &AESEncryptionOptions::new(
+                &BlockCipherMode::CTR(&CTR_NONCE),
+                &Padding::None,
             ),
         );
 
-        assert_eq!(actual_cipher, CIPHERED_ECB);
+        assert_eq!(actual_cipher, openssl_cipher.to_vec());
     }
 
+    /// Reproduce with:
+    /// head -c 4112 /dev/zero | openssl enc -aes-128-ctr -K 2b7e151628aed2a6abf7158809cf4f3c \
+    ///     -iv ffffffffffffffff0000000000000001 | tail -c 32
     #[test]
-    fn decrypts_in_ecb_mode() {
-        let actual_raw = decrypt_aes_128(
-            &CIPHERED_ECB,
-            &ECB_KEY,
-            &BlockCipherMode::ECB,
+    fn encrypts_in_ctr_mode_like_openssl_past_255_blocks() {
+        let raw = [0u8; 257 * BLOCK_SIZE];
+        let openssl_cipher_end: [u8; 32] = [
+            0xc8, 0xc4, 0x7f, 0x54, 0x18, 0xe8, 0xe8, 0x25,
+            0xf9, 0xe7, 0x57, 0x73, 0x26, 0xcd, 0xf6, 0x27,
+            0xaf, 0x6c, 0x16, 0x6e, 0x98, 0x04, 0x65, 0xfe,
+            0xdd, 0x01, 0x57, 0xc3, 0xdd, 0x71, 0x4a, 0x30
+        ];
+
+        let actual_cipher = encrypt_aes_128(
+            &raw,
+            &CTR_KEY,
+            &AESEncryptionOptions::new(
+                &BlockCipherMode::CTR(&CTR_NONCE),
+                &Padding::None,
+            ),
         );
 
-        assert_eq!(actual_raw, RAW_ECB);
+        assert_eq!(actual_cipher[actual_cipher.len() - 32..], openssl_cipher_end);
     }
 
     #[test]
-    fn encrypts_in_cbc_mode() {
-        let actual_cipher = encrypt_aes_128(
-            &RAW_CBC,
+    #[should_panic(expected = "Cannot decrypt using CTR block cipher mode. Use encryption instead.")]
+    fn decryption_in_ctr_mode_should_panic() {
+        decrypt_aes_128(
+            &CIPHERED_CTR,
+            &CTR_KEY,
+            &BlockCipherMode::CTR(&CTR_NONCE),
+        );
+    }
+
+    #[test]
+    fn encrypt_with_mode_reports_mode_iv_and_padding() {
+        let result = encrypt_with_mode(
+            &RAW_CBC[..12],
             &CBC_KEY,
             &AESEncryptionOptions::new(
                 &BlockCipherMode::CBC(&CBC_IV),
-                &Padding::None,
+                &Padding::PKCS7,
             ),
-        );
+        ).unwrap();
 
-        assert_eq!(actual_cipher, CIPHERED_CBC);
+        assert_eq!(result.ciphertext.len(), BLOCK_SIZE);
+        assert_eq!(result.mode, "CBC");
+        assert_eq!(result.iv_used, Some(CBC_IV));
+        assert_eq!(result.padding_added, 4);
     }
 
     #[test]
-    fn decrypts_in_cbc_mode() {
+    fn encrypt_with_mode_reports_no_iv_for_ecb() {
+        let result = encrypt_with_mode(
+            &RAW_ECB,
+            &ECB_KEY,
+            &AESEncryptionOptions::default(),
+        ).unwrap();
+
+        assert_eq!(result.ciphertext, CIPHERED_ECB);
+        assert_eq!(result.mode, "ECB");
+        assert_eq!(result.iv_used, None);
+        assert_eq!(result.padding_added, 0);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn encrypt_cbc_with_random_iv_reports_iv_that_decrypts() {
+        let result = encrypt_cbc_with_random_iv(&RAW_CBC, &CBC_KEY, &Padding::None).unwrap();
+        let iv = result.iv_used.unwrap();
+
         let actual_raw = decrypt_aes_128(
-            &CIPHERED_CBC,
+            &result.ciphertext,
             &CBC_KEY,
-            &BlockCipherMode::CBC(&CBC_IV),
+            &BlockCipherMode::CBC(&iv),
         );
 
         assert_eq!(actual_raw, RAW_CBC);
     }
 
     #[test]
-    fn encrypts_in_ctr_mode() {
-        let actual_cipher = encrypt_aes_128(
-            &RAW_CTR,
-            &CTR_KEY,
-            &AESEncryptionOptions::new(
-                &BlockCipherMode::CTR(&CTR_NONCE),
-                &Padding::None,
-            ),
+    #[cfg(feature = "rand")]
+    fn cbc_with_managed_iv_and_padding_round_trips() {
+        let raw = b"Not block aligned";
+
+        let iv_and_cipher = encrypt_cbc(raw, &CBC_KEY);
+
+        assert_eq!(iv_and_cipher.len(), 3 * BLOCK_SIZE);
+        assert_eq!(decrypt_cbc(&iv_and_cipher, &CBC_KEY), Ok(raw.to_vec()));
+        assert_eq!(decrypt_cbc(&encrypt_cbc(&[], &CBC_KEY), &CBC_KEY), Ok(vec![]));
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn cbc_with_managed_iv_encrypts_same_plaintext_differently() {
+        let first = encrypt_cbc(&RAW_CBC, &CBC_KEY);
+        let second = encrypt_cbc(&RAW_CBC, &CBC_KEY);
+
+        assert_ne!(first[..BLOCK_SIZE], second[..BLOCK_SIZE]);
+        assert_ne!(first[BLOCK_SIZE..], second[BLOCK_SIZE..]);
+    }
+
+    #[test]
+    fn iv_from_counter_is_encrypted_counter_block() {
+        let mut counter_block = [0u8; BLOCK_SIZE];
+        counter_block[BLOCK_SIZE - 1] = 0x01;
+
+        let iv = cbc_iv_from_counter(&ECB_KEY, 1);
+
+        assert_eq!(iv.to_bytes().to_vec(), encrypt_aes_128(&counter_block, &ECB_KEY, &AESEncryptionOptions::default()));
+    }
+
+    #[test]
+    fn iv_from_counter_only_repeats_with_counter() {
+        assert_eq!(cbc_iv_from_counter(&CBC_KEY, 42), cbc_iv_from_counter(&CBC_KEY, 42));
+        assert_ne!(cbc_iv_from_counter(&CBC_KEY, 42), cbc_iv_from_counter(&CBC_KEY, 43));
+        assert_ne!(cbc_iv_from_counter(&CBC_KEY, 0), cbc_iv_from_counter(&CBC_KEY, u64::MAX));
+        assert_ne!(cbc_iv_from_counter(&CBC_KEY, 42), cbc_iv_from_counter(&ECB_KEY, 42));
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn extracts_iv_that_decryption_uses() {
+        let iv_and_cipher = encrypt_cbc(&RAW_CBC, &CBC_KEY);
+
+        let iv = extract_iv(&iv_and_cipher).unwrap();
+
+        let padded_raw = decrypt_aes_128(&iv_and_cipher[BLOCK_SIZE..], &CBC_KEY, &BlockCipherMode::CBC(&iv));
+        assert_eq!(padded_raw[..BLOCK_SIZE], RAW_CBC);
+        assert_eq!(iv.to_bytes()[..], iv_and_cipher[..BLOCK_SIZE]);
+    }
+
+    #[test]
+    fn extract_iv_rejects_blob_shorter_than_iv() {
+        assert_eq!(extract_iv(&[0x00; BLOCK_SIZE - 1]), Err(AesError::InvalidInputLength));
+        assert_eq!(extract_iv(&[]), Err(AesError::InvalidInputLength));
+        assert_eq!(extract_iv(&[0x42; BLOCK_SIZE]), Ok(Iv::from_bytes(&[0x42; BLOCK_SIZE])));
+    }
+
+    #[test]
+    fn decrypt_cbc_rejects_input_without_iv_and_block() {
+        assert_eq!(decrypt_cbc(&[0x00; BLOCK_SIZE], &CBC_KEY), Err(AesError::InvalidInputLength));
+        assert_eq!(decrypt_cbc(&[0x00; 40], &CBC_KEY), Err(AesError::InvalidInputLength));
+    }
+
+    #[test]
+    fn decrypts_borrowed_slice_into_preallocated_buffer() {
+        // a fixed-size array standing in for a memory-mapped file, which isn't Vec-backed
+        let mut mapped = [0u8; 64];
+        mapped[..16].copy_from_slice(&CIPHERED_CBC);
+        let mut out = [0u8; 32];
+
+        let written = decrypt_slice_into(&mapped[..16], &CBC_KEY, &BlockCipherMode::CBC(&CBC_IV), &mut out);
+
+        assert_eq!(written, Ok(16));
+        assert_eq!(out[..16], RAW_CBC);
+        assert_eq!(out[16..], [0u8; 16]);
+    }
+
+    #[test]
+    fn decrypts_ctr_slice_with_incomplete_last_block() {
+        let raw: Vec<u8> = (0..133).map(|i| i as u8).collect();
+        // the counter wraps around after the first block
+        let mode = BlockCipherMode::CTRWithCounter(&CTR_NONCE, u64::MAX);
+        let cipher = encrypt_aes_128(&raw, &CTR_KEY, &AESEncryptionOptions::new(&mode, &Padding::None));
+        let mut out = vec![0u8; cipher.len()];
+
+        assert_eq!(decrypt_slice_into(&cipher, &CTR_KEY, &mode, &mut out), Ok(raw.len()));
+        assert_eq!(out, raw);
+    }
+
+    #[test]
+    fn decrypt_slice_into_rejects_short_buffer_and_incomplete_block() {
+        let mut out = [0u8; 15];
+
+        assert_eq!(
+            decrypt_slice_into(&CIPHERED_ECB, &ECB_KEY, &BlockCipherMode::ECB, &mut out),
+            Err(AesError::BufferTooSmall)
         );
+        assert_eq!(
+            decrypt_slice_into(&CIPHERED_ECB[..15], &ECB_KEY, &BlockCipherMode::ECB, &mut out),
+            Err(AesError::InvalidInputLength)
+        );
+    }
 
-        assert_eq!(actual_cipher, CIPHERED_CTR);
+    #[test]
+    fn decrypt_into_returns_length_without_padding() {
+        let raw = b"Not block aligned";
+        let cipher = encrypt_aes_128(
+            raw,
+            &CBC_KEY,
+            &AESEncryptionOptions::new(&BlockCipherMode::CBC(&CBC_IV), &Padding::PKCS7),
+        );
+        let mut out = [0u8; 64];
+
+        let n = decrypt_into(&cipher, &CBC_KEY, &BlockCipherMode::CBC(&CBC_IV), &Padding::PKCS7, &mut out);
+
+        assert_eq!(n, Ok(raw.len()));
+        assert_eq!(&out[..raw.len()], &raw[..]);
     }
 
     #[test]
-    fn decrypts_in_ctr_mode() {
-        // CTR decryption uses the dencryption process
-        let actual_raw = encrypt_aes_128(
-            &CIPHERED_CTR,
-            &CTR_KEY,
-            &AESEncryptionOptions::new(
-                &BlockCipherMode::CTR(&CTR_NONCE),
-                &Padding::None,
-            ),
+    fn decrypt_into_removes_full_padding_block() {
+        let raw = &[0x42; 32];
+        let cipher = encrypt_aes_128(
+            raw,
+            &ECB_KEY,
+            &AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::PKCS7),
         );
+        let mut out = [0u8; 48];
 
-        assert_eq!(actual_raw, RAW_CTR);
+        let n = decrypt_into(&cipher, &ECB_KEY, &BlockCipherMode::ECB, &Padding::PKCS7, &mut out);
+
+        assert_eq!(cipher.len(), 48);
+        assert_eq!(n, Ok(32));
+        assert_eq!(&out[..32], &raw[..]);
     }
 
     #[test]
-    #[should_panic(expected = "Cannot decrypt using CTR block cipher mode. Use encryption instead.")]
-    fn decryption_in_ctr_mode_should_panic() {
-        decrypt_aes_128(
-            &CIPHERED_CTR,
-            &CTR_KEY,
-            &BlockCipherMode::CTR(&CTR_NONCE),
+    fn decrypt_into_decrypts_without_padding() {
+        let mut out = [0u8; 64];
+
+        let n = decrypt_into(&CIPHERED_CBC, &CBC_KEY, &BlockCipherMode::CBC(&CBC_IV), &Padding::None, &mut out);
+
+        let expected = decrypt_aes_128(&CIPHERED_CBC, &CBC_KEY, &BlockCipherMode::CBC(&CBC_IV));
+        assert_eq!(n, Ok(CIPHERED_CBC.len()));
+        assert_eq!(&out[..CIPHERED_CBC.len()], &expected[..]);
+    }
+
+    #[test]
+    fn decrypt_into_errors_when_out_is_too_small() {
+        let mut out = [0u8; 15];
+
+        let n = decrypt_into(&CIPHERED_ECB, &ECB_KEY, &BlockCipherMode::ECB, &Padding::None, &mut out);
+
+        assert_eq!(n, Err(AesError::BufferTooSmall));
+    }
+
+    #[test]
+    fn decrypt_into_errors_on_invalid_padding() {
+        let mut out = [0u8; 16];
+
+        let n = decrypt_into(&CIPHERED_ECB, &ECB_KEY, &BlockCipherMode::ECB, &Padding::PKCS7, &mut out);
+
+        assert_eq!(n, Err(AesError::InvalidPadding));
+    }
+
+    #[test]
+    fn checks_padding_of_valid_final_block() {
+        let options = AESEncryptionOptions::new(&BlockCipherMode::CBC(&CBC_IV), &Padding::PKCS7);
+        let cipher = encrypt_aes_128(b"attack at dawn, over two blocks", &CBC_KEY, &options);
+
+        let (deciphered, is_valid) = decrypt_cbc_check_padding(&cipher, &CBC_KEY, &CBC_IV);
+
+        assert!(is_valid);
+        assert_eq!(deciphered, pad::pkcs7_pad_aes(b"attack at dawn, over two blocks"));
+    }
+
+    #[test]
+    fn checks_padding_of_invalid_final_block() {
+        let options = AESEncryptionOptions::new(&BlockCipherMode::CBC(&CBC_IV), &Padding::PKCS7);
+        let mut cipher = encrypt_aes_128(b"attack at dawn, over two blocks", &CBC_KEY, &options);
+        // flips the padding byte of the last plaintext block, from 01 to 00
+        cipher[BLOCK_SIZE - 1] ^= 0x01;
+
+        let (deciphered, is_valid) = decrypt_cbc_check_padding(&cipher, &CBC_KEY, &CBC_IV);
+
+        assert!(!is_valid);
+        assert_eq!(deciphered.len(), 2 * BLOCK_SIZE);
+        assert_eq!(deciphered[2 * BLOCK_SIZE - 1], 0x00);
+        assert_eq!(decrypt_cbc_check_padding(&[], &CBC_KEY, &CBC_IV), (vec![], false));
+    }
+
+    #[test]
+    fn row_major_byte_order_transposes_input_and_output_blocks() {
+        let mut transposed_raw = RAW_ECB;
+        transpose_block(&mut transposed_raw);
+        let mut transposed_cipher = CIPHERED_ECB;
+        transpose_block(&mut transposed_cipher);
+        let options = AESEncryptionOptions::default();
+
+        let cipher = encrypt_with_byte_order(&transposed_raw, &ECB_KEY, &options, ByteOrder::RowMajor);
+
+        assert_eq!(transposed_raw[..4], [0x00, 0x44, 0x88, 0xcc]);
+        assert_eq!(cipher, Ok(transposed_cipher.to_vec()));
+        assert_eq!(
+            decrypt_with_byte_order(&transposed_cipher, &ECB_KEY, &BlockCipherMode::ECB, ByteOrder::RowMajor),
+            Ok(transposed_raw.to_vec())
+        );
+    }
+
+    #[test]
+    fn decrypting_with_byte_order_refuses_incomplete_blocks_outside_of_ctr() {
+        let nonce = [0xf0; 8];
+        let cipher = [0x42; BLOCK_SIZE + 1];
+
+        for byte_order in [ByteOrder::ColumnMajor, ByteOrder::RowMajor].iter() {
+            let ecb = decrypt_with_byte_order(&cipher, &ECB_KEY, &BlockCipherMode::ECB, *byte_order);
+            let ctr = decrypt_with_byte_order(&cipher, &ECB_KEY, &BlockCipherMode::CTR(&nonce), *byte_order);
+
+            assert_eq!(ecb, Err(AesError::InvalidInputLength));
+            assert_eq!(ctr.map(|raw| raw.len()), Ok(BLOCK_SIZE + 1));
+        }
+    }
+
+    #[test]
+    fn column_major_byte_order_is_standard_aes() {
+        let options = AESEncryptionOptions::default();
+
+        let cipher = encrypt_with_byte_order(&RAW_ECB, &ECB_KEY, &options, ByteOrder::ColumnMajor);
+
+        assert_eq!(cipher, Ok(CIPHERED_ECB.to_vec()));
+    }
+
+    #[test]
+    fn row_major_byte_order_round_trips_in_every_mode() {
+        let raw: Vec<u8> = (0..3 * BLOCK_SIZE + 5).map(|i| i as u8).collect();
+        let nonce = [0xf0; 8];
+        let modes = [BlockCipherMode::ECB, BlockCipherMode::CBC(&CBC_IV), BlockCipherMode::CTR(&nonce)];
+
+        for mode in modes.iter() {
+            let options = AESEncryptionOptions::new(mode, &Padding::PKCS7);
+
+            let cipher = encrypt_with_byte_order(&raw, &CBC_KEY, &options, ByteOrder::RowMajor).unwrap();
+            let padded = decrypt_with_byte_order(&cipher, &CBC_KEY, mode, ByteOrder::RowMajor).unwrap();
+            let deciphered = match mode {
+                // CTR ignores padding
+                BlockCipherMode::CTR(_) => padded,
+                _ => pad::pkcs7_unpad_aes(&padded).unwrap(),
+            };
+
+            assert_ne!(cipher, encrypt_aes_128(&raw, &CBC_KEY, &options));
+            assert_eq!(deciphered, raw);
+        }
+    }
+
+    #[test]
+    fn backends_produce_same_ciphertext() {
+        let raw = vec![0x42; 3 * BLOCK_SIZE + 7];
+        let options = AESEncryptionOptions::new(&BlockCipherMode::CBC(&CBC_IV), &Padding::PKCS7);
+
+        let table = encrypt_with_backend(&raw, &CBC_KEY, &options, CipherBackend::Table);
+        let constant_time = encrypt_with_backend(&raw, &CBC_KEY, &options, CipherBackend::ConstantTime);
+        let scatter_gather = encrypt_with_backend(&raw, &CBC_KEY, &options, CipherBackend::ScatterGather);
+
+        assert_eq!(table, Ok(encrypt_aes_128(&raw, &CBC_KEY, &options)));
+        assert_eq!(constant_time, table);
+        assert_eq!(scatter_gather, table);
+    }
+
+    #[test]
+    fn reports_increasing_progress_ending_at_cipher_length() {
+        let raw = vec![0x42; 3 * cipher::PROGRESS_INTERVAL + 100];
+        let options = AESEncryptionOptions::new(&BlockCipherMode::CBC(&CBC_IV), &Padding::PKCS7);
+        let mut totals = vec![];
+
+        let cipher = encrypt_with_progress(&raw, &CBC_KEY, &options, |total| totals.push(total)).unwrap();
+
+        assert_eq!(totals.len(), 4);
+        assert!(totals.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(totals[3], cipher.len());
+        assert_eq!(cipher, encrypt_aes_128(&raw, &CBC_KEY, &options));
+    }
+
+    #[test]
+    fn encrypts_ctr_with_progress_like_without() {
+        let raw = vec![0x42; 2 * cipher::PROGRESS_INTERVAL + 5];
+        let options = AESEncryptionOptions::new(&BlockCipherMode::CTR(&CTR_NONCE), &Padding::None);
+        let mut calls = 0;
+
+        let cipher = encrypt_with_progress(&raw, &CTR_KEY, &options, |_| calls += 1).unwrap();
+
+        assert_eq!(calls, 3);
+        assert_eq!(cipher, encrypt_aes_128(&raw, &CTR_KEY, &options));
+    }
+
+    #[test]
+    fn reports_completion_of_empty_cipher() {
+        let mut totals = vec![];
+
+        let cipher = encrypt_with_progress(&[], &ECB_KEY, &AESEncryptionOptions::default(), |total| {
+            totals.push(total)
+        });
+
+        assert_eq!(cipher, Ok(vec![]));
+        assert_eq!(totals, vec![0]);
+    }
+
+    #[test]
+    fn round_trips_through_base64_in_cbc_with_pkcs7() {
+        let raw = b"attack at dawn, bring snacks";
+        let mode = BlockCipherMode::CBC(&CBC_IV);
+        let options = AESEncryptionOptions::new(&mode, &Padding::PKCS7);
+
+        let encoded = encrypt_to_base64(raw, &CBC_KEY, &options).unwrap();
+        let deciphered = decrypt_from_base64(&encoded, &CBC_KEY, &mode).unwrap();
+
+        assert_eq!(encoded.len(), 44);
+        assert_eq!(base64::decode(&encoded), Ok(encrypt_aes_128(raw, &CBC_KEY, &options)));
+        assert_eq!(pad::unpad_aes(&deciphered, &Padding::PKCS7), Ok(raw.to_vec()));
+    }
+
+    #[test]
+    fn encrypt_to_base64_rejects_incomplete_blocks_without_padding() {
+        let result = encrypt_to_base64(&[0x42; 20], &ECB_KEY, &AESEncryptionOptions::default());
+
+        assert_eq!(result, Err(AesError::InvalidInputLength));
+    }
+
+    #[test]
+    fn decrypt_from_base64_rejects_malformed_base64() {
+        let result = decrypt_from_base64("not base64!", &ECB_KEY, &BlockCipherMode::ECB);
+
+        assert_eq!(result, Err(AesError::InvalidBase64));
+    }
+
+    #[test]
+    fn decrypts_from_base64_in_ctr_and_rejects_unaligned_block_cipher() {
+        let mode = BlockCipherMode::CTR(&CTR_NONCE);
+        let options = AESEncryptionOptions::new(&mode, &Padding::None);
+        let encoded = encrypt_to_base64(&[0x42; 20], &CTR_KEY, &options).unwrap();
+
+        assert_eq!(decrypt_from_base64(&encoded, &CTR_KEY, &mode), Ok(vec![0x42; 20]));
+        assert_eq!(decrypt_from_base64(&encoded, &CTR_KEY, &BlockCipherMode::ECB), Err(AesError::InvalidInputLength));
+    }
+
+    #[test]
+    fn encrypts_and_decrypts_empty_input_in_every_mode_and_padding() {
+        let modes = [BlockCipherMode::ECB, BlockCipherMode::CBC(&CBC_IV), BlockCipherMode::CTR(&CTR_NONCE)];
+        let paddings = [
+            Padding::None,
+            Padding::Prepadded,
+            Padding::PKCS7,
+            #[cfg(feature = "rand")]
+            Padding::Iso10126,
+        ];
+
+        for mode in modes.iter() {
+            for padding in paddings.iter() {
+                // CTR ignores padding
+                let adds_padding_block = !matches!(mode, BlockCipherMode::CTR(_))
+                    && !matches!(padding, Padding::None | Padding::Prepadded);
+                let options = AESEncryptionOptions::new(mode, padding);
+
+                let cipher = try_encrypt_aes_128(&[], &CBC_KEY, &options).unwrap();
+                let mut out = [0u8; BLOCK_SIZE];
+                let n = decrypt_into(&cipher, &CBC_KEY, mode, padding, &mut out);
+
+                let expected_length = if adds_padding_block { BLOCK_SIZE } else { 0 };
+                assert_eq!(cipher.len(), expected_length, "{} with {:?}", mode.name(), padding);
+                assert_eq!(n, Ok(0), "{} with {:?}", mode.name(), padding);
+            }
+        }
+    }
+
+    #[test]
+    fn decrypts_empty_cipher() {
+        let mut out = [];
+
+        assert!(decrypt_aes_128(&[], &CBC_KEY, &BlockCipherMode::CBC(&CBC_IV)).is_empty());
+        assert_eq!(
+            decrypt_into(&[], &CBC_KEY, &BlockCipherMode::ECB, &Padding::None, &mut out),
+            Ok(0),
+        );
+        // a PKCS7 padded cipher holds at least one block of padding
+        assert_eq!(
+            decrypt_into(&[], &CBC_KEY, &BlockCipherMode::ECB, &Padding::PKCS7, &mut out),
+            Err(AesError::InvalidPadding),
         );
     }
 
@@ -367,10 +2324,129 @@ mod tests {
             0x28, 0x29, 0x30, 0x31
         ];
         let expected_parts = vec![
-            &bytes[..16],
-            &bytes[16..]
+            &bytes[..BLOCK_SIZE],
+            &bytes[BLOCK_SIZE..]
         ];
 
-        assert_eq!(bytes_to_parts(&bytes.to_vec()), expected_parts);
+        assert_eq!(bytes_to_parts(bytes.as_ref()), expected_parts);
+    }
+
+    #[test]
+    fn reencrypts_cbc_cipher_under_new_key_and_iv() {
+        let new_key = Key([0x5a; 16]);
+        let new_iv = Iv::from_bytes(&[0xa5; BLOCK_SIZE]);
+        let old_mode = BlockCipherMode::CBC(&CBC_IV);
+        let new_mode = BlockCipherMode::CBC(&new_iv);
+        let old_options = AESEncryptionOptions::new(&old_mode, &Padding::PKCS7);
+        let new_options = AESEncryptionOptions::new(&new_mode, &Padding::PKCS7);
+        let raw = b"stored under the old key";
+        let cipher = encrypt_aes_128(raw, &CBC_KEY, &old_options);
+
+        let rotated = reencrypt(&cipher, &CBC_KEY, &old_options, &new_key, &new_options).unwrap();
+
+        assert_eq!(rotated, encrypt_aes_128(raw, &new_key, &new_options));
+        let mut out = vec![0u8; rotated.len()];
+        let length = decrypt_into(&rotated, &new_key, &new_mode, &Padding::PKCS7, &mut out).unwrap();
+        assert_eq!(&out[..length], raw);
+    }
+
+    #[test]
+    fn reencrypt_can_change_mode_and_padding() {
+        let old_options = AESEncryptionOptions::new(&BlockCipherMode::CBC(&CBC_IV), &Padding::None);
+        let new_mode = BlockCipherMode::CTR(&CTR_NONCE);
+        let new_options = AESEncryptionOptions::new(&new_mode, &Padding::None);
+
+        let rotated = reencrypt(&CIPHERED_CBC, &CBC_KEY, &old_options, &CTR_KEY, &new_options);
+
+        assert_eq!(rotated, Ok(encrypt_aes_128(&RAW_CBC, &CTR_KEY, &new_options)));
+    }
+
+    #[test]
+    fn reencrypt_fails_on_invalid_old_padding() {
+        let old_options = AESEncryptionOptions::new(&BlockCipherMode::CBC(&CBC_IV), &Padding::PKCS7);
+        let cipher = encrypt_aes_128(&[0x00; BLOCK_SIZE], &CBC_KEY, &AESEncryptionOptions::new(&BlockCipherMode::CBC(&CBC_IV), &Padding::None));
+
+        assert_eq!(
+            reencrypt(&cipher, &CBC_KEY, &old_options, &CTR_KEY, &AESEncryptionOptions::default()),
+            Err(AesError::InvalidPadding)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn reencrypt_cbc_rotates_key_and_regenerates_iv() {
+        let new_key = Key([0x5a; 16]);
+        let iv_and_cipher = encrypt_cbc(b"stored under the old key", &CBC_KEY);
+
+        let rotated = reencrypt_cbc(&iv_and_cipher, &CBC_KEY, &new_key).unwrap();
+
+        assert_ne!(rotated[..BLOCK_SIZE], iv_and_cipher[..BLOCK_SIZE]);
+        assert_eq!(decrypt_cbc(&rotated, &new_key), Ok(b"stored under the old key".to_vec()));
+    }
+
+    /// ANSI X9.23 padding: zeros followed by a byte holding the padding length, as a user could
+    /// define it outside of the crate.
+    struct AnsiX923;
+
+    impl PaddingScheme for AnsiX923 {
+        fn pad(&self, data: &[u8], block_size: usize) -> Vec<u8> {
+            let pad_length = block_size - data.len() % block_size;
+            let mut padded = data.to_vec();
+            padded.resize(data.len() + pad_length - 1, 0x00);
+            padded.push(pad_length as u8);
+
+            padded
+        }
+
+        fn unpad(&self, data: &[u8], block_size: usize) -> Result<Vec<u8>, AesError> {
+            let pad_length = *data.last().ok_or(AesError::InvalidPadding)? as usize;
+            if pad_length == 0 || pad_length > block_size || pad_length > data.len() {
+                return Err(AesError::InvalidPadding);
+            }
+            let unpadded_length = data.len() - pad_length;
+            if data[unpadded_length..data.len() - 1].iter().any(|byte| *byte != 0x00) {
+                return Err(AesError::InvalidPadding);
+            }
+
+            Ok(data[..unpadded_length].to_vec())
+        }
+    }
+
+    #[test]
+    fn encrypts_with_custom_padding_scheme() {
+        let mode = BlockCipherMode::CBC(&CBC_IV);
+        let raw = b"Not block aligned";
+
+        let cipher = encrypt_with_padding_scheme(raw, &CBC_KEY, &mode, &AnsiX923).unwrap();
+
+        assert_eq!(cipher.len(), 2 * BLOCK_SIZE);
+        let padded_raw = decrypt_aes_128(&cipher, &CBC_KEY, &mode);
+        assert_eq!(padded_raw[raw.len()..], [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0f]);
+        assert_eq!(decrypt_with_padding_scheme(&cipher, &CBC_KEY, &mode, &AnsiX923), Ok(raw.to_vec()));
+    }
+
+    #[test]
+    fn built_in_padding_is_a_padding_scheme() {
+        let mode = BlockCipherMode::CBC(&CBC_IV);
+        let raw = b"Not block aligned";
+
+        let cipher = encrypt_with_padding_scheme(raw, &CBC_KEY, &mode, &Padding::PKCS7).unwrap();
+
+        assert_eq!(cipher, encrypt_aes_128(raw, &CBC_KEY, &AESEncryptionOptions::new(&mode, &Padding::PKCS7)));
+        assert_eq!(decrypt_with_padding_scheme(&cipher, &CBC_KEY, &mode, &Padding::PKCS7), Ok(raw.to_vec()));
+        assert_eq!(
+            encrypt_with_padding_scheme(raw, &CBC_KEY, &mode, &Padding::None),
+            Err(AesError::InvalidInputLength)
+        );
+    }
+
+    #[test]
+    fn ctr_ignores_padding_scheme() {
+        let mode = BlockCipherMode::CTR(&CTR_NONCE);
+
+        let cipher = encrypt_with_padding_scheme(&[0x42; 20], &CTR_KEY, &mode, &AnsiX923).unwrap();
+
+        assert_eq!(cipher.len(), 20);
+        assert_eq!(decrypt_with_padding_scheme(&cipher, &CTR_KEY, &mode, &AnsiX923), Ok(vec![0x42; 20]));
     }
-}
\ No newline at end of file
+}
@@ -2,6 +2,7 @@
 /// In the polynomial representation, multiplication in GF(2^8) (denoted by •) corresponds with the
 /// multiplication of polynomials modulo an irreducible polynomial of degree 8. A polynomial is
 /// irreducible if its only divisors are one and itself
+#[cfg(any(test, not(feature = "precomputed-tables")))]
 pub fn multiply_in_g(polynomial_value: u8, state_value: u8) -> u8 {
     let irreducible_polynomial = 0x1b;
     let mut a = polynomial_value;
@@ -24,6 +25,30 @@ pub fn multiply_in_g(polynomial_value: u8, state_value: u8) -> u8 {
     p
 }
 
+/// Multiplication by x ({02}) in GF(2^8) (Sec. 4.2.1), without branching on b.
+pub fn xtime(b: u8) -> u8 {
+    (b << 1) ^ (0x1b & 0u8.wrapping_sub(b >> 7))
+}
+
+/// Multiplication in GF(2^128) as used by GHASH (SP 800-38D, Sec. 6.3), where blocks are read as
+/// big-endian integers whose most significant bit is the coefficient of x^0. Runs without
+/// branching on the operands.
+pub fn multiply_in_gf128(x: u128, y: u128) -> u128 {
+    let r = 0xe1u128 << 120;
+    let mut z = 0u128;
+    let mut v = y;
+
+    for i in (0..128).rev() {
+        let x_bit_mask = 0u128.wrapping_sub((x >> i) & 1);
+        z ^= v & x_bit_mask;
+
+        let v_lsb_mask = 0u128.wrapping_sub(v & 1);
+        v = (v >> 1) ^ (r & v_lsb_mask);
+    }
+
+    z
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -35,7 +60,7 @@ mod tests {
             b: u8,
             expected: u8,
         }
-        let test_cases = vec![
+        let test_cases = [
             TestCase { a: 0x57, b: 0x83, expected: 0xc1 },
             TestCase { a: 0x57, b: 0x13, expected: 0xfe },
             TestCase { a: 0x57, b: 0x02, expected: 0xae },
@@ -49,4 +74,28 @@ mod tests {
             assert_eq!(result, case.expected);
         }
     }
+
+    #[test]
+    fn xtime_multiplies_by_x() {
+        // Sec. 4.2.1
+        assert_eq!(xtime(0x57), 0xae);
+        assert_eq!(xtime(0xae), 0x47);
+        assert_eq!(xtime(0x47), 0x8e);
+        assert_eq!(xtime(0x8e), 0x07);
+
+        for b in 0..=255u8 {
+            assert_eq!(xtime(b), multiply_in_g(0x02, b));
+        }
+    }
+
+    #[test]
+    fn multiply_in_gf128_test() {
+        // H and the first GHASH step of test case 2 of the GCM specification
+        let h = 0x66e94bd4ef8a2c3b884cfa59ca342b2e;
+        let x = 0x0388dace60b6a392f328c2b971b2fe78;
+
+        assert_eq!(multiply_in_gf128(x, h), 0x5e2ec746917062882c85b0685353deb7);
+        assert_eq!(multiply_in_gf128(h, x), 0x5e2ec746917062882c85b0685353deb7);
+        assert_eq!(multiply_in_gf128(x, 1 << 127), x);
+    }
 }
\ No newline at end of file
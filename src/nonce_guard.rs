@@ -0,0 +1,267 @@
+use std::collections::VecDeque;
+
+use ::{AESEncryptionOptions, BLOCK_SIZE, BlockCipherMode, Nonce, try_encrypt_aes_128};
+use cipher::{CipherParams, encrypt_block_with_params};
+use constant_time::verify_eq;
+use error::AesError;
+use key::Key;
+use pad::Padding;
+
+/// Opt-in safety net remembering the nonces used under each key within the process, refusing to
+/// encrypt twice with the same (key, nonce) pair, which is catastrophic for counter-based modes.
+///
+/// Keys are remembered by their check value (the encryption of an all-zero block) rather than by
+/// their bytes. Lookups go through every remembered pair with constant-time comparisons, so the
+/// time taken doesn't reveal which pair matched. Memory is bounded by the capacity: once it's
+/// reached, the oldest pair is forgotten and its reuse can no longer be detected.
+pub struct NonceGuard {
+    capacity: usize,
+    used: VecDeque<Vec<u8>>,
+}
+
+impl NonceGuard {
+    pub fn new(capacity: usize) -> Self {
+        NonceGuard {
+            capacity,
+            used: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Records nonce as used under key, failing if the pair was already used.
+    pub fn use_nonce(&mut self, key: &Key, nonce: &[u8]) -> Result<(), AesError> {
+        let entry = [&key_check_value(key)[..], nonce].concat();
+
+        let mut reused = false;
+        for used_entry in self.used.iter() {
+            reused |= verify_eq(used_entry, &entry);
+        }
+        if reused {
+            return Err(AesError::NonceReused);
+        }
+
+        if self.capacity > 0 {
+            if self.used.len() == self.capacity {
+                self.used.pop_front();
+            }
+            self.used.push_back(entry);
+        }
+
+        Ok(())
+    }
+
+    /// Encrypts like try_encrypt_aes_128, first recording the nonce when the mode uses one. Input
+    /// that can't be encrypted is rejected before recording the nonce, which stays usable.
+    pub fn encrypt(
+        &mut self,
+        raw_bytes: &[u8],
+        key: &Key,
+        options: &AESEncryptionOptions,
+    ) -> Result<Vec<u8>, AesError> {
+        options.check_input_length(raw_bytes)?;
+        // the nonce is recorded whatever the initial counter, which is conservative
+        if let Some((nonce, _)) = options.block_cipher_mode.counter_start() {
+            self.use_nonce(key, nonce)?;
+        }
+
+        try_encrypt_aes_128(raw_bytes, key, options)
+    }
+}
+
+/// Stricter NonceGuard for CTR, remembering for each (key, nonce) pair the highest counter reached.
+/// A nonce can be used again, but only for a message starting after that high-water mark, so that
+/// counters must be used in increasing order. This refuses messages whose counter ranges would
+/// overlap, and so reuse part of the keystream, even when they start at different counters, but
+/// also ranges that are disjoint from but below ones already used, e.g. counters 1 to 5 after
+/// counters 100 to 110, as only the highest counter is remembered.
+///
+/// Memory is bounded by the capacity like for NonceGuard.
+pub struct CtrGuard {
+    capacity: usize,
+    used: VecDeque<(Vec<u8>, u64)>,
+}
+
+impl CtrGuard {
+    pub fn new(capacity: usize) -> Self {
+        CtrGuard {
+            capacity,
+            used: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Records the counters of block_count blocks starting at initial_counter as used under key
+    /// and nonce, failing with CounterRangeReused unless initial_counter is above the highest
+    /// counter already used with them, and with InputTooLong if the counters would wrap around past
+    /// u64::MAX. No block uses no counter, so nothing is recorded when block_count is 0.
+    pub fn use_counters(
+        &mut self,
+        key: &Key,
+        nonce: &Nonce,
+        initial_counter: u64,
+        block_count: u64,
+    ) -> Result<(), AesError> {
+        if block_count == 0 {
+            return Ok(());
+        }
+        let entry = [&key_check_value(key)[..], nonce].concat();
+        let last_counter = initial_counter.checked_add(block_count - 1).ok_or(AesError::InputTooLong)?;
+
+        let mut found = None;
+        for (i, (used_entry, _)) in self.used.iter().enumerate() {
+            if verify_eq(used_entry, &entry) {
+                found = Some(i);
+            }
+        }
+
+        match found {
+            Some(i) => {
+                let highest_counter = &mut self.used[i].1;
+                if initial_counter <= *highest_counter {
+                    return Err(AesError::CounterRangeReused);
+                }
+                *highest_counter = last_counter;
+            }
+            None if self.capacity > 0 => {
+                if self.used.len() == self.capacity {
+                    self.used.pop_front();
+                }
+                self.used.push_back((entry, last_counter));
+            }
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    /// Encrypts raw_bytes in CTR mode starting at initial_counter, first recording its counters.
+    pub fn encrypt(
+        &mut self,
+        raw_bytes: &[u8],
+        key: &Key,
+        nonce: &Nonce,
+        initial_counter: u64,
+    ) -> Result<Vec<u8>, AesError> {
+        let block_count = raw_bytes.len().div_ceil(BLOCK_SIZE) as u64;
+        self.use_counters(key, nonce, initial_counter, block_count)?;
+
+        let mode = BlockCipherMode::CTRWithCounter(nonce, initial_counter);
+        try_encrypt_aes_128(raw_bytes, key, &AESEncryptionOptions::new(&mode, &Padding::None))
+    }
+}
+
+fn key_check_value(key: &Key) -> [u8; BLOCK_SIZE] {
+    encrypt_block_with_params(&[0u8; BLOCK_SIZE], key, &CipherParams::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: Key = Key([0x2b; 16]);
+    const OTHER_KEY: Key = Key([0x7e; 16]);
+    const RAW: [u8; 20] = [0x42; 20];
+
+    #[test]
+    fn refuses_to_encrypt_twice_with_same_nonce() {
+        let mut guard = NonceGuard::new(16);
+        let mode = BlockCipherMode::CTR(&[0x01; 8]);
+        let options = AESEncryptionOptions::new(&mode, &Padding::None);
+
+        assert!(guard.encrypt(&RAW, &KEY, &options).is_ok());
+        assert_eq!(guard.encrypt(&RAW, &KEY, &options), Err(AesError::NonceReused));
+    }
+
+    #[test]
+    fn doesnt_record_nonce_of_rejected_input() {
+        let mut guard = NonceGuard::new(16);
+        let mode = BlockCipherMode::CTR(&[0x01; 8]);
+
+        let rejected = guard.encrypt(&RAW, &KEY, &AESEncryptionOptions::new(&mode, &Padding::Prepadded));
+
+        assert_eq!(rejected, Err(AesError::InvalidInputLength));
+        assert!(guard.encrypt(&RAW, &KEY, &AESEncryptionOptions::new(&mode, &Padding::None)).is_ok());
+    }
+
+    #[test]
+    fn allows_same_nonce_under_different_keys() {
+        let mut guard = NonceGuard::new(16);
+
+        assert_eq!(guard.use_nonce(&KEY, &[0x01; 8]), Ok(()));
+        assert_eq!(guard.use_nonce(&OTHER_KEY, &[0x01; 8]), Ok(()));
+        assert_eq!(guard.use_nonce(&KEY, &[0x02; 8]), Ok(()));
+    }
+
+    #[test]
+    fn forgets_oldest_nonce_once_at_capacity() {
+        let mut guard = NonceGuard::new(2);
+
+        assert_eq!(guard.use_nonce(&KEY, &[0x01; 8]), Ok(()));
+        assert_eq!(guard.use_nonce(&KEY, &[0x02; 8]), Ok(()));
+        assert_eq!(guard.use_nonce(&KEY, &[0x03; 8]), Ok(()));
+
+        assert_eq!(guard.use_nonce(&KEY, &[0x01; 8]), Ok(()));
+        assert_eq!(guard.use_nonce(&KEY, &[0x03; 8]), Err(AesError::NonceReused));
+    }
+
+    #[test]
+    fn doesnt_track_modes_without_nonce() {
+        let mut guard = NonceGuard::new(16);
+        let options = AESEncryptionOptions::default();
+
+        assert!(guard.encrypt(&RAW[..16], &KEY, &options).is_ok());
+        assert!(guard.encrypt(&RAW[..16], &KEY, &options).is_ok());
+    }
+
+    #[test]
+    fn refuses_overlapping_counter_range_under_same_nonce() {
+        let mut guard = CtrGuard::new(16);
+        let nonce = [0x01; 8];
+
+        // counters 1 to 2, then 3 to 4
+        assert!(guard.encrypt(&RAW, &KEY, &nonce, 1).is_ok());
+        assert!(guard.encrypt(&RAW, &KEY, &nonce, 3).is_ok());
+
+        // counters 4 to 5 overlap the second message on counter 4
+        assert_eq!(guard.encrypt(&RAW, &KEY, &nonce, 4), Err(AesError::CounterRangeReused));
+        assert_eq!(guard.encrypt(&RAW, &KEY, &nonce, 1), Err(AesError::CounterRangeReused));
+        assert!(guard.encrypt(&RAW, &KEY, &nonce, 5).is_ok());
+    }
+
+    #[test]
+    fn refuses_counter_range_below_highest_counter_used() {
+        let mut guard = CtrGuard::new(16);
+
+        assert_eq!(guard.use_counters(&KEY, &[0x01; 8], 100, 11), Ok(()));
+        // disjoint from counters 100 to 110, but below them
+        assert_eq!(guard.use_counters(&KEY, &[0x01; 8], 1, 5), Err(AesError::CounterRangeReused));
+        assert_eq!(guard.use_counters(&KEY, &[0x01; 8], 111, 5), Ok(()));
+    }
+
+    #[test]
+    fn tracks_counters_per_key_and_nonce() {
+        let mut guard = CtrGuard::new(16);
+
+        assert_eq!(guard.use_counters(&KEY, &[0x01; 8], 1, 10), Ok(()));
+        assert_eq!(guard.use_counters(&KEY, &[0x02; 8], 1, 10), Ok(()));
+        assert_eq!(guard.use_counters(&OTHER_KEY, &[0x01; 8], 1, 10), Ok(()));
+        assert_eq!(guard.use_counters(&KEY, &[0x01; 8], 10, 1), Err(AesError::CounterRangeReused));
+    }
+
+    #[test]
+    fn refuses_counter_range_wrapping_around() {
+        let mut guard = CtrGuard::new(16);
+
+        assert_eq!(guard.use_counters(&KEY, &[0x01; 8], u64::MAX, 2), Err(AesError::InputTooLong));
+        assert_eq!(guard.use_counters(&KEY, &[0x01; 8], u64::MAX - 1, 2), Ok(()));
+        assert_eq!(guard.use_counters(&KEY, &[0x02; 8], u64::MAX, 1), Ok(()));
+        assert_eq!(guard.use_counters(&KEY, &[0x02; 8], u64::MAX, 1), Err(AesError::CounterRangeReused));
+    }
+
+    #[test]
+    fn doesnt_record_counters_of_empty_message() {
+        let mut guard = CtrGuard::new(16);
+        let nonce = [0x01; 8];
+
+        assert_eq!(guard.encrypt(&[], &KEY, &nonce, u64::MAX), Ok(vec![]));
+        assert!(guard.encrypt(&RAW[..BLOCK_SIZE], &KEY, &nonce, u64::MAX).is_ok());
+    }
+}
@@ -0,0 +1,108 @@
+use ::{BLOCK_SIZE, BlockCipherMode, Iv, decrypt_aes_128};
+use error::AesError;
+use key::Key;
+use md5::md5;
+use pad::{Padding, unpad_aes};
+
+/// Magic that starts salted `openssl enc` output, followed by the 8 byte salt.
+const SALTED_MAGIC: &[u8; 8] = b"Salted__";
+const SALT_SIZE: usize = 8;
+
+/// Decrypts the output of `openssl enc -aes-128-cbc -md md5 -pass pass:<passphrase>`, i.e.
+/// "Salted__" || salt || ciphertext, where the key and IV are derived from the passphrase and the
+/// salt using EVP_BytesToKey with MD5, and the ciphertext is PKCS7 padded.
+///
+/// Since OpenSSL 1.1.0, `openssl enc` derives keys with SHA-256 unless given `-md md5`. Data
+/// encrypted with `-pbkdf2` or without a salt isn't supported. A wrong passphrase most likely
+/// results in InvalidPadding, but isn't guaranteed to be detected.
+pub fn decrypt_openssl_enc(data: &[u8], passphrase: &[u8]) -> Result<Vec<u8>, AesError> {
+    let header_size = SALTED_MAGIC.len() + SALT_SIZE;
+    if data.len() < header_size || &data[..SALTED_MAGIC.len()] != SALTED_MAGIC {
+        return Err(AesError::InvalidHeader);
+    }
+    let salt = &data[SALTED_MAGIC.len()..header_size];
+    let cipher = &data[header_size..];
+    if cipher.is_empty() || !cipher.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(AesError::InvalidInputLength);
+    }
+
+    let (key, iv) = evp_bytes_to_key(passphrase, salt);
+    let padded_raw = decrypt_aes_128(cipher, &key, &BlockCipherMode::CBC(&iv));
+
+    unpad_aes(&padded_raw, &Padding::PKCS7)
+}
+
+/// OpenSSL's EVP_BytesToKey with MD5 and a single iteration: D_i = MD5(D_i-1 || passphrase ||
+/// salt), where D_0 is empty, concatenated until there are enough bytes for the key and the IV.
+fn evp_bytes_to_key(passphrase: &[u8], salt: &[u8]) -> (Key, Iv) {
+    let mut derived: Vec<u8> = Vec::with_capacity(2 * BLOCK_SIZE);
+    let mut previous_digest: Vec<u8> = vec![];
+
+    while derived.len() < 2 * BLOCK_SIZE {
+        let digest = md5(&[&previous_digest[..], passphrase, salt].concat());
+        derived.extend_from_slice(&digest);
+        previous_digest = digest.to_vec();
+    }
+
+    let mut key = [0u8; BLOCK_SIZE];
+    key.copy_from_slice(&derived[..BLOCK_SIZE]);
+    let mut iv = [0u8; BLOCK_SIZE];
+    iv.copy_from_slice(&derived[BLOCK_SIZE..2 * BLOCK_SIZE]);
+
+    (Key(key), Iv::from_bytes(&iv))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_key_and_iv_like_openssl() {
+        // openssl enc -aes-128-cbc -md md5 -pass "pass:correct horse" -S 0102030405060708 -P
+        let (key, iv) = evp_bytes_to_key(
+            b"correct horse",
+            &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08],
+        );
+
+        assert_eq!(key, Key([
+            0xbc, 0xf8, 0xd9, 0x41,
+            0xd9, 0x29, 0x11, 0x41,
+            0x70, 0x9c, 0x9d, 0x56,
+            0x36, 0x0e, 0xb7, 0x14
+        ]));
+        assert_eq!(iv, Iv::from_bytes(&[
+            0x8e, 0x39, 0x60, 0xab,
+            0x3d, 0xc4, 0x4d, 0x83,
+            0x2c, 0x40, 0x28, 0x56,
+            0x85, 0x45, 0xc9, 0x1d
+        ]));
+    }
+
+    #[test]
+    fn decrypts_openssl_enc_output() {
+        // openssl enc -aes-128-cbc -md md5 -pass "pass:correct horse" -in aes-128-cbc-md5.txt
+        let data = include_bytes!("../tests/openssl/aes-128-cbc-md5.enc");
+        let expected = include_bytes!("../tests/openssl/aes-128-cbc-md5.txt");
+
+        let actual = decrypt_openssl_enc(data, b"correct horse");
+
+        assert_eq!(actual, Ok(expected.to_vec()));
+    }
+
+    #[test]
+    fn rejects_data_without_salted_header() {
+        let data = include_bytes!("../tests/openssl/aes-128-cbc-md5.enc");
+
+        assert_eq!(decrypt_openssl_enc(&data[16..], b"correct horse"), Err(AesError::InvalidHeader));
+        assert_eq!(decrypt_openssl_enc(b"Salted__", b"correct horse"), Err(AesError::InvalidHeader));
+    }
+
+    #[test]
+    fn rejects_truncated_ciphertext() {
+        let data = include_bytes!("../tests/openssl/aes-128-cbc-md5.enc");
+
+        let actual = decrypt_openssl_enc(&data[..data.len() - 1], b"correct horse");
+
+        assert_eq!(actual, Err(AesError::InvalidInputLength));
+    }
+}
@@ -1,56 +1,419 @@
+use BLOCK_SIZE;
+use error::AesError;
+
 #[derive(PartialEq, Debug)]
 pub enum Padding {
     PKCS7,
     None,
+    /// The caller has already padded the input, which must be a multiple of the block size.
+    Prepadded,
+    /// Random bytes followed by a byte holding the padding length.
+    #[cfg(feature = "rand")]
+    Iso10126,
+}
+
+/// Padding scheme, through which encrypt_with_padding_scheme and decrypt_with_padding_scheme pad
+/// and unpad, so that padding schemes other than the built-in Padding can be used.
+///
+/// pad must return a multiple of block_size bytes for block modes, and unpad must undo it.
+pub trait PaddingScheme {
+    fn pad(&self, data: &[u8], block_size: usize) -> Vec<u8>;
+
+    fn unpad(&self, data: &[u8], block_size: usize) -> Result<Vec<u8>, AesError>;
+}
+
+/// The built-in paddings, like pad and unpad. Panics if block_size is over 255.
+impl PaddingScheme for Padding {
+    fn pad(&self, data: &[u8], block_size: usize) -> Vec<u8> {
+        pad(data, padding_block_size(block_size), self)
+    }
+
+    fn unpad(&self, data: &[u8], block_size: usize) -> Result<Vec<u8>, AesError> {
+        unpad(data, padding_block_size(block_size), self)
+    }
+}
+
+fn padding_block_size(block_size: usize) -> u8 {
+    assert!(block_size <= u8::MAX as usize, "Block size must fit in a padding byte.");
+
+    block_size as u8
+}
+
+/// Pads bytes to block_size using the given padding. Paddings that leave the bytes unchanged
+/// return them as is.
+pub fn pad(bytes: &[u8], block_size: u8, padding: &Padding) -> Vec<u8> {
+    match padding {
+        Padding::PKCS7 => pkcs7_pad(bytes, block_size),
+        #[cfg(feature = "rand")]
+        Padding::Iso10126 => iso10126_pad(bytes, block_size),
+        Padding::None | Padding::Prepadded => bytes.to_vec(),
+    }
+}
+
+/// Pads the last, partial block of a message that is processed block by block, e.g. when
+/// streaming, returning the final block(s). An empty partial block gets a full block of padding
+/// with PKCS7 and ISO 10126.
+///
+/// Panics if partial_block isn't shorter than block_size, or if block_size is over 255.
+pub fn finalize_padding(partial_block: &[u8], block_size: usize, padding: &Padding) -> Vec<u8> {
+    assert!(partial_block.len() < block_size, "Partial block must be shorter than block_size.");
+
+    pad(partial_block, padding_block_size(block_size), padding)
+}
+
+/// Removes the given padding from bytes, validating it.
+pub fn unpad(bytes: &[u8], block_size: u8, padding: &Padding) -> Result<Vec<u8>, AesError> {
+    let length = unpadded_length(bytes, block_size, padding)?;
+
+    Ok(bytes[..length].to_vec())
+}
+
+/// Validates the given padding and returns the length of bytes without it, so that callers can
+/// slice instead of copying.
+pub fn unpadded_length(bytes: &[u8], block_size: u8, padding: &Padding) -> Result<usize, AesError> {
+    match padding {
+        Padding::PKCS7 => pkcs7_unpadded_length(bytes, block_size),
+        #[cfg(feature = "rand")]
+        Padding::Iso10126 => Ok(bytes.len() - validated_pad_length(bytes, block_size)?),
+        Padding::None | Padding::Prepadded => Ok(bytes.len()),
+    }
+}
+
+/// Pads bytes to BLOCK_SIZE, the block size of aes, using the given padding, like pad.
+pub fn pad_aes(bytes: &[u8], padding: &Padding) -> Vec<u8> {
+    pad(bytes, BLOCK_SIZE as u8, padding)
+}
+
+/// Removes the given padding from bytes padded to BLOCK_SIZE, like unpad.
+pub fn unpad_aes(bytes: &[u8], padding: &Padding) -> Result<Vec<u8>, AesError> {
+    unpad(bytes, BLOCK_SIZE as u8, padding)
+}
+
+/// Length of bytes padded to BLOCK_SIZE without their padding, like unpadded_length.
+pub fn unpadded_length_aes(bytes: &[u8], padding: &Padding) -> Result<usize, AesError> {
+    unpadded_length(bytes, BLOCK_SIZE as u8, padding)
+}
+
+/// Pads bytes to BLOCK_SIZE using pkcs7 padding, like pkcs7_pad.
+pub fn pkcs7_pad_aes(bytes: &[u8]) -> Vec<u8> {
+    pkcs7_pad(bytes, BLOCK_SIZE as u8)
 }
 
-/// Pads bytes to block_size using pkcs7 padding
+/// Removes pkcs7 padding from bytes padded to BLOCK_SIZE, like pkcs7_unpad.
+pub fn pkcs7_unpad_aes(bytes: &[u8]) -> Result<Vec<u8>, AesError> {
+    pkcs7_unpad(bytes, BLOCK_SIZE as u8)
+}
+
+/// Pads bytes to block_size using pkcs7 padding. Input that is already at length, including empty
+/// input, gets a full block of padding, so that the padding can always be removed unambiguously.
+///
+/// Any block size from 1 to 255 is supported, not only BLOCK_SIZE, e.g. to pad for another cipher.
 ///
 /// See: https://tools.ietf.org/html/rfc5652#section-6.3
+///
+/// Panics if block_size is 0.
 pub fn pkcs7_pad(bytes: &[u8], block_size: u8) -> Vec<u8> {
-    let mut pad_length = block_size - (bytes.len() as u8 % block_size);
+    let pad_length = pad_length(bytes.len(), block_size);
+
+    [bytes, &vec![pad_length; pad_length as usize][..]].concat()
+}
+
+/// Removes pkcs7 padding, checking that every padding byte holds the padding length.
+pub fn pkcs7_unpad(bytes: &[u8], block_size: u8) -> Result<Vec<u8>, AesError> {
+    let length = pkcs7_unpadded_length(bytes, block_size)?;
+
+    Ok(bytes[..length].to_vec())
+}
+
+fn pkcs7_unpadded_length(bytes: &[u8], block_size: u8) -> Result<usize, AesError> {
+    let pad_length = validated_pad_length(bytes, block_size)?;
+    let unpadded_length = bytes.len() - pad_length;
+
+    if bytes[unpadded_length..].iter().any(|byte| *byte as usize != pad_length) {
+        return Err(AesError::InvalidPadding);
+    }
+
+    Ok(unpadded_length)
+}
+
+/// Pads bytes to block_size using ISO 10126 padding: random bytes followed by a byte holding the
+/// padding length. A full block of padding is added when bytes are already at length.
+#[cfg(feature = "rand")]
+pub fn iso10126_pad(bytes: &[u8], block_size: u8) -> Vec<u8> {
+    use rand::RngCore;
+
+    let pad_length = pad_length(bytes.len(), block_size);
+    let mut padding = vec![0u8; pad_length as usize];
+    rand::thread_rng().fill_bytes(&mut padding[..pad_length as usize - 1]);
+    padding[pad_length as usize - 1] = pad_length;
+
+    [bytes, &padding[..]].concat()
+}
+
+/// Removes ISO 10126 padding. Only the final length byte can be validated, the other padding
+/// bytes being random.
+#[cfg(feature = "rand")]
+pub fn iso10126_unpad(bytes: &[u8], block_size: u8) -> Result<Vec<u8>, AesError> {
+    unpad(bytes, block_size, &Padding::Iso10126)
+}
+
+/// Best-effort guess of the padding of a message from its last decrypted block, for ciphertexts of
+/// unknown provenance.
+///
+/// A last byte that is 0 or over BLOCK_SIZE can't be padding, giving Padding::None. Trailing
+/// bytes that all hold their count, e.g. `03 03 03`, give Padding::PKCS7. Anything else is
+/// ambiguous and gives None: a block ending in `01` is valid PKCS7 but is as likely unpadded data,
+/// and ISO 10126 padding can't be told from unpadded data whose last byte is at most BLOCK_SIZE.
+/// Even a clear guess can be wrong, since unpadded data may happen to end in e.g. `02 02`.
+pub fn detect_padding(last_block: &[u8; BLOCK_SIZE]) -> Option<Padding> {
+    let pad_length = match validated_pad_length(last_block, BLOCK_SIZE as u8) {
+        Ok(pad_length) => pad_length,
+        Err(_) => return Some(Padding::None),
+    };
 
-    if pad_length == 0 {
-        pad_length = block_size;
+    if pad_length > 1 && pkcs7_unpad_aes(last_block).is_ok() {
+        Some(Padding::PKCS7)
+    } else {
+        None
     }
+}
+
+/// Number of padding bytes, from 1 to block_size, that bring length to a multiple of block_size.
+/// The remainder is taken over the whole length: truncating the length to a u8 first would only
+/// be correct for block sizes dividing 256.
+fn pad_length(length: usize, block_size: u8) -> u8 {
+    assert!(block_size > 0, "Block size must not be 0.");
 
-    [&bytes[..], &vec![pad_length; pad_length as usize][..]].concat()
+    block_size - (length % block_size as usize) as u8
+}
+
+/// Reads the padding length from the last byte, which must be between 1 and block_size and no
+/// longer than bytes.
+fn validated_pad_length(bytes: &[u8], block_size: u8) -> Result<usize, AesError> {
+    let pad_length = match bytes.last() {
+        Some(last_byte) => *last_byte as usize,
+        None => return Err(AesError::InvalidPadding),
+    };
+
+    if pad_length == 0 || pad_length > block_size as usize || pad_length > bytes.len() {
+        return Err(AesError::InvalidPadding);
+    }
+
+    Ok(pad_length)
 }
 
 #[cfg(test)]
 mod tests {
-    use pad::pkcs7_pad;
+    use super::*;
+
+    #[test]
+    fn padding_scheme_of_built_in_paddings_pads_and_unpads() {
+        let padded = PaddingScheme::pad(&Padding::PKCS7, &[0x42; 5], 8);
+
+        assert_eq!(padded, vec![0x42, 0x42, 0x42, 0x42, 0x42, 0x03, 0x03, 0x03]);
+        assert_eq!(PaddingScheme::unpad(&Padding::PKCS7, &padded, 8), Ok(vec![0x42; 5]));
+        assert_eq!(PaddingScheme::pad(&Padding::None, &[0x42; 5], 8), vec![0x42; 5]);
+        assert_eq!(PaddingScheme::unpad(&Padding::None, &padded, 8), Ok(padded.clone()));
+    }
 
     #[test]
     fn pads_empty_bytes() {
         let empty = &[];
-        let block_size = 16;
 
-        let expected = &[16u8; 16];
+        let expected = &[16u8; BLOCK_SIZE];
 
-        assert_eq!(expected.to_vec(), pkcs7_pad(empty, block_size));
+        assert_eq!(expected.to_vec(), pkcs7_pad_aes(empty));
     }
 
     #[test]
     fn pads_to_length() {
         let some_bytes = &[12; 12];
-        let block_size = 16;
 
         let expected = &[
             12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12,
             4, 4, 4, 4
         ];
 
-        assert_eq!(expected.to_vec(), pkcs7_pad(some_bytes, block_size));
+        assert_eq!(expected.to_vec(), pkcs7_pad_aes(some_bytes));
     }
 
     #[test]
     fn ads_complete_padding_block_when_is_already_at_length() {
-        let full_bytes = &[16; 16];
-        let block_size = 16;
+        let full_bytes = &[16; BLOCK_SIZE];
+
+        let expected = &[16; BLOCK_SIZE * 2];
 
-        let expected = &[16; 16 * 2];
+        assert_eq!(expected.to_vec(), pkcs7_pad_aes(full_bytes));
+    }
+
+    #[test]
+    fn finalizes_pkcs7_padding_for_every_fill_level() {
+        for fill in 0..BLOCK_SIZE {
+            let partial_block = vec![0x42; fill];
+
+            let final_block = finalize_padding(&partial_block, BLOCK_SIZE, &Padding::PKCS7);
+
+            assert_eq!(final_block.len(), BLOCK_SIZE);
+            assert_eq!(final_block[..fill], partial_block[..]);
+            assert!(final_block[fill..].iter().all(|byte| *byte as usize == BLOCK_SIZE - fill));
+        }
+    }
+
+    #[test]
+    fn finalizes_empty_partial_block_to_full_pkcs7_block() {
+        assert_eq!(finalize_padding(&[], BLOCK_SIZE, &Padding::PKCS7), vec![16; BLOCK_SIZE]);
+    }
+
+    #[test]
+    fn finalizes_without_padding() {
+        assert_eq!(finalize_padding(&[1, 2, 3], BLOCK_SIZE, &Padding::None), vec![1, 2, 3]);
+        assert_eq!(finalize_padding(&[], BLOCK_SIZE, &Padding::None), vec![]);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn finalizes_iso10126_padding_for_every_fill_level() {
+        for fill in 0..BLOCK_SIZE {
+            let final_block = finalize_padding(&vec![0x42; fill], BLOCK_SIZE, &Padding::Iso10126);
+
+            assert_eq!(final_block.len(), BLOCK_SIZE);
+            assert_eq!(final_block[BLOCK_SIZE - 1] as usize, BLOCK_SIZE - fill);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn finalize_padding_panics_on_complete_block() {
+        finalize_padding(&[0; BLOCK_SIZE], BLOCK_SIZE, &Padding::PKCS7);
+    }
+
+    #[test]
+    fn pkcs7_unpads() {
+        let padded = pkcs7_pad_aes(&[12; 12]);
+
+        assert_eq!(pkcs7_unpad_aes(&padded), Ok(vec![12; 12]));
+        assert_eq!(pkcs7_unpad_aes(&[16; BLOCK_SIZE]), Ok(vec![]));
+    }
+
+    #[test]
+    fn pkcs7_unpad_rejects_invalid_padding() {
+        let inconsistent = &[12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 4, 4, 3, 4];
+
+        assert_eq!(pkcs7_unpad_aes(inconsistent), Err(AesError::InvalidPadding));
+        assert_eq!(pkcs7_unpad_aes(&[0; BLOCK_SIZE]), Err(AesError::InvalidPadding));
+        assert_eq!(pkcs7_unpad_aes(&[17; BLOCK_SIZE]), Err(AesError::InvalidPadding));
+        assert_eq!(pkcs7_unpad_aes(&[3, 3]), Err(AesError::InvalidPadding));
+        assert_eq!(pkcs7_unpad_aes(&[]), Err(AesError::InvalidPadding));
+    }
+
+    #[test]
+    fn pkcs7_round_trips_across_block_sizes() {
+        for block_size in [1u8, 8, 16, 24, 32, 255].iter().cloned() {
+            for length in 0..3 * block_size as usize {
+                let bytes = vec![0x42; length];
+
+                let padded = pkcs7_pad(&bytes, block_size);
+
+                let pad_length = block_size as usize - length % block_size as usize;
+                assert_eq!(padded.len(), length + pad_length);
+                assert_eq!(padded.len() % block_size as usize, 0);
+                assert!(padded[length..].iter().all(|byte| *byte as usize == pad_length));
+                assert_eq!(pkcs7_unpad(&padded, block_size), Ok(bytes));
+            }
+        }
+    }
+
+    #[test]
+    fn pkcs7_pads_lengths_over_255_for_block_sizes_not_dividing_256() {
+        // 256 % 255 = 1, whereas 256 truncated to a u8 would be 0
+        let padded = pkcs7_pad(&[0x42; 256], 255);
+        assert_eq!(padded.len(), 2 * 255);
+        assert_eq!(padded[256..], [254; 254][..]);
 
-        assert_eq!(expected.to_vec(), pkcs7_pad(full_bytes, block_size));
+        // 300 % 24 = 12, whereas 300 truncated to a u8 is 44, and 44 % 24 = 20
+        let padded = pkcs7_pad(&[0x42; 300], 24);
+        assert_eq!(padded.len(), 312);
+        assert_eq!(padded[300..], [12; 12]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn pkcs7_pads_full_block_of_maximum_size() {
+        assert_eq!(pkcs7_pad(&[], 255), vec![255; 255]);
+        assert_eq!(pkcs7_pad(&[0x42; 255], 255)[255..], [255; 255][..]);
+        assert_eq!(pkcs7_unpad(&[255; 255], 255), Ok(vec![]));
+    }
+
+    #[test]
+    fn pkcs7_unpad_rejects_padding_longer_than_block_size() {
+        assert_eq!(pkcs7_unpad(&[9; 16], 8), Err(AesError::InvalidPadding));
+        assert_eq!(pkcs7_unpad(&[8; 16], 8), Ok(vec![8; 8]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn pkcs7_pad_panics_on_zero_block_size() {
+        pkcs7_pad(&[0x42], 0);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn iso10126_pads_to_length_ending_with_length_byte() {
+        let padded = pad_aes(&[12; 12], &Padding::Iso10126);
+
+        assert_eq!(padded.len(), BLOCK_SIZE);
+        assert_eq!(padded[..12], [12; 12]);
+        assert_eq!(padded[BLOCK_SIZE - 1], 4);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn iso10126_adds_complete_padding_block_when_is_already_at_length() {
+        let padded = pad_aes(&[16; BLOCK_SIZE], &Padding::Iso10126);
+
+        assert_eq!(padded.len(), 2 * BLOCK_SIZE);
+        assert_eq!(padded[2 * BLOCK_SIZE - 1], 16);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn iso10126_unpads_and_validates_length_byte() {
+        let padded = pad_aes(&[12; 12], &Padding::Iso10126);
+
+        assert_eq!(unpad_aes(&padded, &Padding::Iso10126), Ok(vec![12; 12]));
+        assert_eq!(unpad_aes(&[0x42, 0x00], &Padding::Iso10126), Err(AesError::InvalidPadding));
+        assert_eq!(unpad_aes(&[0x42, 0x11], &Padding::Iso10126), Err(AesError::InvalidPadding));
+        assert_eq!(unpad_aes(&[0x42, 0x03], &Padding::Iso10126), Err(AesError::InvalidPadding));
+    }
+
+    #[test]
+    fn detects_pkcs7_padding() {
+        let mut last_block = [0x61; BLOCK_SIZE];
+        last_block[13..].copy_from_slice(&[0x03, 0x03, 0x03]);
+
+        assert_eq!(detect_padding(&last_block), Some(Padding::PKCS7));
+        assert_eq!(detect_padding(&[0x10; BLOCK_SIZE]), Some(Padding::PKCS7));
+    }
+
+    #[test]
+    fn detects_no_padding_when_last_byte_cannot_be_a_padding_length() {
+        let mut last_block = [0x05; BLOCK_SIZE];
+
+        last_block[BLOCK_SIZE - 1] = 0x00;
+        assert_eq!(detect_padding(&last_block), Some(Padding::None));
+        last_block[BLOCK_SIZE - 1] = 0x11;
+        assert_eq!(detect_padding(&last_block), Some(Padding::None));
+        assert_eq!(detect_padding(b"YELLOW SUBMARINE"), Some(Padding::None));
+    }
+
+    #[test]
+    fn does_not_guess_ambiguous_padding() {
+        let mut last_block = [0x61; BLOCK_SIZE];
+
+        // valid PKCS7, but as likely to be unpadded data
+        last_block[BLOCK_SIZE - 1] = 0x01;
+        assert_eq!(detect_padding(&last_block), None);
+        // ISO 10126 padding, or unpadded data
+        last_block[BLOCK_SIZE - 1] = 0x04;
+        assert_eq!(detect_padding(&last_block), None);
+    }
+}
@@ -0,0 +1,212 @@
+use ::{AESEncryptionOptions, BLOCK_SIZE, BlockCipherMode, Iv, decrypt_into, try_encrypt_aes_128};
+use error::AesError;
+use key::Key;
+use pad::Padding;
+
+/// Size of the big-endian u32 length prefixing each record.
+const LENGTH_SIZE: usize = 4;
+
+/// Encrypts a sequence of records into a single ciphertext, each record being framed as its length
+/// as a big-endian u32 followed by its bytes, so that decrypt_records can recover them
+/// individually. The frames are concatenated, then padded and encrypted with the given options.
+///
+/// Fails with InputTooLong if a record is longer than u32::MAX bytes.
+pub fn encrypt_records(
+    records: &[&[u8]],
+    key: &Key,
+    options: &AESEncryptionOptions,
+) -> Result<Vec<u8>, AesError> {
+    let framed_length = records.iter().map(|record| LENGTH_SIZE + record.len()).sum();
+    let mut framed = Vec::with_capacity(framed_length);
+
+    for record in records {
+        if record.len() > u32::MAX as usize {
+            return Err(AesError::InputTooLong);
+        }
+        framed.extend_from_slice(&(record.len() as u32).to_be_bytes());
+        framed.extend_from_slice(record);
+    }
+
+    try_encrypt_aes_128(&framed, key, options)
+}
+
+/// Decrypts a ciphertext produced by encrypt_records, removes its padding and splits it back into
+/// records.
+///
+/// Fails with InvalidFraming if the plaintext isn't a sequence of complete length-prefixed records.
+pub fn decrypt_records(
+    cipher: &[u8],
+    key: &Key,
+    mode: &BlockCipherMode,
+    padding: &Padding,
+) -> Result<Vec<Vec<u8>>, AesError> {
+    let mut framed = vec![0u8; cipher.len()];
+    let framed_length = decrypt_into(cipher, key, mode, padding, &mut framed)?;
+    let mut remaining = &framed[..framed_length];
+    let mut records = vec![];
+
+    while !remaining.is_empty() {
+        if remaining.len() < LENGTH_SIZE {
+            return Err(AesError::InvalidFraming);
+        }
+        let (length, rest) = remaining.split_at(LENGTH_SIZE);
+        let length = u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize;
+        if rest.len() < length {
+            return Err(AesError::InvalidFraming);
+        }
+
+        let (record, rest) = rest.split_at(length);
+        records.push(record.to_vec());
+        remaining = rest;
+    }
+
+    Ok(records)
+}
+
+/// Encrypts raw_bytes in CBC with PKCS7 padding into a frame of a multi-message stream:
+/// iv (16 bytes) || ciphertext length (big-endian u32) || ciphertext. Frames are concatenated to
+/// build the stream read by decrypt_multi.
+pub fn encrypt_frame(raw_bytes: &[u8], key: &Key, iv: &Iv) -> Result<Vec<u8>, AesError> {
+    let mode = BlockCipherMode::CBC(iv);
+    let options = AESEncryptionOptions::new(&mode, &Padding::PKCS7);
+    let cipher = try_encrypt_aes_128(raw_bytes, key, &options)?;
+    if cipher.len() > u32::MAX as usize {
+        return Err(AesError::InputTooLong);
+    }
+
+    Ok([&iv.to_bytes()[..], &(cipher.len() as u32).to_be_bytes(), &cipher].concat())
+}
+
+/// Decrypts every frame of a stream of frames produced by encrypt_frame, each with its own IV.
+///
+/// Fails with InvalidFraming if the stream ends within a frame.
+pub fn decrypt_multi(stream: &[u8], key: &Key) -> Result<Vec<Vec<u8>>, AesError> {
+    let header_size = BLOCK_SIZE + LENGTH_SIZE;
+    let mut remaining = stream;
+    let mut messages = vec![];
+
+    while !remaining.is_empty() {
+        if remaining.len() < header_size {
+            return Err(AesError::InvalidFraming);
+        }
+        let (header, rest) = remaining.split_at(header_size);
+        let mut iv = [0u8; BLOCK_SIZE];
+        iv.copy_from_slice(&header[..BLOCK_SIZE]);
+        let length = u32::from_be_bytes([header[16], header[17], header[18], header[19]]) as usize;
+        if rest.len() < length {
+            return Err(AesError::InvalidFraming);
+        }
+
+        let (cipher, rest) = rest.split_at(length);
+        let iv = Iv::from_bytes(&iv);
+        let mode = BlockCipherMode::CBC(&iv);
+        let mut message = vec![0u8; length];
+        let message_length = decrypt_into(cipher, key, &mode, &Padding::PKCS7, &mut message)?;
+        message.truncate(message_length);
+        messages.push(message);
+        remaining = rest;
+    }
+
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::Block;
+
+    use super::*;
+
+    const KEY: Key = Key([0x2b; BLOCK_SIZE]);
+
+    #[test]
+    fn records_round_trip() {
+        let iv = Block::from_bytes(&[0x24; BLOCK_SIZE]);
+        let mode = BlockCipherMode::CBC(&iv);
+        let records: [&[u8]; 4] = [b"first record", b"", &[0x42; 40], b"last"];
+
+        let cipher = encrypt_records(
+            &records,
+            &KEY,
+            &AESEncryptionOptions::new(&mode, &Padding::PKCS7),
+        ).unwrap();
+        let actual = decrypt_records(&cipher, &KEY, &mode, &Padding::PKCS7);
+
+        assert_eq!(actual, Ok(records.iter().map(|record| record.to_vec()).collect()));
+    }
+
+    #[test]
+    fn records_are_length_prefixed_in_big_endian() {
+        let nonce = [0xf0; 8];
+        let mode = BlockCipherMode::CTR(&nonce);
+        let options = AESEncryptionOptions::new(&mode, &Padding::None);
+
+        let cipher = encrypt_records(&[b"abc", b"de"], &KEY, &options).unwrap();
+        let expected = try_encrypt_aes_128(
+            &[0x00, 0x00, 0x00, 0x03, 0x61, 0x62, 0x63, 0x00, 0x00, 0x00, 0x02, 0x64, 0x65],
+            &KEY,
+            &options,
+        );
+
+        assert_eq!(Ok(cipher), expected);
+    }
+
+    #[test]
+    fn no_records_round_trip() {
+        let options = AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::PKCS7);
+
+        let cipher = encrypt_records(&[], &KEY, &options).unwrap();
+
+        assert_eq!(cipher.len(), BLOCK_SIZE);
+        assert_eq!(decrypt_records(&cipher, &KEY, &BlockCipherMode::ECB, &Padding::PKCS7), Ok(vec![]));
+    }
+
+    #[test]
+    fn decrypt_records_rejects_truncated_record() {
+        let options = AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::PKCS7);
+        // announces 5 bytes but only holds 3
+        let cipher = try_encrypt_aes_128(&[0x00, 0x00, 0x00, 0x05, 0x61, 0x62, 0x63], &KEY, &options)
+            .unwrap();
+
+        let actual = decrypt_records(&cipher, &KEY, &BlockCipherMode::ECB, &Padding::PKCS7);
+
+        assert_eq!(actual, Err(AesError::InvalidFraming));
+    }
+
+    #[test]
+    fn multi_message_stream_round_trips() {
+        let messages: [&[u8]; 3] = [b"first log line", b"", b"a third log line, over a block"];
+        let mut stream = vec![];
+        for (i, message) in messages.iter().enumerate() {
+            let iv = Block::from_bytes(&[i as u8; BLOCK_SIZE]);
+            stream.extend_from_slice(&encrypt_frame(message, &KEY, &iv).unwrap());
+        }
+
+        let actual = decrypt_multi(&stream, &KEY);
+
+        assert_eq!(actual, Ok(messages.iter().map(|message| message.to_vec()).collect()));
+    }
+
+    #[test]
+    fn frame_holds_iv_length_and_cipher() {
+        let iv = Block::from_bytes(&[0x24; BLOCK_SIZE]);
+
+        let frame = encrypt_frame(b"message", &KEY, &iv).unwrap();
+
+        let mode = BlockCipherMode::CBC(&iv);
+        let options = AESEncryptionOptions::new(&mode, &Padding::PKCS7);
+        let cipher = try_encrypt_aes_128(b"message", &KEY, &options).unwrap();
+        assert_eq!(frame, [&[0x24; BLOCK_SIZE][..], &[0x00, 0x00, 0x00, 0x10], &cipher].concat());
+    }
+
+    #[test]
+    fn decrypt_multi_rejects_truncated_frames() {
+        let iv = Block::from_bytes(&[0x24; BLOCK_SIZE]);
+        let frame = encrypt_frame(b"message", &KEY, &iv).unwrap();
+        let stream = [&frame[..], &frame[..]].concat();
+
+        // within the header of the second frame
+        assert_eq!(decrypt_multi(&stream[..frame.len() + 10], &KEY), Err(AesError::InvalidFraming));
+        // within the cipher of the second frame
+        assert_eq!(decrypt_multi(&stream[..stream.len() - 1], &KEY), Err(AesError::InvalidFraming));
+    }
+}
@@ -0,0 +1,98 @@
+//! Access to the S-box tables, either embedded as constants (the `precomputed-tables` feature) or
+//! computed once on first use from the multiplicative inverse in GF(2^8) (Sec. 5.1.1).
+
+#[cfg(not(feature = "precomputed-tables"))]
+use std::sync::OnceLock;
+
+#[cfg(any(test, not(feature = "precomputed-tables")))]
+use math::multiply_in_g;
+#[cfg(feature = "precomputed-tables")]
+use ::{INVERSE_S_BOX, S_BOX};
+
+/// Substitution table used by SubBytes and SubWord.
+#[cfg(feature = "precomputed-tables")]
+pub fn s_box() -> &'static [u8; 256] {
+    &S_BOX
+}
+
+/// Substitution table used by InvSubBytes.
+#[cfg(feature = "precomputed-tables")]
+pub fn inverse_s_box() -> &'static [u8; 256] {
+    &INVERSE_S_BOX
+}
+
+/// Substitution table used by SubBytes and SubWord.
+#[cfg(not(feature = "precomputed-tables"))]
+pub fn s_box() -> &'static [u8; 256] {
+    static S_BOX: OnceLock<[u8; 256]> = OnceLock::new();
+
+    S_BOX.get_or_init(compute_s_box)
+}
+
+/// Substitution table used by InvSubBytes.
+#[cfg(not(feature = "precomputed-tables"))]
+pub fn inverse_s_box() -> &'static [u8; 256] {
+    static INVERSE_S_BOX: OnceLock<[u8; 256]> = OnceLock::new();
+
+    INVERSE_S_BOX.get_or_init(|| compute_inverse_s_box(s_box()))
+}
+
+/// Builds the S-box by taking the multiplicative inverse of each byte in GF(2^8), {00} being
+/// mapped to itself, then applying the affine transformation (5.1).
+#[cfg(any(test, not(feature = "precomputed-tables")))]
+fn compute_s_box() -> [u8; 256] {
+    let mut s_box = [0u8; 256];
+
+    for (byte, substitution) in s_box.iter_mut().enumerate() {
+        let b = inverse_in_g(byte as u8);
+
+        *substitution = b ^ b.rotate_left(1) ^ b.rotate_left(2) ^ b.rotate_left(3)
+            ^ b.rotate_left(4) ^ 0x63;
+    }
+
+    s_box
+}
+
+#[cfg(any(test, not(feature = "precomputed-tables")))]
+fn compute_inverse_s_box(s_box: &[u8; 256]) -> [u8; 256] {
+    let mut inverse_s_box = [0u8; 256];
+
+    for (byte, substitution) in s_box.iter().enumerate() {
+        inverse_s_box[*substitution as usize] = byte as u8;
+    }
+
+    inverse_s_box
+}
+
+/// Multiplicative inverse in GF(2^8), computed as b^254.
+#[cfg(any(test, not(feature = "precomputed-tables")))]
+fn inverse_in_g(b: u8) -> u8 {
+    let mut inverse = 1;
+    for _ in 0..254 {
+        inverse = multiply_in_g(inverse, b);
+    }
+
+    inverse
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::{INVERSE_S_BOX, S_BOX};
+
+    #[test]
+    fn computed_s_box_is_the_embedded_s_box() {
+        assert_eq!(compute_s_box()[..], S_BOX[..]);
+    }
+
+    #[test]
+    fn computed_inverse_s_box_is_the_embedded_inverse_s_box() {
+        assert_eq!(compute_inverse_s_box(&compute_s_box())[..], INVERSE_S_BOX[..]);
+    }
+
+    #[test]
+    fn s_box_is_the_embedded_s_box() {
+        assert_eq!(s_box()[..], S_BOX[..]);
+        assert_eq!(inverse_s_box()[..], INVERSE_S_BOX[..]);
+    }
+}
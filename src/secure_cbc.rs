@@ -0,0 +1,96 @@
+use Iv;
+use cbc_hmac::{MacOrder, decrypt_cbc_hmac, encrypt_cbc_hmac};
+use error::AesError;
+use key::Key;
+
+/// Encrypts raw_bytes in CBC with PKCS7 padding under a random IV, then authenticates the IV and
+/// the ciphertext along with aad, returning the IV followed by the ciphertext and the tag.
+///
+/// This is the recommended way to use CBC with padding. Encrypt-then-MAC is the only order
+/// available, and decrypt only looks at the padding once the tag is verified, so that tampered
+/// ciphertexts can't be used as a padding oracle. enc_key and mac_key must be independent keys.
+#[cfg(feature = "rand")]
+pub fn encrypt(raw_bytes: &[u8], enc_key: &Key, mac_key: &Key, aad: &[u8]) -> Vec<u8> {
+    encrypt_with_iv(raw_bytes, enc_key, mac_key, &Iv::random(), aad)
+}
+
+/// Encrypts like encrypt with the given IV, which must be unpredictable and never reused, e.g.
+/// when random IVs are generated elsewhere.
+pub fn encrypt_with_iv(
+    raw_bytes: &[u8],
+    enc_key: &Key,
+    mac_key: &Key,
+    iv: &Iv,
+    aad: &[u8],
+) -> Vec<u8> {
+    encrypt_cbc_hmac(raw_bytes, enc_key, mac_key, iv, aad, MacOrder::EncryptThenMac)
+}
+
+/// Verifies the tag of the output of encrypt, and only then decrypts it and removes its padding.
+/// Tampered input fails with AuthenticationFailed, without being decrypted nor having its padding
+/// checked. There is no way to decrypt without verifying the tag at this layer.
+pub fn decrypt(
+    sealed: &[u8],
+    enc_key: &Key,
+    mac_key: &Key,
+    aad: &[u8],
+) -> Result<Vec<u8>, AesError> {
+    decrypt_cbc_hmac(sealed, enc_key, mac_key, aad, MacOrder::EncryptThenMac)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::{AESEncryptionOptions, BLOCK_SIZE, BlockCipherMode, encrypt_aes_128};
+    use pad::Padding;
+    use sha256::hmac_sha256;
+
+    use super::*;
+
+    const ENC_KEY: Key = Key([0x11; 16]);
+    const MAC_KEY: Key = Key([0x22; 16]);
+    const AAD: &[u8] = b"header";
+
+    fn iv() -> Iv {
+        Iv::from_bytes(&[0x33; BLOCK_SIZE])
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn round_trips_under_random_ivs() {
+        let first = encrypt(b"attack at dawn", &ENC_KEY, &MAC_KEY, AAD);
+        let second = encrypt(b"attack at dawn", &ENC_KEY, &MAC_KEY, AAD);
+
+        assert_ne!(first, second);
+        assert_eq!(decrypt(&first, &ENC_KEY, &MAC_KEY, AAD), Ok(b"attack at dawn".to_vec()));
+        assert_eq!(decrypt(&second, &ENC_KEY, &MAC_KEY, AAD), Ok(b"attack at dawn".to_vec()));
+    }
+
+    #[test]
+    fn rejects_tampering_before_checking_padding() {
+        let sealed = encrypt_with_iv(b"attack at dawn", &ENC_KEY, &MAC_KEY, &iv(), AAD);
+
+        // flipping the last byte of the IV flips the padding byte of the single plaintext block,
+        // which a padding check would report as InvalidPadding
+        let mut tampered = sealed.clone();
+        tampered[BLOCK_SIZE - 1] ^= 0x01;
+
+        assert!(matches!(
+            decrypt(&tampered, &ENC_KEY, &MAC_KEY, AAD),
+            Err(AesError::AuthenticationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn checks_padding_only_once_authenticated() {
+        // a correctly tagged ciphertext whose plaintext isn't padded
+        let iv = iv();
+        let mode = BlockCipherMode::CBC(&iv);
+        let options = AESEncryptionOptions::new(&mode, &Padding::None);
+        let cipher = encrypt_aes_128(&[0x42; BLOCK_SIZE], &ENC_KEY, &options);
+        let aad_bit_length = (AAD.len() as u64 * 8).to_be_bytes();
+        let mac = hmac_sha256(&MAC_KEY.0, &[AAD, &iv.to_bytes(), &cipher, &aad_bit_length].concat());
+        let sealed = [&iv.to_bytes()[..], &cipher[..], &mac[..16]].concat();
+
+        assert_eq!(decrypt(&sealed, &ENC_KEY, &MAC_KEY, AAD), Err(AesError::InvalidPadding));
+    }
+}
@@ -0,0 +1,151 @@
+/// Size in bytes of a SHA-256 digest.
+pub const DIGEST_SIZE: usize = 32;
+
+/// Size in bytes of the blocks processed by SHA-256.
+const BLOCK_LENGTH: usize = 64;
+
+/// SHA-256 message digest, as specified in FIPS 180-4, Sec. 6.2.
+pub fn sha256(message: &[u8]) -> [u8; DIGEST_SIZE] {
+    let mut state = H0;
+
+    // padding: a 1 bit, zeros up to 56 bytes modulo 64, then the bit length as a big-endian u64
+    let mut padded = message.to_vec();
+    padded.push(0x80);
+    while padded.len() % BLOCK_LENGTH != 56 {
+        padded.push(0x00);
+    }
+    padded.extend_from_slice(&((message.len() as u64).wrapping_mul(8)).to_be_bytes());
+
+    for chunk in padded.chunks_exact(BLOCK_LENGTH) {
+        process_chunk(&mut state, chunk);
+    }
+
+    let mut digest = [0u8; DIGEST_SIZE];
+    for (digest_part, word) in digest.chunks_exact_mut(4).zip(state.iter()) {
+        digest_part.copy_from_slice(&word.to_be_bytes());
+    }
+
+    digest
+}
+
+/// HMAC-SHA-256, as specified in https://www.rfc-editor.org/rfc/rfc2104
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; DIGEST_SIZE] {
+    // keys longer than a block are hashed, and all keys are zero padded to a block
+    let mut block_key = [0u8; BLOCK_LENGTH];
+    if key.len() > BLOCK_LENGTH {
+        block_key[..DIGEST_SIZE].copy_from_slice(&sha256(key));
+    } else {
+        block_key[..key.len()].copy_from_slice(key);
+    }
+
+    let inner_key: Vec<u8> = block_key.iter().map(|byte| byte ^ 0x36).collect();
+    let outer_key: Vec<u8> = block_key.iter().map(|byte| byte ^ 0x5c).collect();
+
+    let inner = sha256(&[&inner_key[..], message].concat());
+
+    sha256(&[&outer_key[..], &inner[..]].concat())
+}
+
+/// Initial hash value (Sec. 5.3.3).
+const H0: [u32; 8] = [
+    0x6a09_e667, 0xbb67_ae85, 0x3c6e_f372, 0xa54f_f53a, 0x510e_527f, 0x9b05_688c, 0x1f83_d9ab, 0x5be0_cd19,
+];
+
+/// Round constants (Sec. 4.2.2).
+const K: [u32; 64] = [
+    0x428a_2f98, 0x7137_4491, 0xb5c0_fbcf, 0xe9b5_dba5, 0x3956_c25b, 0x59f1_11f1, 0x923f_82a4, 0xab1c_5ed5,
+    0xd807_aa98, 0x1283_5b01, 0x2431_85be, 0x550c_7dc3, 0x72be_5d74, 0x80de_b1fe, 0x9bdc_06a7, 0xc19b_f174,
+    0xe49b_69c1, 0xefbe_4786, 0x0fc1_9dc6, 0x240c_a1cc, 0x2de9_2c6f, 0x4a74_84aa, 0x5cb0_a9dc, 0x76f9_88da,
+    0x983e_5152, 0xa831_c66d, 0xb003_27c8, 0xbf59_7fc7, 0xc6e0_0bf3, 0xd5a7_9147, 0x06ca_6351, 0x1429_2967,
+    0x27b7_0a85, 0x2e1b_2138, 0x4d2c_6dfc, 0x5338_0d13, 0x650a_7354, 0x766a_0abb, 0x81c2_c92e, 0x9272_2c85,
+    0xa2bf_e8a1, 0xa81a_664b, 0xc24b_8b70, 0xc76c_51a3, 0xd192_e819, 0xd699_0624, 0xf40e_3585, 0x106a_a070,
+    0x19a4_c116, 0x1e37_6c08, 0x2748_774c, 0x34b0_bcb5, 0x391c_0cb3, 0x4ed8_aa4a, 0x5b9c_ca4f, 0x682e_6ff3,
+    0x748f_82ee, 0x78a5_636f, 0x84c8_7814, 0x8cc7_0208, 0x90be_fffa, 0xa450_6ceb, 0xbef9_a3f7, 0xc671_78f2,
+];
+
+fn process_chunk(state: &mut [u32; 8], chunk: &[u8]) {
+    // message schedule (Sec. 6.2.2, step 1)
+    let mut w = [0u32; 64];
+    for (word, bytes) in w.iter_mut().zip(chunk.chunks_exact(4)) {
+        *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    for t in 16..64 {
+        let s0 = w[t - 15].rotate_right(7) ^ w[t - 15].rotate_right(18) ^ (w[t - 15] >> 3);
+        let s1 = w[t - 2].rotate_right(17) ^ w[t - 2].rotate_right(19) ^ (w[t - 2] >> 10);
+        w[t] = w[t - 16].wrapping_add(s0).wrapping_add(w[t - 7]).wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for t in 0..64 {
+        let big_sigma_1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h.wrapping_add(big_sigma_1).wrapping_add(ch).wrapping_add(K[t]).wrapping_add(w[t]);
+        let big_sigma_0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = big_sigma_0.wrapping_add(maj);
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (word, working) in state.iter_mut().zip([a, b, c, d, e, f, g, h].iter()) {
+        *word = word.wrapping_add(*working);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_hex(digest: &[u8]) -> String {
+        digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn digests_fips_180_examples() {
+        let test_cases: [(&str, &str); 3] = [
+            ("", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+            ("abc", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+            (
+                "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            ),
+        ];
+
+        for (message, expected_digest) in test_cases.iter() {
+            assert_eq!(to_hex(&sha256(message.as_bytes())), *expected_digest);
+        }
+    }
+
+    // RFC 4231, Sec. 4.2, 4.3 and 4.7
+    #[test]
+    fn computes_rfc_4231_hmacs() {
+        let test_cases: [(&[u8], &[u8], &str); 3] = [
+            (
+                &[0x0b; 20],
+                b"Hi There",
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            ),
+            (
+                b"Jefe",
+                b"what do ya want for nothing?",
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ),
+        ];
+
+        for (key, message, expected_mac) in test_cases.iter() {
+            assert_eq!(to_hex(&hmac_sha256(key, message)), *expected_mac);
+        }
+    }
+}
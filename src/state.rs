@@ -1,6 +1,8 @@
-use ::{math, Nb};
-use ::{Iv, S_BOX};
-use INVERSE_S_BOX;
+use std::fmt;
+
+use ::{constant_time, math, Nb};
+use Iv;
+use sbox::{inverse_s_box, s_box};
 
 /// Current state of the aes-128 cipher
 #[derive(Debug, Clone,PartialEq)]
@@ -11,13 +13,19 @@ pub struct State {
 impl State {
     pub fn from_part(part: &[u8]) -> State {
         let mut state = State::empty();
+        state.load_part(part);
+
+        state
+    }
+
+    /// Overwrites the State with part, allowing a single State to be reused across blocks. The
+    /// bytes fill the State column by column (Sec. 3.4), as in standard AES.
+    pub fn load_part(&mut self, part: &[u8]) {
         for r in 0..4 {
             for c in 0..Nb {
-                state.data[c][r] = part[r + 4 * c];
+                self.data[c][r] = part[r + 4 * c];
             }
         }
-
-        state
     }
 
     pub fn empty() -> State {
@@ -26,13 +34,31 @@ impl State {
 
     pub fn to_block(&self) -> Vec<u8> {
         let mut out = vec![0u8; 4 * Nb];
+        self.write_block(&mut out);
+
+        out
+    }
+
+    /// The State as the 4 x Nb matrix of Sec. 3.4, indexed by row then column, whereas it's
+    /// stored column by column.
+    pub fn to_matrix(&self) -> [[u8; Nb]; 4] {
+        let mut matrix = [[0u8; Nb]; 4];
+        for (r, row) in matrix.iter_mut().enumerate() {
+            for (c, byte) in row.iter_mut().enumerate() {
+                *byte = self.data[c][r];
+            }
+        }
+
+        matrix
+    }
+
+    /// Writes the State to out, which must hold at least 4 * Nb bytes, without allocating.
+    pub fn write_block(&self, out: &mut [u8]) {
         for r in 0..4 {
             for c in 0..Nb {
                 out[r + 4 * c] = self.data[c][r];
             }
         }
-
-        out
     }
 
     pub fn xor_with_state(&mut self, other: &State) {
@@ -40,9 +66,9 @@ impl State {
     }
 
     pub fn xor(&mut self, data: &[&[u8; 4]; Nb]) {
-        for r in 0..4 {
-            for c in 0..Nb {
-                self.data[r][c] ^= data[r][c];
+        for (word, other_word) in self.data.iter_mut().zip(data.iter()) {
+            for (byte, other_byte) in word.iter_mut().zip(other_word.iter()) {
+                *byte ^= other_byte;
             }
         }
     }
@@ -68,12 +94,30 @@ impl State {
     /// substitution table (S-box) that operates on each of the State bytes
     /// independently.
     pub fn sub_bytes(&mut self) {
-        self.sub_bytes_with_box(&S_BOX)
+        self.sub_bytes_with_box(s_box())
     }
 
     /// Transformation in the Inverse Cipher that is the inverse of SubBytes
     pub fn inv_sub_bytes(&mut self) {
-        self.sub_bytes_with_box(&INVERSE_S_BOX)
+        self.sub_bytes_with_box(inverse_s_box())
+    }
+
+    /// SubBytes where each S-box lookup reads the whole S-box, see constant_time::masked_lookup.
+    pub fn masked_sub_bytes(&mut self) {
+        self.masked_sub_bytes_with_box(s_box())
+    }
+
+    /// InvSubBytes where each lookup reads the whole inverse S-box, like masked_sub_bytes.
+    pub fn masked_inv_sub_bytes(&mut self) {
+        self.masked_sub_bytes_with_box(inverse_s_box())
+    }
+
+    fn masked_sub_bytes_with_box(&mut self, substitution_box: &[u8; 256]) {
+        for row in self.data.iter_mut() {
+            for byte in row.iter_mut() {
+                *byte = constant_time::masked_lookup(substitution_box, *byte);
+            }
+        }
     }
 
     fn sub_bytes_with_box(&mut self, substitution_box: &[u8; 256]) {
@@ -87,64 +131,95 @@ impl State {
     /// Transformation in the Cipher that processes the State by cyclically
     /// shifting the last three rows of the State by different offsets.
     pub fn shift_rows(&mut self) {
-        self.data = [
-            [self.data[0][0], self.data[1][1], self.data[2][2], self.data[3][3]],
-            [self.data[1][0], self.data[2][1], self.data[3][2], self.data[0][3]],
-            [self.data[2][0], self.data[3][1], self.data[0][2], self.data[1][3]],
-            [self.data[3][0], self.data[0][1], self.data[1][2], self.data[2][3]],
-        ]
+        self.rotate_row_left(1);
+        self.swap_row_halves(2);
+        self.rotate_row_right(3);
     }
 
     /// Transformation in the Inverse Cipher that is the inverse of ShiftRows
     pub fn inv_shift_rows(&mut self) {
-        self.data = [
-            [self.data[0][0], self.data[3][1], self.data[2][2], self.data[1][3]],
-            [self.data[1][0], self.data[0][1], self.data[3][2], self.data[2][3]],
-            [self.data[2][0], self.data[1][1], self.data[0][2], self.data[3][3]],
-            [self.data[3][0], self.data[2][1], self.data[1][2], self.data[0][3]],
-        ]
+        self.rotate_row_right(1);
+        self.swap_row_halves(2);
+        self.rotate_row_left(3);
+    }
+
+    fn rotate_row_left(&mut self, r: usize) {
+        let first = self.data[0][r];
+        for c in 0..Nb - 1 {
+            self.data[c][r] = self.data[c + 1][r];
+        }
+        self.data[Nb - 1][r] = first;
+    }
+
+    fn rotate_row_right(&mut self, r: usize) {
+        let last = self.data[Nb - 1][r];
+        for c in (1..Nb).rev() {
+            self.data[c][r] = self.data[c - 1][r];
+        }
+        self.data[0][r] = last;
+    }
+
+    /// Rotates the row by two, which swaps its first and second halves.
+    fn swap_row_halves(&mut self, r: usize) {
+        for c in 0..Nb / 2 {
+            let byte = self.data[c][r];
+            self.data[c][r] = self.data[c + Nb / 2][r];
+            self.data[c + Nb / 2][r] = byte;
+        }
     }
 
     /// Transformation in the Cipher that takes all of the columns of the
     /// State and mixes their data (independently of one another) to
     /// produce new columns.
+    ///
+    /// Each column is multiplied by {03}x^3 + {01}x^2 + {01}x + {02} (5.5) in place, with
+    /// s'_i = s_i ^ t ^ {02} • (s_i ^ s_i+1) where t = s_0 ^ s_1 ^ s_2 ^ s_3.
     pub fn mix_columns(&mut self) {
-        let fixed_polynomial: &[&[u8]] = &[
-            &[0x02, 0x03, 0x01, 0x01],
-            &[0x01, 0x02, 0x03, 0x01],
-            &[0x01, 0x01, 0x02, 0x03],
-            &[0x03, 0x01, 0x01, 0x02],
-        ];
-        self.mix_columns_using_substitution_matrix(fixed_polynomial)
+        for column in self.data.iter_mut() {
+            let t = column[0] ^ column[1] ^ column[2] ^ column[3];
+            let first = column[0];
+
+            column[0] ^= t ^ math::xtime(column[0] ^ column[1]);
+            column[1] ^= t ^ math::xtime(column[1] ^ column[2]);
+            column[2] ^= t ^ math::xtime(column[2] ^ column[3]);
+            column[3] ^= t ^ math::xtime(column[3] ^ first);
+        }
     }
 
     /// Transformation in the Inverse Cipher that is the inverse of MixColumns
+    ///
+    /// {0b}x^3 + {0d}x^2 + {09}x + {0e} (5.10) is ({04}x^2 + {05}) multiplied by MixColumns'
+    /// polynomial, so each column is first multiplied by {04}x^2 + {05}, then mixed.
     pub fn inv_mix_columns(&mut self) {
-        let fixed_polynomial: &[&[u8]] = &[
-            &[0x0e, 0x0b, 0x0d, 0x09],
-            &[0x09, 0x0e, 0x0b, 0x0d],
-            &[0x0d, 0x09, 0x0e, 0x0b],
-            &[0x0b, 0x0d, 0x09, 0x0e],
-        ];
+        for column in self.data.iter_mut() {
+            let u = math::xtime(math::xtime(column[0] ^ column[2]));
+            let v = math::xtime(math::xtime(column[1] ^ column[3]));
+
+            column[0] ^= u;
+            column[1] ^= v;
+            column[2] ^= u;
+            column[3] ^= v;
+        }
 
-        self.mix_columns_using_substitution_matrix(fixed_polynomial)
+        self.mix_columns()
     }
+}
 
-    // TODO: Find a way to do this without using a temporary array
-    fn mix_columns_using_substitution_matrix(&mut self, substitution_matrix: &[&[u8]]) {
-        let mut mixed_columns = [[0; 4]; Nb];
-        for c in 0..Nb {
-            for r in 0..4 {
-                let mut multiplications_xor = 0;
-                for i in 0..4 {
-                    multiplications_xor ^= math::multiply_in_g(substitution_matrix[r][i],
-                                                               self.data[c][i])
-                }
-                mixed_columns[c][r] = multiplications_xor
+/// Prints the State as the matrix of Sec. 3.4, one row per line, e.g.
+/// 32 88 31 e0
+/// 43 5a 31 37
+/// f6 30 98 07
+/// a8 8d a2 34
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (r, row) in self.to_matrix().iter().enumerate() {
+            if r > 0 {
+                writeln!(f)?;
             }
+            write!(f, "{:02x} {:02x} {:02x} {:02x}", row[0], row[1], row[2], row[3])?;
         }
 
-        self.data = mixed_columns
+        Ok(())
     }
 }
 
@@ -222,6 +297,25 @@ mod tests {
         assert_eq!(state.to_block(), part.to_vec());
     }
 
+    #[test]
+    fn load_part_overwrites_state() {
+        let part = &[0x42; 16];
+        let mut state = SOME_STATE;
+
+        state.load_part(part);
+
+        assert_eq!(state, State::from_part(part));
+    }
+
+    #[test]
+    fn write_block_writes_block() {
+        let mut out = [0u8; 16];
+
+        SOME_STATE.write_block(&mut out);
+
+        assert_eq!(out.to_vec(), SOME_STATE.to_block());
+    }
+
     #[test]
     fn xor_with_state_xors_with_state() {
         let mut base_state = SOME_STATE;
@@ -423,31 +517,85 @@ mod tests {
         assert_eq!(state, expected_state);
     }
 
-    #[test]
-    fn mix_columns_using_substitution_matrix_mixes_columns_using_substitution_matrix() {
-        let mut state = create_state([
-            [0xbd, 0x6e, 0x7c, 0x3d],
-            [0xf2, 0xb5, 0x77, 0x9e],
-            [0x0b, 0x61, 0x21, 0x6e],
-            [0x8b, 0x10, 0xb6, 0x89]
-        ]);
+    /// Multiplies each column by the matrix, as in (5.6) and (5.9)
+    fn multiply_columns_by_matrix(state: &State, matrix: &[[u8; 4]; 4]) -> State {
+        let mut mixed = EMPTY_STATE;
+        for (mixed_column, column) in mixed.data.iter_mut().zip(state.data.iter()) {
+            for (mixed_byte, matrix_row) in mixed_column.iter_mut().zip(matrix.iter()) {
+                for (matrix_value, state_value) in matrix_row.iter().zip(column.iter()) {
+                    *mixed_byte ^= math::multiply_in_g(*matrix_value, *state_value);
+                }
+            }
+        }
 
-        let expected_state = create_state([
-            [0x47, 0x73, 0xb9, 0x1f],
-            [0xf7, 0x2f, 0x35, 0x43],
-            [0x61, 0xcb, 0x01, 0x8e],
-            [0xa1, 0xe6, 0xcf, 0x2c]
-        ]);
+        mixed
+    }
 
-        let substitution_matrix: &[&[u8]] = &[
-            &[0x0e, 0x0b, 0x0d, 0x09],
-            &[0x09, 0x0e, 0x0b, 0x0d],
-            &[0x0d, 0x09, 0x0e, 0x0b],
-            &[0x0b, 0x0d, 0x09, 0x0e],
+    #[test]
+    fn in_place_mix_columns_match_matrix_multiplication() {
+        let mix_columns_matrix = [
+            [0x02, 0x03, 0x01, 0x01],
+            [0x01, 0x02, 0x03, 0x01],
+            [0x01, 0x01, 0x02, 0x03],
+            [0x03, 0x01, 0x01, 0x02],
+        ];
+        let inv_mix_columns_matrix = [
+            [0x0e, 0x0b, 0x0d, 0x09],
+            [0x09, 0x0e, 0x0b, 0x0d],
+            [0x0d, 0x09, 0x0e, 0x0b],
+            [0x0b, 0x0d, 0x09, 0x0e],
         ];
 
-        state.mix_columns_using_substitution_matrix(substitution_matrix);
+        for seed in 0..=255u8 {
+            let mut state = EMPTY_STATE;
+            for (i, byte) in state.data.iter_mut().flat_map(|column| column.iter_mut()).enumerate() {
+                *byte = seed.wrapping_mul(31).wrapping_add(i as u8 * 17);
+            }
+            let mut mixed = state.clone();
+            let mut inv_mixed = state.clone();
+
+            mixed.mix_columns();
+            inv_mixed.inv_mix_columns();
 
-        assert_eq!(state, expected_state);
+            assert_eq!(mixed, multiply_columns_by_matrix(&state, &mix_columns_matrix));
+            assert_eq!(inv_mixed, multiply_columns_by_matrix(&state, &inv_mix_columns_matrix));
+        }
+    }
+
+    #[test]
+    fn inv_shift_rows_reverses_shift_rows() {
+        let mut state = SOME_STATE;
+
+        state.shift_rows();
+        state.inv_shift_rows();
+
+        assert_eq!(state, SOME_STATE);
+    }
+
+    // Appendix B, the input of the cipher
+    const APPENDIX_B_INPUT: [u8; 16] = [
+        0x32, 0x43, 0xf6, 0xa8,
+        0x88, 0x5a, 0x30, 0x8d,
+        0x31, 0x31, 0x98, 0xa2,
+        0xe0, 0x37, 0x07, 0x34
+    ];
+
+    #[test]
+    fn to_matrix_lays_out_rows_of_the_state() {
+        let state = State::from_part(&APPENDIX_B_INPUT);
+
+        assert_eq!(state.to_matrix(), [
+            [0x32, 0x88, 0x31, 0xe0],
+            [0x43, 0x5a, 0x31, 0x37],
+            [0xf6, 0x30, 0x98, 0x07],
+            [0xa8, 0x8d, 0xa2, 0x34],
+        ]);
+    }
+
+    #[test]
+    fn displays_state_as_matrix() {
+        let state = State::from_part(&APPENDIX_B_INPUT);
+
+        assert_eq!(state.to_string(), "32 88 31 e0\n43 5a 31 37\nf6 30 98 07\na8 8d a2 34");
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,84 @@
+use BLOCK_SIZE;
+use constant_time::verify_eq;
+
+/// Authentication tag of BLOCK_SIZE bytes, as produced by the authenticated modes and the MACs.
+/// Tags are compared in constant time: == inspects every byte regardless of where the first
+/// difference is, so that comparing a received tag to a computed one doesn't leak through its
+/// timing how much of the tag is right.
+#[derive(Clone, Copy, Debug)]
+pub struct Tag([u8; BLOCK_SIZE]);
+
+impl Tag {
+    pub const fn from_bytes(bytes: &[u8; BLOCK_SIZE]) -> Self {
+        Tag(*bytes)
+    }
+
+    pub fn to_bytes(&self) -> [u8; BLOCK_SIZE] {
+        self.0
+    }
+
+    pub fn as_bytes(&self) -> &[u8; BLOCK_SIZE] {
+        &self.0
+    }
+}
+
+/// Constant-time equality, see Tag.
+impl PartialEq for Tag {
+    fn eq(&self, other: &Tag) -> bool {
+        verify_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Tag {}
+
+impl From<[u8; BLOCK_SIZE]> for Tag {
+    fn from(bytes: [u8; BLOCK_SIZE]) -> Self {
+        Tag(bytes)
+    }
+}
+
+impl From<Tag> for [u8; BLOCK_SIZE] {
+    fn from(tag: Tag) -> Self {
+        tag.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_tags_are_equal() {
+        let tag = Tag::from([0x42; BLOCK_SIZE]);
+
+        assert_eq!(tag, Tag::from_bytes(&[0x42; BLOCK_SIZE]));
+    }
+
+    #[test]
+    fn tags_differing_in_any_byte_are_unequal() {
+        let tag = Tag::from([0x42; BLOCK_SIZE]);
+
+        // the comparison doesn't stop at the first byte, and still catches a difference in the last
+        for i in 0..BLOCK_SIZE {
+            let mut bytes = [0x42; BLOCK_SIZE];
+            bytes[i] ^= 0x01;
+
+            assert_ne!(tag, Tag::from(bytes));
+        }
+    }
+
+    #[test]
+    fn converts_to_and_from_bytes() {
+        let bytes = [
+            0x00, 0x01, 0x02, 0x03,
+            0x04, 0x05, 0x06, 0x07,
+            0x08, 0x09, 0x0a, 0x0b,
+            0x0c, 0x0d, 0x0e, 0x0f
+        ];
+        let tag = Tag::from(bytes);
+
+        assert_eq!(tag.to_bytes(), bytes);
+        assert_eq!(tag.as_bytes(), &bytes);
+        assert_eq!(<[u8; BLOCK_SIZE]>::from(tag), bytes);
+    }
+}
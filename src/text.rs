@@ -0,0 +1,62 @@
+use std::ops::Deref;
+
+/// Defines a newtype over the bytes of a message, so that plaintexts and ciphertexts can't be
+/// mixed up. It derefs to [u8] and converts from and into Vec<u8>, the raw bytes escape hatch.
+macro_rules! message_bytes {
+    ($(#[$attribute:meta])* $name:ident) => {
+        $(#[$attribute])*
+        #[derive(PartialEq, Debug, Clone)]
+        pub struct $name(pub Vec<u8>);
+
+        impl $name {
+            pub fn into_inner(self) -> Vec<u8> {
+                self.0
+            }
+        }
+
+        impl Deref for $name {
+            type Target = [u8];
+
+            fn deref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        impl From<Vec<u8>> for $name {
+            fn from(bytes: Vec<u8>) -> Self {
+                $name(bytes)
+            }
+        }
+
+        impl<'a> From<&'a [u8]> for $name {
+            fn from(bytes: &'a [u8]) -> Self {
+                $name(bytes.to_vec())
+            }
+        }
+    };
+}
+
+message_bytes!(
+    /// Unencrypted message bytes.
+    Plaintext
+);
+message_bytes!(
+    /// Encrypted message bytes.
+    Ciphertext
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_from_and_into_bytes() {
+        let plaintext = Plaintext::from(vec![0x01, 0x02]);
+        let ciphertext: Ciphertext = (&[0x03, 0x04][..]).into();
+
+        assert_eq!(plaintext.len(), 2);
+        assert_eq!(&ciphertext[..], &[0x03, 0x04]);
+        assert_eq!(plaintext.into_inner(), vec![0x01, 0x02]);
+        assert_eq!(ciphertext.into_inner(), vec![0x03, 0x04]);
+    }
+}
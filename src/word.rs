@@ -1,20 +1,40 @@
-use S_BOX;
+use math::xtime;
+use sbox::s_box;
 
 /// Function used in the Key Expansion routine that takes a four-byte
 /// word and performs a cyclic permutation.
-pub fn rot_word(word: &[u8]) -> Vec<u8> {
-    assert_eq!(word.len(), 4);
-
-    [&word[1..], &[word[0]]].concat()
+pub fn rot_word(word: &[u8; 4]) -> [u8; 4] {
+    [word[1], word[2], word[3], word[0]]
 }
 
 /// Function used in the Key Expansion routine that takes a four-byte
 /// input word and applies an S-box to each of the four bytes to
 /// produce an output word.
-pub fn sub_word(word: &[u8]) -> Vec<u8> {
-    assert_eq!(word.len(), 4);
+pub fn sub_word(word: &[u8; 4]) -> [u8; 4] {
+    let s_box = s_box();
 
-    word.iter().map(|byte| S_BOX[*byte as usize]).collect()
+    [
+        s_box[word[0] as usize],
+        s_box[word[1] as usize],
+        s_box[word[2] as usize],
+        s_box[word[3] as usize],
+    ]
+}
+
+/// InvMixColumns (Sec. 5.3.3) of a single column, such as a word of a round key of the
+/// equivalent inverse cipher (Sec. 5.3.5), computed like State::inv_mix_columns.
+pub fn inv_mix_column(word: &[u8; 4]) -> [u8; 4] {
+    let u = xtime(xtime(word[0] ^ word[2]));
+    let v = xtime(xtime(word[1] ^ word[3]));
+    let c = [word[0] ^ u, word[1] ^ v, word[2] ^ u, word[3] ^ v];
+    let t = c[0] ^ c[1] ^ c[2] ^ c[3];
+
+    [
+        c[0] ^ t ^ xtime(c[0] ^ c[1]),
+        c[1] ^ t ^ xtime(c[1] ^ c[2]),
+        c[2] ^ t ^ xtime(c[2] ^ c[3]),
+        c[3] ^ t ^ xtime(c[3] ^ c[0]),
+    ]
 }
 
 #[cfg(test)]
@@ -23,20 +43,29 @@ mod tests {
 
     #[test]
     fn rot_word_rotates_word() {
-        let word: &[u8] = &[0, 1, 2, 3];
-        let expected_word: &[u8] = &[1, 2, 3, 0];
+        let word = [0, 1, 2, 3];
+        let expected_word = [1, 2, 3, 0];
 
-        let actual_word = rot_word(word);
-        assert_eq!(actual_word.as_slice(), expected_word);
+        let actual_word = rot_word(&word);
+        assert_eq!(actual_word, expected_word);
     }
 
     #[test]
     fn sub_word_substitutes_word() {
-        let word: &[u8] = &[0, 1, 2, 3];
-        let expected_word: &[u8] = &[0x63, 0x7c, 0x77, 0x7b];
+        let word = [0, 1, 2, 3];
+        let expected_word = [0x63, 0x7c, 0x77, 0x7b];
 
-        let actual_word = sub_word(word);
+        let actual_word = sub_word(&word);
 
-        assert_eq!(actual_word.as_slice(), expected_word);
+        assert_eq!(actual_word, expected_word);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn inv_mix_column_inv_mixes_column() {
+        // first column of the InvMixColumns example of state.rs
+        let word = [0xbd, 0x6e, 0x7c, 0x3d];
+        let expected_word = [0x47, 0x73, 0xb9, 0x1f];
+
+        assert_eq!(inv_mix_column(&word), expected_word);
+    }
+}
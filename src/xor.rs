@@ -1,8 +1,11 @@
+/// XORs input with key repeated over its length. Only tests use it, the key expansion working on
+/// [u8; 4] words in place.
+#[cfg(test)]
 pub fn fixed_key_xor(input: &[u8], key: &[u8]) -> Vec<u8> {
     let mut result: Vec<u8> = Vec::with_capacity(input.len());
 
     for (i, item) in input.iter().enumerate() {
-        let key_byte = if key.len() > 0 {
+        let key_byte = if !key.is_empty() {
             key[i % key.len()]
         } else {
             0
@@ -13,9 +16,23 @@ pub fn fixed_key_xor(input: &[u8], key: &[u8]) -> Vec<u8> {
     result
 }
 
+/// XORs two slices of the same length byte by byte. Panics if their lengths differ.
+pub fn xor_bytes(a: &[u8], b: &[u8]) -> Vec<u8> {
+    assert_eq!(a.len(), b.len(), "Slices must be of the same length.");
+
+    a.iter().zip(b.iter()).map(|(a_byte, b_byte)| a_byte ^ b_byte).collect()
+}
+
+/// XORs data with other in place, byte by byte, up to the length of the shorter one.
+pub fn xor_in_place(data: &mut [u8], other: &[u8]) {
+    for (byte, other_byte) in data.iter_mut().zip(other.iter()) {
+        *byte ^= other_byte;
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use xor::fixed_key_xor;
+    use xor::{fixed_key_xor, xor_bytes, xor_in_place};
 
     #[test]
     fn rotates_xor_key() {
@@ -24,7 +41,7 @@ mod tests {
             key: &'a [u8],
             expected: &'a [u8],
         }
-        let test_cases = vec![
+        let test_cases = [
             TestCase {
                 input: &[0x01, 0x02, 0x03, 0x04, 0x05],
                 key: &[0x01, 0x02, 0x03],
@@ -38,7 +55,7 @@ mod tests {
         ];
 
         for case in test_cases.iter() {
-            let result = fixed_key_xor(&case.input, &case.key);
+            let result = fixed_key_xor(case.input, case.key);
             assert_eq!(result, case.expected);
         }
     }
@@ -50,7 +67,7 @@ mod tests {
             key: &'a [u8],
             expected: &'a [u8],
         }
-        let test_cases = vec![
+        let test_cases = [
             TestCase {
                 input: &[0x01, 0x02, 0x03, 0x04],
                 key: &[0x01],
@@ -64,7 +81,7 @@ mod tests {
         ];
 
         for case in test_cases.iter() {
-            let result = fixed_key_xor(&case.input, &case.key);
+            let result = fixed_key_xor(case.input, case.key);
             assert_eq!(result, case.expected);
         }
     }
@@ -101,4 +118,25 @@ mod tests {
         let result = fixed_key_xor(input, key);
         assert_eq!(result, expected);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn xor_in_place_xors_up_to_shorter_length() {
+        let mut data = [0x01, 0x02, 0x03];
+
+        xor_in_place(&mut data, &[0x01, 0x01]);
+
+        assert_eq!(data, [0x00, 0x03, 0x03]);
+    }
+
+    #[test]
+    fn xor_bytes_xors_slices_of_same_length() {
+        assert_eq!(xor_bytes(&[0x01, 0xff, 0x0f], &[0x03, 0x0f, 0x0f]), vec![0x02, 0xf0, 0x00]);
+        assert!(xor_bytes(&[], &[]).is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn xor_bytes_panics_on_different_lengths() {
+        xor_bytes(&[0x01, 0x02], &[0x01]);
+    }
+}
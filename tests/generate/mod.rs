@@ -65,7 +65,7 @@ mod tests {
         let bytes = generate_bytes_for_length(length);
 
         assert_some_randomness(&bytes);
-        assert_eq!(bytes.len(), length as usize);
+        assert_eq!(bytes.len(), length);
     }
 
     fn assert_some_randomness<T>(random_bytes: &[T]) {
@@ -0,0 +1,266 @@
+extern crate aes_ndlr as aes;
+
+use std::collections::HashMap;
+
+use aes::{AESEncryptionOptions, Block, BlockCipherMode, decrypt_aes_128, encrypt_aes_128, pad::Padding};
+use aes::key::Key;
+
+/// A single vector of a NIST AESAVS `.rsp` file.
+struct KnownAnswer {
+    count: String,
+    encrypt: bool,
+    key: Vec<u8>,
+    iv: Option<Vec<u8>>,
+    plaintext: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+fn parse_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+/// Parses the `[ENCRYPT]`/`[DECRYPT]` sections of a `.rsp` file. Records are `NAME = value` lines
+/// separated by blank lines; comments start with `#`.
+fn parse_rsp(contents: &str) -> Vec<KnownAnswer> {
+    let mut known_answers = vec![];
+    let mut encrypt = true;
+    let mut fields: HashMap<&str, &str> = HashMap::new();
+
+    for line in contents.lines().map(str::trim).chain(Some("")) {
+        if line.starts_with('#') {
+            continue;
+        }
+        if line.is_empty() {
+            if !fields.is_empty() {
+                known_answers.push(KnownAnswer {
+                    count: fields["COUNT"].to_string(),
+                    encrypt,
+                    key: parse_hex(fields["KEY"]),
+                    iv: fields.get("IV").map(|iv| parse_hex(iv)),
+                    plaintext: parse_hex(fields["PLAINTEXT"]),
+                    ciphertext: parse_hex(fields["CIPHERTEXT"]),
+                });
+                fields.clear();
+            }
+            continue;
+        }
+        if line.starts_with('[') {
+            encrypt = line == "[ENCRYPT]";
+            continue;
+        }
+
+        let mut name_and_value = line.splitn(2, '=');
+        let name = name_and_value.next().unwrap().trim();
+        let value = name_and_value.next().unwrap().trim();
+        fields.insert(name, value);
+    }
+
+    known_answers
+}
+
+fn to_block(bytes: &[u8]) -> Block {
+    let mut array = [0; 16];
+    array.copy_from_slice(bytes);
+
+    Block::from_bytes(&array)
+}
+
+fn assert_known_answers_pass(file_name: &str, contents: &str) {
+    let known_answers = parse_rsp(contents);
+    assert!(!known_answers.is_empty(), "{} has no vectors", file_name);
+
+    for known_answer in known_answers {
+        let mut key = [0; 16];
+        key.copy_from_slice(&known_answer.key);
+        let key = &Key(key);
+        let iv = known_answer.iv.as_ref().map(|iv| to_block(iv));
+        let mode = match iv {
+            Some(ref iv) => BlockCipherMode::CBC(iv),
+            None => BlockCipherMode::ECB,
+        };
+
+        if known_answer.encrypt {
+            let actual = encrypt_aes_128(
+                &known_answer.plaintext,
+                key,
+                &AESEncryptionOptions::new(&mode, &Padding::None),
+            );
+            assert_eq!(
+                actual, known_answer.ciphertext,
+                "{} ENCRYPT COUNT = {}", file_name, known_answer.count
+            );
+        } else {
+            let actual = decrypt_aes_128(&known_answer.ciphertext, key, &mode);
+            assert_eq!(
+                actual, known_answer.plaintext,
+                "{} DECRYPT COUNT = {}", file_name, known_answer.count
+            );
+        }
+    }
+}
+
+macro_rules! known_answer_test {
+    ($name:ident, $file_name:expr) => {
+        #[test]
+        fn $name() {
+            assert_known_answers_pass($file_name, include_str!(concat!("kat/", $file_name)));
+        }
+    };
+}
+
+known_answer_test!(ecb_gfsbox_128, "ECBGFSbox128.rsp");
+known_answer_test!(ecb_var_key_128, "ECBVarKey128.rsp");
+known_answer_test!(ecb_var_txt_128, "ECBVarTxt128.rsp");
+known_answer_test!(cbc_gfsbox_128, "CBCGFSbox128.rsp");
+known_answer_test!(cbc_var_key_128, "CBCVarKey128.rsp");
+known_answer_test!(cbc_var_txt_128, "CBCVarTxt128.rsp");
+
+/// Mode of an aes_kat! vector. Cbc takes the IV, and Ctr the first 128-bit counter block, whose
+/// last 8 bytes are the big-endian counter.
+enum KatMode {
+    Ecb,
+    Cbc,
+    Ctr,
+}
+
+/// Checks a single vector of hex strings both ways: plaintext encrypts to ciphertext, and
+/// ciphertext decrypts back to plaintext.
+fn assert_kat(
+    name: &str,
+    mode: KatMode,
+    key: &str,
+    iv: Option<&str>,
+    plaintext: &str,
+    ciphertext: &str,
+) {
+    let mut key_bytes = [0; 16];
+    key_bytes.copy_from_slice(&parse_hex(key));
+    let key = &Key(key_bytes);
+    let iv = iv.map(parse_hex);
+    let (plaintext, ciphertext) = (parse_hex(plaintext), parse_hex(ciphertext));
+
+    let iv_block;
+    let mut nonce = [0; 8];
+    let mode = match mode {
+        KatMode::Ecb => BlockCipherMode::ECB,
+        KatMode::Cbc => {
+            iv_block = to_block(iv.as_ref().expect("Cbc vectors need an iv."));
+            BlockCipherMode::CBC(&iv_block)
+        }
+        KatMode::Ctr => {
+            let counter_block = iv.as_ref().expect("Ctr vectors need an iv.");
+            nonce.copy_from_slice(&counter_block[..8]);
+            let mut counter = [0; 8];
+            counter.copy_from_slice(&counter_block[8..]);
+            BlockCipherMode::CTRWithCounter(&nonce, u64::from_be_bytes(counter))
+        }
+    };
+    let options = AESEncryptionOptions::new(&mode, &Padding::None);
+
+    assert_eq!(encrypt_aes_128(&plaintext, key, &options), ciphertext, "{} encryption", name);
+    let decrypted = match mode {
+        BlockCipherMode::CTRWithCounter(..) => encrypt_aes_128(&ciphertext, key, &options),
+        _ => decrypt_aes_128(&ciphertext, key, &mode),
+    };
+    assert_eq!(decrypted, plaintext, "{} decryption", name);
+}
+
+/// Expands known answer vectors, given as hex strings, into tests checking both encryption and
+/// decryption. Long values can be split with concat!. To add a vector:
+///
+/// aes_kat! {
+///     test_name {
+///         key: "...",
+///         iv: "...", // Cbc and Ctr only
+///         plaintext: "...",
+///         ciphertext: "...",
+///         mode: Ecb,
+///     }
+/// }
+macro_rules! aes_kat {
+    ($(
+        $name:ident {
+            key: $key:expr,
+            $(iv: $iv:expr,)?
+            plaintext: $plaintext:expr,
+            ciphertext: $ciphertext:expr,
+            mode: $mode:ident $(,)?
+        }
+    )*) => {
+        $(
+            #[test]
+            fn $name() {
+                assert_kat(
+                    stringify!($name),
+                    KatMode::$mode,
+                    $key,
+                    aes_kat!(@iv $($iv)?),
+                    $plaintext,
+                    $ciphertext,
+                );
+            }
+        )*
+    };
+    (@iv) => { None };
+    (@iv $iv:expr) => { Some($iv) };
+}
+
+aes_kat! {
+    // FIPS-197, Appendix C.1
+    fips_197_appendix_c_1 {
+        key: "000102030405060708090a0b0c0d0e0f",
+        plaintext: "00112233445566778899aabbccddeeff",
+        ciphertext: "69c4e0d86a7b0430d8cdb78070b4c55a",
+        mode: Ecb,
+    }
+
+    // SP 800-38A, F.2.1 and F.2.2
+    sp_800_38a_cbc_aes128 {
+        key: "2b7e151628aed2a6abf7158809cf4f3c",
+        iv: "000102030405060708090a0b0c0d0e0f",
+        plaintext: concat!(
+            "6bc1bee22e409f96e93d7e117393172a", "ae2d8a571e03ac9c9eb76fac45af8e51",
+            "30c81c46a35ce411e5fbc1191a0a52ef", "f69f2445df4f9b17ad2b417be66c3710"
+        ),
+        ciphertext: concat!(
+            "7649abac8119b246cee98e9b12e9197d", "5086cb9b507219ee95db113a917678b2",
+            "73bed6b8e3c1743b7116e69e22229516", "3ff1caa1681fac09120eca307586e1a7"
+        ),
+        mode: Cbc,
+    }
+
+    // SP 800-38A, F.5.1 and F.5.2, the first block using the initial counter block unchanged
+    sp_800_38a_ctr_aes128 {
+        key: "2b7e151628aed2a6abf7158809cf4f3c",
+        iv: "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff",
+        plaintext: concat!(
+            "6bc1bee22e409f96e93d7e117393172a", "ae2d8a571e03ac9c9eb76fac45af8e51",
+            "30c81c46a35ce411e5fbc1191a0a52ef", "f69f2445df4f9b17ad2b417be66c3710"
+        ),
+        ciphertext: concat!(
+            "874d6191b620e3261bef6864990db6ce", "9806f66b7970fdff8617187bb9fffdff",
+            "5ae4df3edbd5d35e5b4f09020db03eab", "1e031dda2fbe03d1792170a0f3009cee"
+        ),
+        mode: Ctr,
+    }
+}
+
+#[test]
+fn parses_rsp_records_and_sections() {
+    let contents = "# comment\r\n\r\n[ENCRYPT]\r\n\r\nCOUNT = 0\r\nKEY = 00ff\r\nIV = 01\r\n\
+        PLAINTEXT = 02\r\nCIPHERTEXT = 03\r\n\r\n[DECRYPT]\r\n\r\nCOUNT = 0\r\nKEY = 00ff\r\n\
+        CIPHERTEXT = 03\r\nPLAINTEXT = 02";
+
+    let known_answers = parse_rsp(contents);
+
+    assert_eq!(known_answers.len(), 2);
+    assert!(known_answers[0].encrypt);
+    assert_eq!(known_answers[0].key, vec![0x00, 0xff]);
+    assert_eq!(known_answers[0].iv, Some(vec![0x01]));
+    assert!(!known_answers[1].encrypt);
+    assert_eq!(known_answers[1].iv, None);
+    assert_eq!(known_answers[1].ciphertext, vec![0x03]);
+}
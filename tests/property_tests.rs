@@ -0,0 +1,57 @@
+extern crate aes_ndlr as aes;
+extern crate proptest;
+
+use aes::{AESEncryptionOptions, BlockCipherMode, State, cipher, encrypt_aes_128, key::Key, pad::Padding};
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+proptest! {
+    #[test]
+    fn ecb_is_deterministic(key in any::<[u8; 16]>(), raw in vec(any::<u8>(), 0..256)) {
+        let key = Key(key);
+        let options = AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::PKCS7);
+
+        prop_assert_eq!(encrypt_aes_128(&raw, &key, &options), encrypt_aes_128(&raw, &key, &options));
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn cbc_with_random_iv_is_not_deterministic(
+        key in any::<[u8; 16]>(),
+        raw in vec(any::<u8>(), 0..256),
+    ) {
+        let key = Key(key);
+
+        let first = aes::encrypt_cbc_with_random_iv(&raw, &key, &Padding::PKCS7).unwrap();
+        let second = aes::encrypt_cbc_with_random_iv(&raw, &key, &Padding::PKCS7).unwrap();
+
+        prop_assert_ne!(first.iv_used, second.iv_used);
+        prop_assert_ne!(first.ciphertext, second.ciphertext);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn auto_is_not_deterministic(key in any::<[u8; 16]>(), raw in vec(any::<u8>(), 0..256)) {
+        let key = Key(key);
+
+        let first = aes::auto::encrypt_auto(&raw, &key).unwrap();
+        let second = aes::auto::encrypt_auto(&raw, &key).unwrap();
+
+        prop_assert_ne!(first, second);
+    }
+
+    #[test]
+    fn decrypting_with_inverse_key_expansion_matches_inverse_cipher(
+        key in any::<[u8; 16]>(),
+        block in any::<[u8; 16]>(),
+    ) {
+        let key = Key(key);
+        let mut expected = State::from_part(&block);
+        let mut actual = State::from_part(&block);
+
+        cipher::inv_cipher(&mut expected, &key.do_key_expansion().0);
+        cipher::eq_inv_cipher(&mut actual, &key.do_inv_key_expansion().0);
+
+        prop_assert_eq!(actual, expected);
+    }
+}
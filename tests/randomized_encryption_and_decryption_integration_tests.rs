@@ -2,12 +2,11 @@ extern crate aes_ndlr as aes;
 
 use std::ops::Range;
 
-use aes::{AESEncryptionOptions, BlockCipherMode, decrypt_aes_128, encrypt_aes_128, pad::Padding};
+use aes::{AESEncryptionOptions, BLOCK_SIZE, BlockCipherMode, decrypt_aes_128, encrypt_aes_128, pad::Padding};
 use generate::{generate_bytes_for_length, generate_iv, generate_key};
 
 mod generate;
 
-const BLOCK_SIZE: usize = 16;
 const TEST_CASES: Range<usize> = 0..100;
 
 #[test]
@@ -19,7 +18,7 @@ fn encrypt_and_decrypt_ecb() {
         let iv = &generate_iv();
 
         let cipher = encrypt_aes_128(
-            &raw,
+            raw,
             key,
             &AESEncryptionOptions::new(
                 &BlockCipherMode::CBC(iv),
@@ -41,7 +40,7 @@ fn encrypt_and_decrypt_cbc() {
         let iv = &generate_iv();
 
         let cipher = encrypt_aes_128(
-            &raw,
+            raw,
             key,
             &AESEncryptionOptions::new(
                 &BlockCipherMode::CBC(iv),
@@ -77,8 +76,8 @@ fn encrypt_and_decrypt_ctr() {
             &Padding::None,
         );
 
-        let ciphered = encrypt_aes_128(&raw, &key, &options);
-        let deciphered = encrypt_aes_128(&ciphered, &key, &options);
+        let ciphered = encrypt_aes_128(raw, key, options);
+        let deciphered = encrypt_aes_128(&ciphered, key, options);
 
         assert_eq!(deciphered, raw);
     }